@@ -1,4 +1,4 @@
-use crate::error::VividResult;
+use crate::error::{VividError, VividResult};
 
 #[cfg(all(windows, target_pointer_width = "32"))]
 pub const LIBRARY_NAME: &[u8; 13] = b"atiadlxx.dll\0";
@@ -22,6 +22,14 @@ impl super::VibranceAdapter for Amd {
         todo!()
     }
 
+    fn set_display_vibrance(&mut self, _display_name: &str, _vibrance: u8) -> VividResult<u8> {
+        Err(VividError::UnsupportedAdapter("AMD"))
+    }
+
+    fn get_display_vibrance(&mut self, _display_name: &str) -> VividResult<u8> {
+        Err(VividError::UnsupportedAdapter("AMD"))
+    }
+
     fn get_sku(&mut self) -> VividResult<String> {
         todo!()
     }
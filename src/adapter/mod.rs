@@ -27,6 +27,8 @@ fn dll_exists(path: *const winapi::ctypes::c_char) -> bool {
 pub trait VibranceAdapter: std::fmt::Debug {
     fn set_vibrance(&mut self, vibrance: u8) -> VividResult<u8>;
     fn get_vibrance(&mut self) -> VividResult<u8>;
+    fn set_display_vibrance(&mut self, display_name: &str, vibrance: u8) -> VividResult<u8>;
+    fn get_display_vibrance(&mut self, display_name: &str) -> VividResult<u8>;
     fn get_sku(&mut self) -> VividResult<String>;
     fn get_vendor(&mut self) -> VividResult<GpuVendor>;
     fn get_system_type(&mut self) -> VividResult<SystemType>;
@@ -93,9 +95,99 @@ impl Gpu {
 
     pub(crate) fn get_primary_monitor_name() -> VividResult<String> {
         let primary_monitor_hwnd = unsafe { winapi::um::winuser::MonitorFromWindow(std::ptr::null_mut(), winapi::um::winuser::MONITOR_DEFAULTTOPRIMARY) };
+        Self::get_monitor_name(primary_monitor_hwnd)
+    }
+
+    /// Returns the device name (i.e. `\\.\DISPLAY1`) of the monitor the provided window is displayed on
+    pub(crate) fn get_window_monitor_name(
+        hwnd: winapi::shared::windef::HWND,
+    ) -> VividResult<String> {
+        let monitor_hwnd = unsafe {
+            winapi::um::winuser::MonitorFromWindow(
+                hwnd,
+                winapi::um::winuser::MONITOR_DEFAULTTONEAREST,
+            )
+        };
+        Self::get_monitor_name(monitor_hwnd)
+    }
+
+    /// Returns the device names of every connected monitor
+    pub(crate) fn get_monitor_names() -> VividResult<Vec<String>> {
+        unsafe extern "system" fn enum_proc(
+            monitor_hwnd: winapi::shared::windef::HMONITOR,
+            _: winapi::shared::windef::HDC,
+            _: winapi::shared::windef::LPRECT,
+            data: winapi::shared::minwindef::LPARAM,
+        ) -> winapi::shared::minwindef::BOOL {
+            let monitors = &mut *(data as *mut Vec<winapi::shared::windef::HMONITOR>);
+            monitors.push(monitor_hwnd);
+            winapi::shared::minwindef::TRUE
+        }
+
+        let mut monitors: Vec<winapi::shared::windef::HMONITOR> = vec![];
+        let res = unsafe {
+            winapi::um::winuser::EnumDisplayMonitors(
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                Some(enum_proc),
+                &mut monitors as *mut _ as winapi::shared::minwindef::LPARAM,
+            )
+        };
+        if res != winapi::shared::minwindef::TRUE {
+            return Err(VividError::NoDisplayDetected);
+        }
+
+        monitors.into_iter().map(Self::get_monitor_name).collect()
+    }
+
+    /// Returns the video modes supported by a monitor
+    pub(crate) fn get_video_modes(
+        monitor_name: &str,
+    ) -> VividResult<Vec<crate::config::VideoMode>> {
+        let device_name: Vec<u16> =
+            std::os::windows::ffi::OsStrExt::encode_wide(std::ffi::OsStr::new(monitor_name))
+                .chain(std::iter::once(0))
+                .collect();
+        let mut modes: Vec<crate::config::VideoMode> = vec![];
+        for mode_index in 0.. {
+            let mut dev_mode = winapi::um::wingdi::DEVMODEW::default();
+            dev_mode.dmSize = std::mem::size_of::<winapi::um::wingdi::DEVMODEW>() as u16;
+            let res = unsafe {
+                winapi::um::winuser::EnumDisplaySettingsW(
+                    device_name.as_ptr(),
+                    mode_index,
+                    &mut dev_mode,
+                )
+            };
+            if res == 0 {
+                break;
+            }
+            let mode = crate::config::VideoMode {
+                width: dev_mode.dmPelsWidth,
+                height: dev_mode.dmPelsHeight,
+                freq: dev_mode.dmDisplayFrequency,
+            };
+            if !modes.contains(&mode) {
+                modes.push(mode);
+            }
+        }
+
+        if modes.is_empty() {
+            return Err(VividError::NoDisplayDetected);
+        }
+
+        Ok(modes)
+    }
+
+    fn get_monitor_name(monitor_hwnd: winapi::shared::windef::HMONITOR) -> VividResult<String> {
         let mut monitor_info = winapi::um::winuser::MONITORINFOEXW::default();
         monitor_info.cbSize = std::mem::size_of::<winapi::um::winuser::MONITORINFOEXW>() as u32;
-        let res = unsafe { winapi::um::winuser::GetMonitorInfoW(primary_monitor_hwnd, &mut monitor_info as *mut _ as *mut _) };
+        let res = unsafe {
+            winapi::um::winuser::GetMonitorInfoW(
+                monitor_hwnd,
+                &mut monitor_info as *mut _ as *mut _,
+            )
+        };
         if res != winapi::shared::minwindef::TRUE {
             return Err(VividError::NoDisplayDetected);
         }
@@ -129,4 +221,12 @@ impl Gpu {
     pub fn get_vibrance(&mut self) -> VividResult<u8> {
         self.adapter.get_vibrance()
     }
+
+    pub fn set_display_vibrance(&mut self, display_name: &str, vibrance: u8) -> VividResult<u8> {
+        self.adapter.set_display_vibrance(display_name, vibrance)
+    }
+
+    pub fn get_display_vibrance(&mut self, display_name: &str) -> VividResult<u8> {
+        self.adapter.get_display_vibrance(display_name)
+    }
 }
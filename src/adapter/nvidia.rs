@@ -42,11 +42,15 @@ impl Nvidia {
     }
 
     fn get_target_display(&mut self) -> VividResult<&Display> {
-        self.displays = self.gpu.lock().connected_displays()?;
         let target_display = super::Gpu::get_primary_monitor_name()?;
+        self.get_display(&target_display)
+    }
+
+    fn get_display(&mut self, display_name: &str) -> VividResult<&Display> {
+        self.displays = self.gpu.lock().connected_displays()?;
         self.displays
             .iter()
-            .find(|display| display.display_name == target_display)
+            .find(|display| display.display_name == display_name)
             .ok_or_else(|| VividError::NoDisplayDetected)
     }
 }
@@ -64,6 +68,18 @@ impl super::VibranceAdapter for Nvidia {
             .map_err(From::from)
     }
 
+    fn set_display_vibrance(&mut self, display_name: &str, vibrance: u8) -> VividResult<u8> {
+        self.get_display(display_name)?
+            .set_vibrance(vibrance)
+            .map_err(Into::into)
+    }
+
+    fn get_display_vibrance(&mut self, display_name: &str) -> VividResult<u8> {
+        self.get_display(display_name)?
+            .get_vibrance()
+            .map_err(From::from)
+    }
+
     fn get_sku(&mut self) -> VividResult<String> {
         Ok(self.gpu.lock().info()?.name)
     }
@@ -0,0 +1,64 @@
+use crate::config::Config;
+use crate::error::VividResult;
+use crate::format::Format;
+use crate::schedule::Clock as _;
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct CheckOpts {
+    /// Fail on warnings too
+    #[structopt(long)]
+    strict: bool,
+}
+
+pub fn run(
+    opts: CheckOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    let path = Config::discover(config_file)?.path;
+    let files = Config::read_files(&path, format)?;
+    let report = crate::validate::check_files(
+        &files,
+        &Config::environment(),
+        crate::validate::Tests::Checked,
+    );
+    for diagnostic in &report.diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+
+    // With several files, tell where each effective rule comes from
+    if files.len() > 1 {
+        println!(
+            "Merged {} files, rules and program settings in evaluation order:",
+            files.len()
+        );
+        for provenance in &report.provenance {
+            println!("  {}", provenance);
+        }
+    }
+
+    // Test cases can only run against a valid configuration
+    let failures = match report.config {
+        Some(ref config) if !report.has_errors() => super::test::print_outcomes(
+            &crate::rule_tests::run(config, &crate::schedule::SystemClock.now()),
+            false,
+        ),
+        _ => 0,
+    };
+
+    let errors = report.errors().count();
+    let warnings = report.warnings().count();
+    println!(
+        "{}: {} error(s), {} warning(s), {} failed test(s)",
+        path.display(),
+        errors,
+        warnings,
+        failures
+    );
+
+    if errors > 0 || failures > 0 || (opts.strict && warnings > 0) {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
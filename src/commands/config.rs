@@ -0,0 +1,96 @@
+use crate::config::{Config, ConfigFragment};
+use crate::error::{VividError, VividResult};
+use crate::format::Format;
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct ConfigOpts {
+    #[structopt(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+enum ConfigCommand {
+    /// Converts a configuration file between TOML, JSON and YAML. Comments aren't kept
+    Convert(ConvertOpts),
+}
+
+#[derive(Debug, structopt::StructOpt)]
+struct ConvertOpts {
+    /// File to convert, the configuration file by default. Its format comes from its extension or `--format`
+    #[structopt(parse(from_os_str))]
+    input: Option<std::path::PathBuf>,
+    /// Format to convert to (toml, json or yaml), guessed from the extension of the output file by default
+    #[structopt(long)]
+    to: Option<Format>,
+    /// Writes the converted file there instead of printing it
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<std::path::PathBuf>,
+    /// Overwrites the output file if it exists
+    #[structopt(long)]
+    force: bool,
+}
+
+pub fn run(
+    opts: ConfigOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    match opts.command {
+        ConfigCommand::Convert(opts) => convert(opts, config_file, format),
+    }
+}
+
+fn convert(
+    opts: ConvertOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    let input = match opts.input {
+        Some(input) => input,
+        None => Config::discover(config_file)?.path,
+    };
+    let from = Format::of_main(&input, format);
+    let to = opts
+        .to
+        .or_else(|| opts.output.as_deref().and_then(Format::from_extension))
+        .ok_or_else(|| {
+            VividError::ConfigurationConversion(
+                "pass the format to convert to with --to, or an output file with a .toml, .json or .yaml extension"
+                    .into(),
+            )
+        })?;
+
+    let source = std::fs::read_to_string(&input)
+        .map_err(|e| VividError::ConfigurationFileError(input.clone(), e))?;
+    // Only configuration files are converted, and they have to read the same once converted
+    let invalid = |e: crate::format::ParseError| {
+        VividError::ConfigurationConversion(format!("{} ({}): {}", input.display(), from, e))
+    };
+    from.parse::<ConfigFragment>(&source).map_err(invalid)?;
+    let converted = from.convert(&source, to).map_err(invalid)?;
+    to.parse::<ConfigFragment>(&converted).map_err(|e| {
+        VividError::ConfigurationConversion(format!("the {} result doesn't read back: {}", to, e))
+    })?;
+
+    match opts.output {
+        Some(output) => {
+            if output.exists() && !opts.force {
+                return Err(VividError::ConfigurationExists(output));
+            }
+            crate::config::write_atomically(&output, &converted)?;
+            println!(
+                "Converted {} ({}) to {} ({})",
+                input.display(),
+                from,
+                output.display(),
+                to
+            );
+            if from != Format::Json && from != to {
+                println!("Comments of {} aren't kept", input.display());
+            }
+        }
+        None => print!("{}", converted),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use super::rule::Loaded;
+use crate::config::Config;
+use crate::discover::{Game, Launcher};
+use crate::error::{VividError, VividResult};
+use crate::format::Format;
+use crate::rules::Condition;
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct DiscoverOpts {
+    #[structopt(subcommand)]
+    command: DiscoverCommand,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+enum DiscoverCommand {
+    /// Lists games installed with Steam, in every library folder
+    Steam(GamesOpts),
+    /// Lists games installed with Lutris
+    Lutris(GamesOpts),
+    /// Lists games installed with Heroic: Epic, GOG and Amazon games, and the ones added by hand
+    Heroic(GamesOpts),
+    /// Lists games installed with the Epic Games Launcher
+    Epic(GamesOpts),
+    /// Lists games installed with GOG Galaxy or GOG installers
+    Gog(GamesOpts),
+}
+
+#[derive(Debug, structopt::StructOpt)]
+struct GamesOpts {
+    /// Adds a rule applying to every program of this game, by id or name as listed. Can be repeated
+    #[structopt(long = "add", requires = "vibrance")]
+    add: Vec<String>,
+    /// Vibrance of the added rules, in percent
+    #[structopt(long)]
+    vibrance: Option<u8>,
+    /// Settings with a higher priority win over others
+    #[structopt(long)]
+    priority: Option<i32>,
+}
+
+pub fn run(
+    opts: DiscoverOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    use crate::discover::{epic::Epic, gog::Gog, heroic::Heroic, lutris::Lutris, steam::Steam};
+
+    let (launcher, opts): (&dyn Launcher, GamesOpts) = match opts.command {
+        DiscoverCommand::Steam(opts) => (&Steam, opts),
+        DiscoverCommand::Lutris(opts) => (&Lutris, opts),
+        DiscoverCommand::Heroic(opts) => (&Heroic, opts),
+        DiscoverCommand::Epic(opts) => (&Epic, opts),
+        DiscoverCommand::Gog(opts) => (&Gog, opts),
+    };
+    let games = launcher
+        .installed()
+        .map_err(|e| VividError::Discovery(format!("{}: {}", launcher.name(), e)))?;
+    games_command(games, opts, config_file, format)
+}
+
+/// Tells if one of the rules applies to every program of the game
+fn has_rule(config: &Config, game: &Game) -> bool {
+    let prefix = game.path_prefix();
+    config.rules().iter().any(|rule| match &rule.when {
+        Condition::Path(path) => path.eq_ignore_ascii_case(&prefix),
+        _ => false,
+    })
+}
+
+fn games_command(
+    games: Vec<Game>,
+    opts: GamesOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    if opts.add.is_empty() {
+        list(&games, Config::load(config_file, format).ok().as_ref());
+        return Ok(());
+    }
+
+    let mut chosen: Vec<&Game> = vec![];
+    for selector in &opts.add {
+        let game = games
+            .iter()
+            .find(|game| game.matches(selector))
+            .ok_or_else(|| {
+                VividError::Discovery(format!(
+                    "there's no installed game with the id or name \"{}\"",
+                    selector
+                ))
+            })?;
+        if !chosen.iter().any(|other| other.id == game.id) {
+            chosen.push(game);
+        }
+    }
+    add(chosen, &opts, config_file, format)
+}
+
+fn list(games: &[Game], config: Option<&Config>) {
+    if games.is_empty() {
+        println!("No game is installed");
+        return;
+    }
+
+    for game in games {
+        let has_rule = config.map_or(false, |config| has_rule(config, game));
+        println!(
+            "{} {}{}",
+            game.id,
+            game.name,
+            if has_rule { " (has a rule)" } else { "" }
+        );
+        println!("    install dir: {}", game.install_dir.display());
+        match &game.executable {
+            Some(executable) => println!("    main executable: {}", executable.display()),
+            None => println!("    main executable: not found"),
+        }
+    }
+    println!();
+    println!("Add rules with `--add <id or name> --vibrance <percent>`");
+}
+
+/// Adds a rule per game to the main configuration file, matching the programs in its install directory
+fn add(
+    games: Vec<&Game>,
+    opts: &GamesOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    use toml::Value;
+
+    let loaded = Loaded::new(config_file, format)?;
+    let mut entries = vec![];
+    for game in games {
+        if has_rule(loaded.config(), game) {
+            println!("{} already has a rule, skipping it", game.name);
+            continue;
+        }
+
+        let mut fields: Vec<(&str, Value)> = vec![("name", Value::String(game.name.clone()))];
+        if let Some(priority) = opts.priority {
+            fields.push(("priority", Value::Integer(priority.into())));
+        }
+        // `requires` makes structopt reject `--add` without `--vibrance`
+        fields.push((
+            "vibrance",
+            Value::Integer(opts.vibrance.unwrap_or_default().into()),
+        ));
+        let mut when = toml::value::Table::new();
+        when.insert("path".into(), Value::String(game.path_prefix()));
+        fields.push(("when", Value::Table(when)));
+        entries.push(fields);
+    }
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let file = loaded.main_file();
+    loaded.edit(file, |document| {
+        entries
+            .iter()
+            .try_for_each(|fields| document.append("rules", fields))
+    })
+}
@@ -0,0 +1,245 @@
+use crate::config::Config;
+use crate::error::{VividError, VividResult};
+use crate::format::Format;
+
+/// Copy of the configuration being edited, next to it so includes and drop-in files resolve the same way.
+/// It keeps the extension, so editors and Vivid see the same format
+fn editing_copy_path(path: &std::path::Path) -> std::path::PathBuf {
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "vivid".into(), |stem| stem.to_string_lossy());
+    let extension = path
+        .extension()
+        .map_or_else(|| "toml".into(), |extension| extension.to_string_lossy());
+    path.with_file_name(format!("{}.edit.{}", stem, extension))
+}
+
+/// Splits a command line into words, double quotes grouping words with spaces (i.e. `"C:\Program Files\..." -n`)
+fn split_words(command: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut in_word = false;
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    words
+}
+
+/// Asks a yes/no question, `default` being the answer to an empty line. Without input, the answer is no
+pub fn ask(question: &str, default: bool) -> VividResult<bool> {
+    use std::io::Write as _;
+    print!("{} [{}] ", question, if default { "Y/n" } else { "y/N" });
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer)? == 0 {
+        return Ok(false);
+    }
+    Ok(match answer.trim().to_ascii_lowercase().as_str() {
+        "" => default,
+        answer => answer.starts_with('y'),
+    })
+}
+
+/// Runs the editor from `$VISUAL` or `$EDITOR` on a file, and waits for it to exit
+fn run_editor(editor: &str, path: &std::path::Path) -> VividResult<()> {
+    let mut words = split_words(editor);
+    if words.is_empty() {
+        return Err(VividError::ConfigurationEdit(format!(
+            "the editor command is empty: {:?}",
+            editor
+        )));
+    }
+    let program = words.remove(0);
+
+    let mut status = std::process::Command::new(&program)
+        .args(&words)
+        .arg(path)
+        .status();
+    // Editors installed as .cmd scripts (i.e. `code`) can only be started through the shell
+    if cfg!(windows) {
+        if let Err(e) = &status {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                status = std::process::Command::new("cmd")
+                    .arg("/C")
+                    .arg(&program)
+                    .args(&words)
+                    .arg(path)
+                    .status();
+            }
+        }
+    }
+
+    let status = status.map_err(|e| {
+        VividError::ConfigurationEdit(format!("cannot start the editor {}: {}", program, e))
+    })?;
+    if !status.success() {
+        return Err(VividError::ConfigurationEdit(format!(
+            "the editor {} exited with {}",
+            program, status
+        )));
+    }
+
+    Ok(())
+}
+
+/// Opens a file with the program associated with it, and waits for it to be closed
+#[cfg(windows)]
+fn open_with_default_handler(path: &std::path::Path) -> VividResult<()> {
+    use winapi::um::{handleapi, shellapi, synchapi, winbase, winuser};
+
+    let file = std::ffi::CString::new(path.to_string_lossy().as_bytes())
+        .map_err(|e| VividError::ConfigurationEdit(e.to_string()))?;
+    let mut info = shellapi::SHELLEXECUTEINFOA {
+        cbSize: std::mem::size_of::<shellapi::SHELLEXECUTEINFOA>() as _,
+        fMask: shellapi::SEE_MASK_NOCLOSEPROCESS | shellapi::SEE_MASK_NOASYNC,
+        lpFile: file.as_ptr(),
+        nShow: winuser::SW_SHOWNORMAL,
+        ..Default::default()
+    };
+
+    if unsafe { shellapi::ShellExecuteExA(&mut info) } == 0 {
+        // No program is associated with .toml files
+        log::info!(
+            "Cannot open {}, using notepad: {}",
+            path.display(),
+            std::io::Error::last_os_error()
+        );
+        return run_editor("notepad.exe", path);
+    }
+
+    if info.hProcess.is_null() {
+        // The file went to an already running program, there's no process to wait for
+        wait_for_enter()
+    } else {
+        unsafe {
+            synchapi::WaitForSingleObject(info.hProcess, winbase::INFINITE);
+            handleapi::CloseHandle(info.hProcess);
+        }
+        Ok(())
+    }
+}
+
+/// Opens a file with the program associated with it, and waits for it to be closed
+#[cfg(not(windows))]
+fn open_with_default_handler(path: &std::path::Path) -> VividResult<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    run_editor(opener, path)?;
+    // The opener returns as soon as the file is handed over
+    wait_for_enter()
+}
+
+fn wait_for_enter() -> VividResult<()> {
+    use std::io::Write as _;
+    print!("Press Enter once the file is saved and closed ");
+    std::io::stdout().flush()?;
+    std::io::stdin().read_line(&mut String::new())?;
+    Ok(())
+}
+
+/// Opens a file in `$VISUAL`, `$EDITOR`, or the program associated with it, and waits for it to be closed
+fn open_editor(path: &std::path::Path) -> VividResult<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|variable| std::env::var(variable).ok())
+        .find(|editor| !editor.trim().is_empty());
+    match editor {
+        Some(editor) => run_editor(&editor, path),
+        None => open_with_default_handler(path),
+    }
+}
+
+/// Edits the configuration like `crontab -e` does: a copy is edited, validated once the editor exits,
+/// and only replaces the configuration when it's valid
+pub fn run(config_file: Option<String>, format: Option<Format>) -> VividResult<()> {
+    let path = Config::discover(config_file)?.path;
+    let original = match std::fs::read_to_string(&path) {
+        Ok(source) => Some(source),
+        // Explicitly passed paths may not exist yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(VividError::ConfigurationFileError(path, e)),
+    };
+
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    let copy = editing_copy_path(&path);
+    let contents = match &original {
+        Some(original) => original.clone(),
+        None => super::init::template_as(
+            super::init::FALLBACK_VIBRANCE,
+            &[],
+            Format::of_main(&path, format),
+        )?,
+    };
+    std::fs::write(&copy, contents)?;
+
+    let result = edit_copy(&path, &copy, format, original.as_deref());
+    let _ = std::fs::remove_file(&copy);
+    result
+}
+
+fn edit_copy(
+    path: &std::path::Path,
+    copy: &std::path::Path,
+    format: Option<Format>,
+    original: Option<&str>,
+) -> VividResult<()> {
+    loop {
+        open_editor(copy)?;
+        let edited = std::fs::read_to_string(copy)?;
+        if original == Some(edited.as_str()) {
+            println!("No changes, {} is left unchanged", path.display());
+            return Ok(());
+        }
+
+        let report = crate::validate::check_files(
+            &Config::read_files(copy, format)?,
+            &Config::environment(),
+            crate::validate::Tests::Checked,
+        );
+        for diagnostic in &report.diagnostics {
+            eprintln!("{}", diagnostic);
+        }
+        if report.config.is_some() && !report.has_errors() {
+            std::fs::rename(copy, path)?;
+            println!("Saved {}", path.display());
+            if crate::config_watch::notify_reload() {
+                println!("Vivid is reloading it");
+            }
+            return Ok(());
+        }
+
+        if !ask(
+            "The configuration is invalid, edit it again? Otherwise the changes are discarded",
+            true,
+        )? {
+            println!("Changes discarded, {} is left unchanged", path.display());
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use crate::config::{Config, WindowState};
+use crate::error::VividResult;
+use crate::foreground_watch::{
+    ForegroundWatcher, ForegroundWatcherEvent, ForegroundWatcherEventKind,
+};
+use crate::format::Format;
+use crate::power::PowerSource;
+use crate::rules::Decision;
+use crate::schedule::Clock as _;
+use chrono::{DateTime, FixedOffset};
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct ExplainOpts {
+    /// Name of the program to simulate. The current foreground window is inspected if unset
+    #[structopt(long)]
+    exe: Option<String>,
+    /// Path of the program to simulate
+    #[structopt(long)]
+    path: Option<std::path::PathBuf>,
+    /// Title of the window to simulate
+    #[structopt(long)]
+    title: Option<String>,
+    /// Class of the window to simulate
+    #[structopt(long)]
+    class: Option<String>,
+    /// Simulate an exclusive fullscreen window
+    #[structopt(long, conflicts_with = "borderless")]
+    fullscreen: bool,
+    /// Simulate a borderless window covering its monitor
+    #[structopt(long)]
+    borderless: bool,
+    /// Monitor the window is displayed on, i.e. `\\.\DISPLAY1`. Defaults to the primary monitor
+    #[structopt(long)]
+    display: Option<String>,
+    /// Time to evaluate rules at, either "HH:MM" (today) or an RFC 3339 date and time. Defaults to now
+    #[structopt(long, parse(try_from_str = parse_time))]
+    time: Option<DateTime<FixedOffset>>,
+    /// Simulate a power source, "ac" or "battery"
+    #[structopt(long, parse(try_from_str = parse_power_source))]
+    power: Option<PowerSource>,
+    /// Simulate a battery charge in percent
+    #[structopt(long)]
+    battery: Option<u8>,
+    /// Programs to consider running on top of the actually running ones. Can be repeated
+    #[structopt(long)]
+    running: Vec<String>,
+    /// Seconds to wait before inspecting the foreground window, to leave time to switch to it
+    #[structopt(long, default_value = "0")]
+    delay: u64,
+}
+
+fn parse_time(value: &str) -> Result<DateTime<FixedOffset>, String> {
+    crate::schedule::parse_time(value, &crate::schedule::SystemClock.now())
+}
+
+fn parse_power_source(value: &str) -> Result<PowerSource, String> {
+    match value {
+        "ac" => Ok(PowerSource::Ac),
+        "battery" => Ok(PowerSource::Battery),
+        _ => Err(format!(
+            "unknown power source \"{}\", expected ac or battery",
+            value
+        )),
+    }
+}
+
+impl ExplainOpts {
+    fn event(&self) -> ForegroundWatcherEvent {
+        let exe = match &self.exe {
+            Some(exe) => exe.clone(),
+            None => {
+                if self.delay > 0 {
+                    println!("Inspecting the foreground window in {}s...", self.delay);
+                    std::thread::sleep(std::time::Duration::from_secs(self.delay));
+                }
+                return ForegroundWatcher::current_event(ForegroundWatcherEventKind::Foreground);
+            }
+        };
+
+        ForegroundWatcherEvent {
+            kind: ForegroundWatcherEventKind::Foreground,
+            hwnd: std::ptr::null_mut(),
+            process_id: 0,
+            process_path: self.path.clone().unwrap_or_else(|| exe.clone().into()),
+            process_exe: exe,
+            window_class: self.class.clone().unwrap_or_default(),
+            window_title: self.title.clone().unwrap_or_default(),
+            window_state: if self.fullscreen {
+                WindowState::Fullscreen
+            } else if self.borderless {
+                WindowState::Borderless
+            } else {
+                WindowState::Windowed
+            },
+            monitor: self.display.clone().unwrap_or_else(|| {
+                crate::adapter::Gpu::get_primary_monitor_name().unwrap_or_default()
+            }),
+        }
+    }
+}
+
+pub fn run(
+    opts: ExplainOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    use sysinfo::SystemExt as _;
+    let config = Config::load(config_file, format)?;
+    let event = opts.event();
+    let now = opts
+        .time
+        .unwrap_or_else(|| crate::schedule::SystemClock.now());
+    let mut power = crate::power::current_status();
+    if let Some(source) = opts.power {
+        power.source = source;
+    }
+    if let Some(battery) = opts.battery {
+        power.battery_percent = Some(battery);
+    }
+
+    crate::foreground_watch::SYSTEM.write().refresh_processes();
+    let is_running = |exe: &str| {
+        opts.running
+            .iter()
+            .any(|running| running.eq_ignore_ascii_case(exe))
+            || crate::process_watch::is_running(exe)
+    };
+
+    println!(
+        "Window: {} [{}] \"{}\" (class: {})",
+        event.process_exe,
+        event.process_path.display(),
+        event.window_title,
+        event.window_class
+    );
+    println!(
+        "State: {:?} | Display: {} | Time: {} | Power: {:?}{}",
+        event.window_state,
+        event.monitor,
+        now,
+        power.source,
+        power
+            .battery_percent
+            .map_or_else(String::new, |percent| format!(" ({}%)", percent))
+    );
+    println!();
+
+    let evaluation =
+        match crate::foreground_callback::decide(&config, &event, now, power, &is_running) {
+            Decision::Ignored => {
+                println!(
+                    "Ignored: this window is in the ignore list, current settings would be kept"
+                );
+                return Ok(());
+            }
+            Decision::Evaluated(evaluation) => evaluation,
+        };
+
+    println!("Rules:");
+    for (index, outcome) in evaluation.outcomes.iter().enumerate() {
+        let (status, reason) = match &outcome.result {
+            Ok(reason) => ("match", reason),
+            Err(reason) => ("no match", reason),
+        };
+        println!(
+            "  #{} {} (priority {}): {}{}",
+            index + 1,
+            outcome.rule.name.as_deref().unwrap_or("<unnamed>"),
+            outcome.rule.priority,
+            status,
+            if evaluation.winner == Some(index) {
+                " [WINNER]"
+            } else {
+                ""
+            }
+        );
+        println!("      {}", reason);
+    }
+    println!();
+
+    match evaluation.winner {
+        Some(index) => println!(
+            "Result: rule #{} {} wins",
+            index + 1,
+            evaluation.outcomes[index]
+                .rule
+                .name
+                .as_deref()
+                .unwrap_or("<unnamed>")
+        ),
+        None => println!("Result: no rule matched, desktop settings apply"),
+    }
+    println!("  Vibrance: {}%", evaluation.vibrance);
+    println!(
+        "  Resolution: {}",
+        evaluation
+            .resolution
+            .as_ref()
+            .map_or_else(|| "unchanged".to_string(), ToString::to_string)
+    );
+    if evaluation.sticky {
+        println!(
+            "  Sticky: settings stay on {} while the window is visible",
+            event.monitor
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use crate::config::Config;
+use crate::error::{VividError, VividResult};
+use crate::format::Format;
+use crate::validate::{SourceFile, Tests};
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct ImportOpts {
+    #[structopt(subcommand)]
+    command: ImportCommand,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+enum ImportCommand {
+    /// Converts VibranceGUI settings: vibrance outside of listed programs, programs and their resolutions
+    #[structopt(name = "vibrancegui")]
+    VibranceGui(VibranceGuiOpts),
+}
+
+#[derive(Debug, structopt::StructOpt)]
+struct VibranceGuiOpts {
+    /// VibranceGUI settings file, `%APPDATA%\vibranceGUI\vibranceGUI.ini` by default
+    #[structopt(parse(from_os_str))]
+    file: Option<std::path::PathBuf>,
+    /// Writes the imported settings to this file instead of printing them, i.e. `vivid.d/vibrancegui.toml`.
+    /// Its format comes from its extension. Drop-in files don't get the desktop vibrance, it would override the main file's
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<std::path::PathBuf>,
+    /// Overwrites the output file if it exists
+    #[structopt(long)]
+    force: bool,
+}
+
+pub fn run(
+    opts: ImportOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    match opts.command {
+        ImportCommand::VibranceGui(opts) => vibrancegui(opts, config_file, format),
+    }
+}
+
+fn vibrancegui(
+    opts: VibranceGuiOpts,
+    config_file: Option<String>,
+    config_format: Option<Format>,
+) -> VividResult<()> {
+    let file = opts
+        .file
+        .or_else(crate::vibrancegui::default_path)
+        .ok_or_else(|| {
+            VividError::Import("pass the path of the VibranceGUI settings file".into())
+        })?;
+    let bytes =
+        std::fs::read(&file).map_err(|e| VividError::ConfigurationFileError(file.clone(), e))?;
+    let mut imported = crate::vibrancegui::import(&crate::vibrancegui::decode(&bytes))
+        .map_err(|e| VividError::Import(format!("{}: {}", file.display(), e)))?;
+
+    // Drop-in files are merged last, their desktop vibrance would win over the main file's
+    let is_drop_in = opts
+        .output
+        .as_deref()
+        .and_then(std::path::Path::parent)
+        .and_then(std::path::Path::file_name)
+        .map_or(false, |directory| {
+            directory == crate::config::DROP_IN_DIRECTORY
+        });
+    if is_drop_in {
+        if let Some(vibrance) = imported.desktop_vibrance.take() {
+            imported.notes.push(format!(
+                "inactiveValue is left out of drop-in files, set `desktop_vibrance = {}` in the main file to use it",
+                vibrance
+            ));
+        }
+    }
+
+    for note in &imported.notes {
+        eprintln!("note: {}", note);
+    }
+    // Programs the current configuration already has settings for would be shadowed, or shadow them
+    if let Ok(config) = Config::load(config_file, config_format) {
+        for program in &imported.program_settings {
+            if config
+                .program_settings()
+                .iter()
+                .any(|existing| existing.exe_name.eq_ignore_ascii_case(&program.exe_name))
+            {
+                eprintln!(
+                    "note: the configuration already has settings for {}, the ones merged last win",
+                    program.exe_name
+                );
+            }
+        }
+    }
+
+    let format = opts.output.as_deref().map_or(Format::Toml, Format::of);
+    // Going through TOML leaves unset keys out instead of writing them as null
+    let toml = Format::Toml
+        .write(&imported)
+        .map_err(|e| VividError::Import(format!("cannot write the settings: {}", e)))?;
+    let contents = match format {
+        Format::Toml => format!(
+            "# Imported from {} by `vivid import vibrancegui`\n\n{}",
+            file.display(),
+            toml
+        ),
+        _ => Format::Toml.convert(&toml, format).map_err(|e| {
+            VividError::Import(format!("cannot write the settings as {}: {}", format, e))
+        })?,
+    };
+
+    // Without a desktop vibrance the settings only load as a drop-in file, otherwise they load on their own
+    if imported.desktop_vibrance.is_some() {
+        let report = crate::validate::check_files(
+            &[SourceFile {
+                path: opts.output.clone().unwrap_or_default(),
+                format,
+                source: contents.clone(),
+            }],
+            &Config::environment(),
+            Tests::Skipped,
+        );
+        for diagnostic in &report.diagnostics {
+            eprintln!("{}", diagnostic);
+        }
+    }
+
+    match opts.output {
+        Some(output) => {
+            if output.exists() && !opts.force {
+                return Err(VividError::ConfigurationExists(output));
+            }
+            if let Some(directory) = output
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                std::fs::create_dir_all(directory)?;
+            }
+            crate::config::write_atomically(&output, &contents)?;
+            println!(
+                "Imported {} programs from {} to {}",
+                imported.program_settings.len(),
+                file.display(),
+                output.display()
+            );
+        }
+        None => print!("{}", contents),
+    }
+
+    Ok(())
+}
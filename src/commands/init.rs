@@ -0,0 +1,162 @@
+use crate::config::{Config, ConfigSource};
+use crate::error::{VividError, VividResult};
+use crate::format::Format;
+
+/// Vibrance used when no GPU can tell the current one
+pub const FALLBACK_VIBRANCE: u8 = 50;
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct InitOpts {
+    /// Overwrites an existing configuration file
+    #[structopt(long)]
+    force: bool,
+    /// Creates the file next to the executable instead of the user configuration directory
+    #[structopt(long)]
+    portable: bool,
+    /// Adds settings for this program, with the running program's exact name if it's running. Can be repeated
+    #[structopt(long = "program")]
+    programs: Vec<String>,
+}
+
+/// Current vibrance if a GPU can be used, it's never required
+fn detect_vibrance() -> Option<u8> {
+    match crate::adapter::Gpu::detect_gpu().and_then(|mut gpu| gpu.get_vibrance()) {
+        Ok(vibrance) => Some(vibrance),
+        Err(e) => {
+            log::info!("Cannot read the current vibrance, using defaults: {}", e);
+            None
+        }
+    }
+}
+
+/// Program names as spelled by the running processes, when they are running
+fn running_names(programs: &[String]) -> Vec<String> {
+    use sysinfo::{ProcessExt as _, SystemExt as _};
+    let mut system = crate::foreground_watch::SYSTEM.write();
+    system.refresh_processes();
+    programs
+        .iter()
+        .map(|program| {
+            system
+                .get_processes()
+                .values()
+                .map(|process| process.name())
+                .find(|name| name.eq_ignore_ascii_case(program))
+                .map_or_else(
+                    || {
+                        println!("{} isn't running, adding it as is", program);
+                        program.clone()
+                    },
+                    ToString::to_string,
+                )
+        })
+        .collect()
+}
+
+/// Commented configuration to start from
+pub fn template(vibrance: u8, programs: &[String]) -> String {
+    let mut template = format!(
+        r#"# Vivid configuration, the format is documented at https://github.com/OtaK/vivid#configuration-format
+# Run `vivid check` after editing it
+
+# Version of the configuration format, `vivid migrate` upgrades older files
+version = {}
+
+# Vibrance to restore when any non-selected program comes to foreground, included explorer.exe
+desktop_vibrance = {}
+
+# Desktop resolution to restore
+# resolution = {{ width = 1920, height = 1080, freq = 60 }}
+
+# Programs and windows that never trigger a change, on top of the built-in list (overlays, alt-tab...)
+# [ignore]
+# programs = ["MyOverlay.exe"]
+
+# Program-specific settings
+"#,
+        crate::migrate::CURRENT_VERSION,
+        vibrance
+    );
+
+    if programs.is_empty() {
+        template.push_str(
+            r#"# [[program_settings]]
+# exe_name = "your_favorite_game.exe" # Name of the program to react on
+# vibrance = 80 # Vibrance value in percentage to apply when this program comes to foreground
+# fullscreen_only = true # Only apply settings when the program is fullscreen
+"#,
+        );
+    }
+
+    for program in programs {
+        template.push_str(&format!(
+            r#"[[program_settings]]
+exe_name = {} # Name of the program to react on
+vibrance = {} # Vibrance value in percentage to apply when this program comes to foreground
+fullscreen_only = false # Only apply settings when the program is fullscreen
+
+"#,
+            toml::Value::String(program.clone()),
+            vibrance
+        ));
+    }
+
+    template
+}
+
+/// The template in another format, without its comments as only TOML keeps them
+pub fn template_as(vibrance: u8, programs: &[String], format: Format) -> VividResult<String> {
+    let contents = template(vibrance, programs);
+    // The template must always load
+    Config::from_source(&contents)?;
+    match format {
+        Format::Toml => Ok(contents),
+        _ => Format::Toml.convert(&contents, format).map_err(|e| {
+            VividError::Other(anyhow::anyhow!(
+                "Cannot write the template as {}: {}",
+                format,
+                e
+            ))
+        }),
+    }
+}
+
+pub fn run(opts: InitOpts, config_file: Option<String>, format: Option<Format>) -> VividResult<()> {
+    let target = if opts.portable {
+        ConfigSource::Portable
+    } else {
+        ConfigSource::UserDirectory
+    };
+    // `--format` picks which of vivid.toml, vivid.json and vivid.yaml is created
+    let created = format.unwrap_or(Format::Toml);
+    let location = Config::candidates(config_file)
+        .into_iter()
+        .find(|candidate| {
+            candidate.is_explicit()
+                || (candidate.source == target && Format::of(&candidate.path) == created)
+        })
+        .ok_or_else(|| {
+            VividError::Other(anyhow::anyhow!(
+                "Vivid couldn't find where to create the configuration file, pass its path with --config"
+            ))
+        })?;
+
+    if location.path.exists() && !opts.force {
+        return Err(VividError::ConfigurationExists(location.path));
+    }
+
+    let vibrance = detect_vibrance().unwrap_or(FALLBACK_VIBRANCE);
+    let contents = template_as(
+        vibrance,
+        &running_names(&opts.programs),
+        Format::of_main(&location.path, format),
+    )?;
+
+    if let Some(directory) = location.path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    crate::config::write_atomically(&location.path, &contents)?;
+    println!("Created {} ({})", location.path.display(), location.source);
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use super::rule::Loaded;
+use crate::config::{Config, WindowState};
+use crate::error::VividResult;
+use crate::foreground_watch::{
+    ForegroundWatcher, ForegroundWatcherEvent, ForegroundWatcherEventKind,
+};
+use crate::format::Format;
+use crate::learn::{format_duration, Recorder, Usage};
+use crate::rules::Decision;
+use crate::schedule::Clock as _;
+
+lazy_static::lazy_static! {
+    static ref RECORDER: parking_lot::Mutex<Recorder> = parking_lot::Mutex::new(Recorder::new());
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct LearnOpts {
+    /// Stops recording after this many minutes, otherwise recording goes on until Ctrl+C
+    #[structopt(long)]
+    minutes: Option<u64>,
+    /// Minutes a program has to be used fullscreen to be proposed
+    #[structopt(long, default_value = "5")]
+    min_fullscreen: u64,
+    /// Vibrance of the proposed program settings, in percent
+    #[structopt(long, default_value = "80")]
+    vibrance: u8,
+    /// Adds every proposed program without asking
+    #[structopt(long)]
+    yes: bool,
+}
+
+fn record(event: &ForegroundWatcherEvent) -> VividResult<()> {
+    let mut recorder = RECORDER.lock();
+    if let Some(usage) = recorder.record(
+        &event.process_exe,
+        &event.process_path,
+        event.window_state,
+        std::time::Instant::now(),
+    ) {
+        println!(
+            "New program: {} [{}], {:?}",
+            usage.exe,
+            usage.path.display(),
+            event.window_state
+        );
+    }
+
+    Ok(())
+}
+
+/// Stops the message loop after some time
+fn stop_after(minutes: u64) {
+    use winapi::um::{processthreadsapi, winuser};
+    let thread = unsafe { processthreadsapi::GetCurrentThreadId() };
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_secs(minutes * 60));
+        unsafe { winuser::PostThreadMessageA(thread, winuser::WM_QUIT, 0, 0) };
+    });
+}
+
+/// Tells if the configuration already decides what happens to a program in fullscreen: it's either ignored or
+/// a rule matches it
+fn is_covered(config: &Config, usage: &Usage) -> bool {
+    let event = ForegroundWatcherEvent {
+        kind: ForegroundWatcherEventKind::Foreground,
+        hwnd: std::ptr::null_mut(),
+        process_id: 0,
+        process_exe: usage.exe.clone(),
+        process_path: usage.path.clone(),
+        window_class: String::new(),
+        window_title: String::new(),
+        window_state: WindowState::Fullscreen,
+        monitor: crate::adapter::Gpu::get_primary_monitor_name().unwrap_or_default(),
+    };
+    match crate::foreground_callback::decide(
+        config,
+        &event,
+        crate::schedule::SystemClock.now(),
+        crate::power::current_status(),
+        &crate::process_watch::is_running,
+    ) {
+        Decision::Ignored => true,
+        Decision::Evaluated(evaluation) => evaluation.winner.is_some(),
+    }
+}
+
+pub fn run(
+    opts: LearnOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    // Fails early rather than after a whole session
+    Config::load(config_file.clone(), format)?;
+
+    let mut watcher = ForegroundWatcher::new();
+    watcher.add_event_callback(record);
+    watcher.register()?;
+    match opts.minutes {
+        Some(minutes) => {
+            println!(
+                "Recording foreground programs for {} minutes, press Ctrl+C to stop earlier...",
+                minutes
+            );
+            stop_after(minutes);
+        }
+        None => println!("Recording foreground programs, press Ctrl+C to stop..."),
+    }
+    record(&ForegroundWatcher::current_event(
+        ForegroundWatcherEventKind::Foreground,
+    ))?;
+    unsafe { crate::w32_ctrlc::init_ctrlc()? };
+
+    let mut msg = unsafe { std::mem::zeroed() };
+    loop {
+        crate::w32_msgloop::read_message(&mut msg)?;
+        if crate::w32_msgloop::process_message(&msg) {
+            break;
+        }
+    }
+    drop(watcher);
+
+    let recorder = {
+        let mut recorder = RECORDER.lock();
+        recorder.stop(std::time::Instant::now());
+        std::mem::take(&mut *recorder)
+    };
+    println!();
+    println!(
+        "{:<32} {:>12} {:>12} {:>12}",
+        "Program", "Foreground", "Fullscreen", "Activations"
+    );
+    for usage in recorder.usages() {
+        println!(
+            "{:<32} {:>12} {:>12} {:>12}",
+            usage.exe,
+            format_duration(usage.foreground),
+            format_duration(usage.fullscreen),
+            usage.activations
+        );
+    }
+    println!();
+
+    // Loaded after the session, in case the configuration was edited meanwhile
+    let loaded = Loaded::new(config_file, format)?;
+    let min_fullscreen = std::time::Duration::from_secs(opts.min_fullscreen * 60);
+    let proposals: Vec<&Usage> = recorder
+        .proposals(min_fullscreen)
+        .into_iter()
+        .filter(|usage| !is_covered(loaded.config(), usage))
+        .collect();
+    if proposals.is_empty() {
+        println!(
+            "No program without settings was used fullscreen for {} minutes or more",
+            opts.min_fullscreen
+        );
+        return Ok(());
+    }
+
+    let mut accepted = vec![];
+    for usage in proposals {
+        let question = format!(
+            "Add settings for {} ({} fullscreen, vibrance {})?",
+            usage.exe,
+            format_duration(usage.fullscreen),
+            opts.vibrance
+        );
+        if opts.yes || super::edit::ask(&question, true)? {
+            accepted.push(usage);
+        }
+    }
+    if accepted.is_empty() {
+        return Ok(());
+    }
+
+    let entries: Vec<Vec<(&str, toml::Value)>> = accepted
+        .iter()
+        .map(|usage| {
+            vec![
+                ("exe_name", toml::Value::String(usage.exe.clone())),
+                ("vibrance", toml::Value::Integer(opts.vibrance.into())),
+                ("fullscreen_only", toml::Value::Boolean(true)),
+            ]
+        })
+        .collect();
+    let file = loaded.main_file();
+    loaded.edit(file, |document| {
+        entries
+            .iter()
+            .try_for_each(|fields| document.append("program_settings", fields))
+    })
+}
@@ -0,0 +1,105 @@
+use crate::config::Config;
+use crate::error::{VividError, VividResult};
+use crate::format::Format;
+use crate::migrate::CURRENT_VERSION;
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct MigrateOpts {
+    /// Shows the changes without writing anything
+    #[structopt(long)]
+    dry_run: bool,
+}
+
+/// Path to save the original of a file at, never overwriting a previous backup
+fn backup_path(path: &std::path::Path, version: u32) -> std::path::PathBuf {
+    let name = path
+        .file_name()
+        .map_or_else(|| "vivid.toml".into(), |name| name.to_string_lossy());
+    (0..)
+        .map(|attempt| {
+            path.with_file_name(if attempt == 0 {
+                format!("{}.v{}.bak", name, version)
+            } else {
+                format!("{}.v{}.{}.bak", name, version, attempt)
+            })
+        })
+        .find(|backup| !backup.exists())
+        .unwrap()
+}
+
+pub fn run(
+    opts: MigrateOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    let path = Config::discover(config_file)?.path;
+    let mut files = Config::read_files(&path, format)?;
+
+    let mut upgrades = vec![];
+    for (index, file) in files.iter().enumerate() {
+        let migrated = crate::migrate::migrate(&file.source, file.format)
+            .map_err(|e| VividError::Migration(file.path.clone(), e))?;
+        if migrated.is_upgrade() {
+            upgrades.push((index, migrated));
+        }
+    }
+    if upgrades.is_empty() {
+        println!(
+            "Every file is written for version {} of the configuration format, nothing to do",
+            CURRENT_VERSION
+        );
+        return Ok(());
+    }
+
+    for (index, migrated) in &upgrades {
+        let file = &files[*index];
+        println!("--- {} (version {})", file.path.display(), migrated.from);
+        println!("+++ {} (version {})", file.path.display(), CURRENT_VERSION);
+        print!("{}", crate::migrate::diff(&file.source, &migrated.source));
+        for migration in &migrated.applied {
+            println!("# {:?}", migration);
+        }
+    }
+    if opts.dry_run {
+        println!("Dry run, nothing was written");
+        return Ok(());
+    }
+
+    // The upgraded configuration has to be valid before anything is written
+    let originals = files.clone();
+    for (index, migrated) in &upgrades {
+        files[*index].source = migrated.source.clone();
+    }
+    let report = crate::validate::check_files(
+        &files,
+        &Config::environment(),
+        crate::validate::Tests::Skipped,
+    );
+    for error in report.errors() {
+        eprintln!("{}", error);
+    }
+    if report.config.is_none() || report.has_errors() {
+        return Err(VividError::Migration(
+            path,
+            "the upgraded configuration is invalid, nothing was written".into(),
+        ));
+    }
+
+    for (index, migrated) in &upgrades {
+        let original = &originals[*index];
+        let backup = backup_path(&original.path, migrated.from);
+        crate::config::write_atomically(&backup, &original.source)?;
+        crate::config::write_atomically(&original.path, &migrated.source)?;
+        println!(
+            "Upgraded {} to version {}, the original is saved as {}",
+            original.path.display(),
+            CURRENT_VERSION,
+            backup.display()
+        );
+    }
+    if crate::config_watch::notify_reload() {
+        println!("Vivid is reloading the configuration");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use crate::error::VividResult;
+use crate::format::Format;
+
+pub mod check;
+pub mod config;
+pub mod discover;
+pub mod edit;
+pub mod explain;
+pub mod import;
+pub mod init;
+pub mod learn;
+pub mod migrate;
+pub mod rule;
+pub mod schema;
+pub mod status;
+pub mod test;
+pub mod watch;
+
+#[derive(Debug, structopt::StructOpt)]
+pub enum Command {
+    /// Validates the configuration file and runs its tests, exits with a non-zero code on errors or failures
+    Check(check::CheckOpts),
+    /// Works on configuration files as a whole, i.e. `vivid config convert` between TOML, JSON and YAML
+    Config(config::ConfigOpts),
+    /// Lists games installed through launchers and adds rules for them, i.e. `vivid discover steam`
+    Discover(discover::DiscoverOpts),
+    /// Shows which rule would match a window and why, either a simulated one or the current foreground window
+    Explain(explain::ExplainOpts),
+    /// Imports settings from other programs, i.e. `vivid import vibrancegui`
+    Import(import::ImportOpts),
+    /// Creates a commented configuration file in the user configuration directory
+    Init(init::InitOpts),
+    /// Records the programs brought to foreground and proposes settings for the ones used fullscreen
+    Learn(learn::LearnOpts),
+    /// Upgrades configuration files written for older versions of the format, keeping a backup of the originals
+    Migrate(migrate::MigrateOpts),
+    /// Lists, adds, removes or edits program settings and rules, keeping the comments and formatting of the file
+    Rule(rule::RuleOpts),
+    /// Prints the JSON Schema of the configuration file, for completion and validation in editors
+    Schema(schema::SchemaOpts),
+    /// Shows which configuration file is used and whether it's valid
+    Status(status::StatusOpts),
+    /// Runs the test cases of the configuration file (`[[tests]]`)
+    Test(test::TestOpts),
+    /// Prints foreground events as they happen, with the rule that matches and the settings Vivid would apply
+    Watch(watch::WatchOpts),
+}
+
+impl Command {
+    pub fn run(self, config_file: Option<String>, format: Option<Format>) -> VividResult<()> {
+        match self {
+            Self::Check(opts) => check::run(opts, config_file, format),
+            Self::Config(opts) => config::run(opts, config_file, format),
+            Self::Discover(opts) => discover::run(opts, config_file, format),
+            Self::Explain(opts) => explain::run(opts, config_file, format),
+            Self::Import(opts) => import::run(opts, config_file, format),
+            Self::Init(opts) => init::run(opts, config_file, format),
+            Self::Learn(opts) => learn::run(opts, config_file, format),
+            Self::Migrate(opts) => migrate::run(opts, config_file, format),
+            Self::Rule(opts) => rule::run(opts, config_file, format),
+            Self::Schema(opts) => schema::run(opts),
+            Self::Status(opts) => status::run(opts, config_file, format),
+            Self::Test(opts) => test::run(opts, config_file, format),
+            Self::Watch(opts) => watch::run(opts, config_file, format),
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use crate::config::{Config, Scope};
+use crate::config_edit::{self, Document};
+use crate::error::{VividError, VividResult};
+use crate::format::Format;
+use crate::validate::{Report, SourceFile, Tests};
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct RuleOpts {
+    #[structopt(subcommand)]
+    command: RuleCommand,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+enum RuleCommand {
+    /// Lists program settings and rules, with the file and line they're declared at
+    List,
+    /// Adds program settings, or a rule when `--name` and `--when` are passed
+    Add(AddOpts),
+    /// Removes program settings or a rule
+    Remove {
+        /// Program name, rule name, or key as listed by `vivid rule list` (i.e. `rules[0]`)
+        target: String,
+    },
+    /// Sets a key of program settings or a rule, i.e. `vivid rule set game.exe vibrance 80`
+    Set {
+        /// Program name, rule name, or key as listed by `vivid rule list` (i.e. `rules[0]`)
+        target: String,
+        key: String,
+        /// TOML value (`80`, `true`, `{ exe = "game.exe" }`), plain text is taken as a string
+        value: String,
+    },
+}
+
+#[derive(Debug, structopt::StructOpt)]
+struct AddOpts {
+    /// Name of the program, i.e. `game.exe`
+    #[structopt(required_unless = "name")]
+    exe: Option<String>,
+    /// Vibrance to apply, in percent
+    #[structopt(long)]
+    vibrance: u8,
+    /// Adds a rule with this name instead of program settings
+    #[structopt(long, requires = "when", conflicts_with = "exe")]
+    name: Option<String>,
+    /// Condition of the rule, as a TOML inline table, i.e. `{ exe = "game.exe", running = "obs64.exe" }`
+    #[structopt(long)]
+    when: Option<String>,
+    /// Settings with a higher priority win over others
+    #[structopt(long)]
+    priority: Option<i32>,
+    /// Only applies settings in fullscreen, program settings only
+    #[structopt(long, conflicts_with = "name")]
+    fullscreen_only: bool,
+    /// Applies settings as long as the program runs instead of while it's focused, program settings only
+    #[structopt(long, conflicts_with = "name")]
+    running: bool,
+    /// Keeps settings on the program's monitor while its window stays visible there
+    #[structopt(long)]
+    sticky: bool,
+}
+
+/// Configuration files in merge order, and their validation
+pub struct Loaded {
+    main: std::path::PathBuf,
+    files: Vec<SourceFile>,
+    report: Report,
+}
+
+impl Loaded {
+    pub fn new(config_file: Option<String>, format: Option<Format>) -> VividResult<Self> {
+        let main = Config::discover(config_file)?.path;
+        let files = Config::read_files(&main, format)?;
+        let report = crate::validate::check_files(&files, &Config::environment(), Tests::Skipped);
+        if report.config.is_none() {
+            for error in report.errors() {
+                eprintln!("{}", error);
+            }
+            return Err(VividError::ConfigurationEdit(
+                "the configuration can't be parsed, fix it first".into(),
+            ));
+        }
+
+        Ok(Self {
+            main,
+            files,
+            report,
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        self.report.config.as_ref().unwrap()
+    }
+
+    /// Finds an entry by key, program name or rule name, and returns its section and index in the merged configuration
+    fn find(&self, target: &str) -> VividResult<(&'static str, usize)> {
+        let config = self.config();
+        let counts = [
+            ("rules", config.rules().len()),
+            ("program_settings", config.program_settings().len()),
+        ];
+
+        for &(section, count) in &counts {
+            let index = target
+                .strip_prefix(section)
+                .and_then(|rest| rest.strip_prefix('['))
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|index| index.parse::<usize>().ok());
+            if let Some(index) = index {
+                if index >= count {
+                    return Err(VividError::ConfigurationEdit(format!(
+                        "there's no {}, `{}` has {} entries",
+                        target, section, count
+                    )));
+                }
+                return Ok((section, index));
+            }
+        }
+
+        let matches: Vec<(&'static str, usize)> = config
+            .rules()
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.name.as_deref() == Some(target))
+            .map(|(index, _)| ("rules", index))
+            .chain(
+                config
+                    .program_settings()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, program)| program.exe_name.eq_ignore_ascii_case(target))
+                    .map(|(index, _)| ("program_settings", index)),
+            )
+            .collect();
+
+        match matches.as_slice() {
+            [found] => Ok(*found),
+            [] => Err(VividError::ConfigurationEdit(format!(
+                "there's no rule or program named \"{}\", see `vivid rule list`",
+                target
+            ))),
+            _ => Err(VividError::ConfigurationEdit(format!(
+                "\"{}\" matches several entries, use one of {}",
+                target,
+                matches
+                    .iter()
+                    .map(|(section, index)| format!("{}[{}]", section, index))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+
+    /// File an entry is declared in, and its index in this file
+    fn locate(&self, section: &str, index: usize) -> (usize, usize) {
+        let origin = self.report.origins.entries[section][index];
+        (origin.file, origin.index)
+    }
+
+    /// Index of the main configuration file, where new entries are added
+    pub fn main_file(&self) -> usize {
+        self.files
+            .iter()
+            .position(|file| file.path == self.main)
+            .unwrap_or(0)
+    }
+
+    /// Applies an edit to one of the files, and saves it if the configuration is still valid
+    pub fn edit(
+        mut self,
+        file: usize,
+        edit: impl FnOnce(&mut Document) -> Result<(), String>,
+    ) -> VividResult<()> {
+        // Comments and formatting can only be kept in TOML files
+        if self.files[file].format != Format::Toml {
+            return Err(VividError::ConfigurationEdit(format!(
+                "{} is a {} file, only TOML files are edited in place, convert it with `vivid config convert`",
+                self.files[file].path.display(),
+                self.files[file].format
+            )));
+        }
+
+        let mut document = Document::new(self.files[file].source.clone());
+        edit(&mut document).map_err(VividError::ConfigurationEdit)?;
+        self.files[file].source = document.source().into();
+
+        let path = self.files[file].path.clone();
+        let report =
+            crate::validate::check_files(&self.files, &Config::environment(), Tests::Skipped);
+        for diagnostic in &report.diagnostics {
+            eprintln!("{}", diagnostic);
+        }
+        if report.config.is_none() || report.has_errors() {
+            return Err(VividError::ConfigurationEdit(format!(
+                "the result is invalid, {} is left unchanged",
+                path.display()
+            )));
+        }
+
+        crate::config::write_atomically(&path, &self.files[file].source)?;
+        println!("Updated {}", path.display());
+        if crate::config_watch::notify_reload() {
+            println!("Vivid is reloading it");
+        } else {
+            println!("Vivid isn't running, the change applies when it starts");
+        }
+
+        Ok(())
+    }
+}
+
+pub fn run(opts: RuleOpts, config_file: Option<String>, format: Option<Format>) -> VividResult<()> {
+    let loaded = Loaded::new(config_file, format)?;
+    match opts.command {
+        RuleCommand::List => {
+            list(&loaded);
+            Ok(())
+        }
+        RuleCommand::Add(opts) => add(loaded, opts),
+        RuleCommand::Remove { target } => {
+            let (section, index) = loaded.find(&target)?;
+            let (file, local_index) = loaded.locate(section, index);
+            loaded.edit(file, |document| document.remove(section, local_index))
+        }
+        RuleCommand::Set { target, key, value } => {
+            let (section, index) = loaded.find(&target)?;
+            let keys = config_edit::settable_keys(section);
+            if !keys.contains(&key.as_str()) {
+                return Err(VividError::ConfigurationEdit(format!(
+                    "`{}` has no `{}` key, expected one of: {}",
+                    section,
+                    key,
+                    keys.join(", ")
+                )));
+            }
+
+            let value = config_edit::parse_value(&value);
+            let (file, local_index) = loaded.locate(section, index);
+            loaded.edit(file, |document| {
+                document.set(section, local_index, &key, &value)
+            })
+        }
+    }
+}
+
+fn list(loaded: &Loaded) {
+    let config = loaded.config();
+    let documents: Vec<Option<Document>> = loaded
+        .files
+        .iter()
+        .map(|file| {
+            Some(Document::new(file.source.clone())).filter(|_| file.format == Format::Toml)
+        })
+        .collect();
+    let declaration = |section: &str, index: usize| {
+        let (file, local_index) = loaded.locate(section, index);
+        let path = loaded.files[file].path.display();
+        match documents[file]
+            .as_ref()
+            .and_then(|document| document.entry_line(section, local_index))
+        {
+            Some(line) => format!("{}:{}", path, line),
+            None => path.to_string(),
+        }
+    };
+
+    for (index, rule) in config.rules().iter().enumerate() {
+        println!(
+            "rules[{}] {}: vibrance {}, priority {}{} ({})",
+            index,
+            rule.name
+                .as_ref()
+                .map_or_else(|| "<unnamed rule>".into(), |name| format!("\"{}\"", name)),
+            rule.vibrance,
+            rule.priority,
+            if rule.sticky.unwrap_or(false) {
+                ", sticky"
+            } else {
+                ""
+            },
+            declaration("rules", index)
+        );
+    }
+
+    for (index, program) in config.program_settings().iter().enumerate() {
+        let mut details = vec![format!("vibrance {}", program.vibrance)];
+        if let Some(priority) = program.priority {
+            details.push(format!("priority {}", priority));
+        }
+        if program.fullscreen_only.unwrap_or(false) {
+            details.push("fullscreen only".into());
+        }
+        if program.scope.unwrap_or_default() == Scope::Running {
+            details.push("while running".into());
+        }
+        if program.sticky.unwrap_or(false) {
+            details.push("sticky".into());
+        }
+        println!(
+            "program_settings[{}] {}: {} ({})",
+            index,
+            program.exe_name,
+            details.join(", "),
+            declaration("program_settings", index)
+        );
+    }
+}
+
+/// Adds the entry to the main configuration file
+fn add(loaded: Loaded, opts: AddOpts) -> VividResult<()> {
+    use toml::Value;
+
+    let mut fields: Vec<(&str, Value)> = vec![];
+    let section = match (&opts.exe, &opts.name) {
+        (_, Some(name)) => {
+            fields.push(("name", Value::String(name.clone())));
+            if let Some(priority) = opts.priority {
+                fields.push(("priority", Value::Integer(priority.into())));
+            }
+            fields.push(("vibrance", Value::Integer(opts.vibrance.into())));
+            let when = opts.when.as_deref().unwrap_or_default();
+            match config_edit::parse_value(when) {
+                condition @ Value::Table(_) => fields.push(("when", condition)),
+                _ => {
+                    return Err(VividError::ConfigurationEdit(format!(
+                        "the condition has to be an inline table, i.e. {{ exe = \"game.exe\" }}, not {}",
+                        when
+                    )))
+                }
+            }
+            "rules"
+        }
+        (Some(exe), None) => {
+            fields.push(("exe_name", Value::String(exe.clone())));
+            fields.push(("vibrance", Value::Integer(opts.vibrance.into())));
+            if opts.fullscreen_only {
+                fields.push(("fullscreen_only", Value::Boolean(true)));
+            }
+            if opts.running {
+                fields.push(("scope", Value::String("running".into())));
+            }
+            if let Some(priority) = opts.priority {
+                fields.push(("priority", Value::Integer(priority.into())));
+            }
+            "program_settings"
+        }
+        (None, None) => unreachable!("structopt requires either an exe or a name"),
+    };
+    if opts.sticky {
+        fields.push(("sticky", Value::Boolean(true)));
+    }
+
+    let file = loaded.main_file();
+    loaded.edit(file, |document| document.append(section, &fields))
+}
@@ -0,0 +1,97 @@
+use crate::config::Config;
+use crate::error::VividResult;
+use schemars::schema::{
+    ArrayValidation, InstanceType, Metadata, NumberValidation, RootSchema, Schema, SchemaObject,
+};
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct SchemaOpts {
+    /// Writes the schema to this file instead of printing it
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<std::path::PathBuf>,
+}
+
+fn described(description: &str, schema: SchemaObject) -> Schema {
+    SchemaObject {
+        metadata: Some(Box::new(Metadata {
+            description: Some(description.into()),
+            ..Default::default()
+        })),
+        ..schema
+    }
+    .into()
+}
+
+/// JSON Schema of configuration files, generated from the types they're deserialized into
+pub fn generate() -> RootSchema {
+    let mut settings = schemars::gen::SchemaSettings::draft07();
+    // TOML has no null, unset keys are simply omitted
+    settings.option_add_null_type = false;
+    let mut root = settings.into_generator().into_root_schema_for::<Config>();
+
+    let metadata = root.schema.metadata();
+    metadata.title = Some("Vivid configuration".into());
+    metadata.description = Some(
+        "Configuration file of Vivid. Files can be merged with includes and drop-in files, \
+         `desktop_vibrance` has to be set by one of them"
+            .into(),
+    );
+
+    // Keys handled before deserializing, and nothing is required in a single file
+    let object = root.schema.object();
+    object.required.clear();
+    let mut properties = schemars::Map::new();
+    properties.insert(
+        "version".into(),
+        described(
+            "Version of the configuration format, `vivid migrate` upgrades older files",
+            SchemaObject {
+                instance_type: Some(InstanceType::Integer.into()),
+                number: Some(Box::new(NumberValidation {
+                    minimum: Some(crate::migrate::UNVERSIONED.into()),
+                    maximum: Some(crate::migrate::CURRENT_VERSION.into()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        ),
+    );
+    properties.insert(
+        "include".into(),
+        described(
+            "Files merged before this one, relative to it",
+            SchemaObject {
+                instance_type: Some(InstanceType::Array.into()),
+                array: Some(Box::new(ArrayValidation {
+                    items: Some(
+                        Schema::Object(SchemaObject {
+                            instance_type: Some(InstanceType::String.into()),
+                            ..Default::default()
+                        })
+                        .into(),
+                    ),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        ),
+    );
+    properties.extend(std::mem::take(&mut object.properties));
+    object.properties = properties;
+
+    root
+}
+
+pub fn run(opts: SchemaOpts) -> VividResult<()> {
+    let schema = serde_json::to_string_pretty(&generate())
+        .expect("schemas only contain JSON-representable values");
+    match opts.output {
+        Some(path) => {
+            crate::config::write_atomically(&path, &(schema + "\n"))?;
+            println!("Wrote {}", path.display());
+        }
+        None => println!("{}", schema),
+    }
+
+    Ok(())
+}
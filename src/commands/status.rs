@@ -0,0 +1,79 @@
+use crate::config::Config;
+use crate::error::VividResult;
+use crate::format::Format;
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct StatusOpts {}
+
+pub fn run(_: StatusOpts, config_file: Option<String>, format: Option<Format>) -> VividResult<()> {
+    println!("Configuration lookup, in order:");
+    for candidate in Config::candidates(config_file.clone()) {
+        println!(
+            "  [{}] {} ({})",
+            if candidate.path.is_file() { "x" } else { " " },
+            candidate.path.display(),
+            candidate.source
+        );
+    }
+
+    let location = match Config::discover(config_file) {
+        Ok(location) => location,
+        Err(e) => {
+            println!("{}", e);
+            return Ok(());
+        }
+    };
+    println!(
+        "Using {} ({}, {})",
+        location.path.display(),
+        location.source,
+        Format::of_main(&location.path, format)
+    );
+
+    let files = match Config::read_files(&location.path, format) {
+        Ok(files) => files,
+        Err(e) => {
+            println!("Cannot read it: {}", e);
+            return Ok(());
+        }
+    };
+    for file in files.iter().filter(|file| file.path != location.path) {
+        println!("Merging {}", file.path.display());
+    }
+    for file in &files {
+        match crate::migrate::version(&file.source, file.format) {
+            Ok(version) if version < crate::migrate::CURRENT_VERSION => println!(
+                "{} is written for version {} of the configuration format, run `vivid migrate` to upgrade it to version {}",
+                file.path.display(),
+                version,
+                crate::migrate::CURRENT_VERSION
+            ),
+            _ => {}
+        }
+    }
+    let report = crate::validate::check_files(
+        &files,
+        &Config::environment(),
+        crate::validate::Tests::Skipped,
+    );
+    match &report.config {
+        Some(config) if !report.has_errors() => println!(
+            "Valid, {} warning(s): {} rule(s), {} program setting(s), {} test(s)",
+            report.warnings().count(),
+            config.rules().len(),
+            config.program_settings().len(),
+            config.tests().len()
+        ),
+        _ => println!(
+            "Invalid, {} error(s): run `vivid check` for details",
+            report.errors().count()
+        ),
+    }
+
+    match crate::adapter::Gpu::detect_gpu() {
+        Ok(gpu) => println!("GPU: {} ({:?}, {:?})", gpu.sku, gpu.vendor, gpu.system_type),
+        Err(e) => println!("GPU: {}", e),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use crate::config::Config;
+use crate::error::VividResult;
+use crate::format::Format;
+use crate::rule_tests::TestOutcome;
+use crate::schedule::Clock as _;
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct TestOpts {
+    /// Only runs the tests whose name contains this text
+    filter: Option<String>,
+}
+
+/// Prints test outcomes, passing ones only if `verbose`, and returns the number of failures
+pub fn print_outcomes(outcomes: &[TestOutcome], verbose: bool) -> usize {
+    let mut failures = 0;
+    for outcome in outcomes {
+        if !outcome.passed() {
+            failures += 1;
+            println!("{}", outcome);
+        } else if verbose {
+            println!("{}", outcome);
+        }
+    }
+
+    failures
+}
+
+pub fn run(opts: TestOpts, config_file: Option<String>, format: Option<Format>) -> VividResult<()> {
+    let path = Config::discover(config_file)?.path;
+    let files = Config::read_files(&path, format)?;
+    let report = crate::validate::check_files(
+        &files,
+        &Config::environment(),
+        crate::validate::Tests::Checked,
+    );
+    let config = match report.config {
+        Some(ref config) if !report.has_errors() => config,
+        _ => {
+            for error in report.errors() {
+                eprintln!("{}", error);
+            }
+            eprintln!(
+                "{}: the configuration is invalid, run `vivid check` for details",
+                path.display()
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let outcomes: Vec<TestOutcome> =
+        crate::rule_tests::run(config, &crate::schedule::SystemClock.now())
+            .into_iter()
+            .filter(|outcome| {
+                opts.filter
+                    .as_ref()
+                    .map_or(true, |filter| outcome.name.contains(filter.as_str()))
+            })
+            .collect();
+    let failures = print_outcomes(&outcomes, true);
+    println!(
+        "{}: {} passed, {} failed",
+        path.display(),
+        outcomes.len() - failures,
+        failures
+    );
+
+    if failures > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use crate::config::{Config, WindowState};
+use crate::error::VividResult;
+use crate::foreground_watch::{
+    ForegroundWatcher, ForegroundWatcherEvent, ForegroundWatcherEventKind,
+};
+use crate::format::Format;
+use crate::rules::Decision;
+use crate::schedule::Clock as _;
+
+/// Whether events are printed as JSON lines: callbacks are plain functions, options can't be passed to them
+static JSON: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct WatchOpts {
+    /// Prints an event per line as JSON instead of a table
+    #[structopt(long)]
+    json: bool,
+}
+
+/// What Vivid does for an event
+#[derive(Debug, serde::Serialize)]
+struct Action {
+    vibrance: u8,
+    /// Monitor the settings apply to
+    monitor: String,
+    sticky: bool,
+    /// The monitor is held by another sticky program, its current settings are kept
+    held: bool,
+    /// Other monitors going back to desktop vibrance, no sticky program holding them
+    desktop: Vec<String>,
+}
+
+/// An event, the rule it matched and what Vivid does, as printed with `--json`
+#[derive(Debug, serde::Serialize)]
+struct Line<'a> {
+    time: String,
+    event: &'static str,
+    pid: usize,
+    process_exe: &'a str,
+    process_path: &'a std::path::Path,
+    window_title: &'a str,
+    window_class: &'a str,
+    window_state: WindowState,
+    monitor: &'a str,
+    /// The window is in the ignore list, current settings are kept
+    ignored: bool,
+    /// Position of the winning rule, starting at 1 like `vivid explain` shows them
+    rule: Option<usize>,
+    rule_name: Option<&'a str>,
+    /// Unset when ignored
+    action: Option<Action>,
+}
+
+fn event_name(kind: ForegroundWatcherEventKind) -> &'static str {
+    match kind {
+        ForegroundWatcherEventKind::Foreground => "foreground",
+        ForegroundWatcherEventKind::StateChanged => "state_changed",
+        ForegroundWatcherEventKind::ProcessChanged => "process_changed",
+        ForegroundWatcherEventKind::ScheduleChanged => "schedule_changed",
+        ForegroundWatcherEventKind::PowerChanged => "power_changed",
+        ForegroundWatcherEventKind::ConfigReloaded => "config_reloaded",
+    }
+}
+
+/// Columns of the table: time, event, pid, program, state, monitor, rule, action
+const COLUMNS: [(&str, usize); 8] = [
+    ("TIME", 8),
+    ("EVENT", 16),
+    ("PID", 6),
+    ("PROGRAM", 24),
+    ("STATE", 10),
+    ("MONITOR", 14),
+    ("RULE", 24),
+    ("ACTION", 0),
+];
+
+/// Cuts text to a column's width, marking the cut
+fn fit(text: &str, width: usize) -> String {
+    if width == 0 || text.chars().count() <= width {
+        return format!("{:<width$}", text, width = width);
+    }
+    let mut fitted: String = text.chars().take(width - 1).collect();
+    fitted.push('~');
+    fitted
+}
+
+fn print_row(cells: &[String]) {
+    let row: Vec<String> = cells
+        .iter()
+        .zip(COLUMNS.iter())
+        .map(|(cell, (_, width))| fit(cell, *width))
+        .collect();
+    println!("{}", row.join(" ").trim_end());
+}
+
+fn print_event(event: &ForegroundWatcherEvent) -> VividResult<()> {
+    let config = unsafe { crate::CONFIG.as_ref()? };
+    // Shown like the event handler sees it: about the window below an ignored one
+    let subject = crate::foreground_callback::subject(config, event);
+    let event = subject.as_ref().unwrap_or(event);
+    let now = crate::schedule::SystemClock.now();
+    let decision = crate::foreground_callback::decide(
+        config,
+        event,
+        now,
+        crate::power::current_status(),
+        &crate::process_watch::is_running,
+    );
+    let (rule, rule_name, action) = match &decision {
+        Decision::Ignored => (None, None, None),
+        Decision::Evaluated(evaluation) => {
+            let sticky = crate::foreground_callback::is_sticky(evaluation, event);
+            let monitor = crate::foreground_callback::target_monitor(event).unwrap_or_default();
+            // Same bookkeeping as the event handler, so sticky programs hold monitors the same way
+            let holds = crate::foreground_callback::update_holds(event, sticky, &monitor)?;
+            let action = Action {
+                vibrance: evaluation.vibrance,
+                desktop: holds.idle,
+                monitor,
+                sticky,
+                held: holds.held,
+            };
+            (
+                evaluation.winner.map(|index| index + 1),
+                evaluation
+                    .winning_rule()
+                    .and_then(|outcome| outcome.rule.name.as_deref()),
+                Some(action),
+            )
+        }
+    };
+
+    if JSON.load(std::sync::atomic::Ordering::Relaxed) {
+        let line = Line {
+            time: now.to_rfc3339(),
+            event: event_name(event.kind),
+            pid: event.process_id,
+            process_exe: &event.process_exe,
+            process_path: &event.process_path,
+            window_title: &event.window_title,
+            window_class: &event.window_class,
+            window_state: event.window_state,
+            monitor: &event.monitor,
+            ignored: action.is_none(),
+            rule,
+            rule_name,
+            action,
+        };
+        match serde_json::to_string(&line) {
+            Ok(line) => println!("{}", line),
+            Err(e) => log::error!("Cannot write the event as JSON: {}", e),
+        }
+        return Ok(());
+    }
+
+    let rule = match (rule, rule_name) {
+        (Some(index), Some(name)) => format!("#{} {}", index, name),
+        (Some(index), None) => format!("#{}", index),
+        (None, _) if action.is_some() => "none, desktop".into(),
+        (None, _) => "ignored".into(),
+    };
+    let action = match action {
+        Some(action) => {
+            let mut text = if action.held {
+                format!("keep current settings, {} is held", action.monitor)
+            } else {
+                format!("vibrance {}% on {}", action.vibrance, action.monitor)
+            };
+            if action.sticky {
+                text += ", sticky";
+            }
+            for monitor in &action.desktop {
+                text += &format!(", desktop vibrance on {}", monitor);
+            }
+            text
+        }
+        None => "keep current settings".into(),
+    };
+    print_row(&[
+        now.format("%H:%M:%S").to_string(),
+        event_name(event.kind).into(),
+        event.process_id.to_string(),
+        event.process_exe.clone(),
+        format!("{:?}", event.window_state).to_lowercase(),
+        event.monitor.clone(),
+        rule,
+        action,
+    ]);
+    println!(
+        "{:>9}{} \"{}\" (class {})",
+        "",
+        event.process_path.display(),
+        event.window_title,
+        event.window_class
+    );
+
+    Ok(())
+}
+
+pub fn run(
+    opts: WatchOpts,
+    config_file: Option<String>,
+    format: Option<Format>,
+) -> VividResult<()> {
+    // Rules are evaluated with the same global configuration the event handler uses
+    let config_location = Config::discover(config_file)?;
+    unsafe {
+        crate::CONFIG = Ok(Config::load_path(&config_location.path, format)?);
+    }
+    JSON.store(opts.json, std::sync::atomic::Ordering::Relaxed);
+
+    let mut watcher = ForegroundWatcher::new();
+    watcher.add_event_callback(print_event);
+    watcher.register()?;
+    // Programs rules depend on starting or exiting, and schedule boundaries, change settings too
+    let mut process_watcher = crate::process_watch::ProcessWatcher::new();
+    process_watcher.add_event_callback(crate::foreground_callback::process_handler);
+    process_watcher.register()?;
+    let mut schedule_watcher = crate::schedule_watch::ScheduleWatcher::new();
+    schedule_watcher.register()?;
+    // Only laptops can switch between AC and battery, without a GPU adapter at hand the battery tells
+    let mut power_watcher = crate::power_watch::PowerWatcher::new();
+    if crate::power::current_status().battery_percent.is_some() {
+        power_watcher.register()?;
+    }
+    // Reloads show as `config_reloaded` events, with the rules of the new configuration
+    let mut config_watcher = crate::config_watch::ConfigWatcher::new(config_location.path, format);
+    config_watcher.register()?;
+
+    if !opts.json {
+        eprintln!(
+            "Watching foreground events, press Ctrl+C to stop. Settings aren't applied, only shown"
+        );
+        print_row(
+            &COLUMNS
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect::<Vec<_>>(),
+        );
+    }
+    print_event(&ForegroundWatcher::current_event(
+        ForegroundWatcherEventKind::Foreground,
+    ))?;
+    unsafe { crate::w32_ctrlc::init_ctrlc()? };
+
+    let mut msg = unsafe { std::mem::zeroed() };
+    loop {
+        crate::w32_msgloop::read_message(&mut msg)?;
+        if crate::w32_msgloop::process_message(&msg) {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -1,12 +1,38 @@
 use crate::error::VividError;
-use winapi::{
-    shared::ntdef::NULL,
-    um::{shellapi::ShellExecuteA, winuser::SW_SHOWNORMAL},
-};
+use crate::format::Format;
+use crate::power::PowerCondition;
+use crate::rule_tests::TestCase;
+use crate::rules::Rule;
+use crate::schedule::{Coordinates, Schedule};
 
 pub const DEFAULT_CONFIG_FILENAME: &str = "vivid.toml";
+/// Directory next to the configuration file whose `*.toml`, `*.json` and `*.yaml` files are merged into it
+pub const DROP_IN_DIRECTORY: &str = "vivid.d";
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Replaces the contents of a file without ever leaving it half written:
+/// the contents go to a temporary file next to it, which then replaces it
+pub fn write_atomically(path: &std::path::Path, contents: &str) -> crate::VividResult<()> {
+    use std::io::Write as _;
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    let temporary_path = std::path::PathBuf::from(temporary_path);
+
+    let result = std::fs::File::create(&temporary_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temporary_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary_path);
+    }
+
+    result.map_err(Into::into)
+}
+
+#[derive(
+    Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct VideoMode {
     /// Screen pixel width
     pub width: u32,
@@ -16,26 +42,310 @@ pub struct VideoMode {
     pub freq: u32,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+impl std::fmt::Display for VideoMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}@{}Hz", self.width, self.height, self.freq)
+    }
+}
+
+/// State of a window relative to the monitor it's displayed on
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowState {
+    /// Exclusive fullscreen (D3D fullscreen)
+    Fullscreen,
+    /// Borderless window covering the whole monitor
+    Borderless,
+    /// Any other window
+    Windowed,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct WindowStateVibrance {
+    /// Vibrance to apply when the program is in exclusive fullscreen
+    #[schemars(range(max = 100))]
+    pub fullscreen: Option<u8>,
+    /// Vibrance to apply when the program is a borderless window covering its monitor
+    #[schemars(range(max = 100))]
+    pub borderless: Option<u8>,
+    /// Vibrance to apply when the program is windowed
+    #[schemars(range(max = 100))]
+    pub windowed: Option<u8>,
+}
+
+impl WindowStateVibrance {
+    pub fn get(&self, state: WindowState) -> Option<u8> {
+        match state {
+            WindowState::Fullscreen => self.fullscreen,
+            WindowState::Borderless => self.borderless,
+            WindowState::Windowed => self.windowed,
+        }
+    }
+}
+
+/// When program settings should be active
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// While the program is in foreground
+    Focused,
+    /// As long as the program is running, settings get reverted as soon as it exits
+    Running,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self::Focused
+    }
+}
+
+/// Requirements on other running programs for settings to apply
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct ProcessCondition {
+    /// Programs that all have to be running
+    #[serde(default)]
+    pub running: Vec<String>,
+    /// Programs that must not be running
+    #[serde(default)]
+    pub not_running: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct Program {
     /// Name of the program to react on
     pub exe_name: String,
     /// Vibrance value in percentage to apply when this program comes to foreground.
+    #[schemars(range(max = 100))]
     pub vibrance: u8,
     /// Only apply settings when the program comes to foreground in FullScreen mode
     pub fullscreen_only: Option<bool>,
+    /// Whether settings apply while the program is focused (default) or while it's running
+    pub scope: Option<Scope>,
+    /// Keep settings on the program's monitor as long as its window stays visible there,
+    /// focusing windows on other monitors won't revert them
+    pub sticky: Option<bool>,
+    /// Settings with a higher priority win over others, declaration order breaks ties. Defaults to 0
+    pub priority: Option<i32>,
     /// Only apply this video mode when this program starts
     pub resolution: Option<VideoMode>,
+    /// Vibrance overrides depending on the window state of the program
+    pub window_states: Option<WindowStateVibrance>,
+    /// Only apply these settings during this schedule
+    pub schedule: Option<Schedule>,
+    /// Only apply these settings when power requirements are met (AC/battery, battery charge)
+    pub power: Option<PowerCondition>,
+    /// Only apply these settings when other programs are running or not
+    pub processes: Option<ProcessCondition>,
+}
+
+/// Programs shipped in the built-in ignore list: overlays, screenshot tools...
+pub const DEFAULT_IGNORED_PROGRAMS: &[&str] = &[
+    "GameOverlayUI.exe",
+    "NVIDIA Share.exe",
+    "GameBar.exe",
+    "ScreenClippingHost.exe",
+    "SnippingTool.exe",
+    "ShareX.exe",
+];
+
+/// Window classes shipped in the built-in ignore list: alt-tab host, volume OSD...
+pub const DEFAULT_IGNORED_WINDOW_CLASSES: &[&str] = &[
+    "MultitaskingViewFrame",
+    "XamlExplorerHostIslandWindow",
+    "TaskSwitcherWnd",
+    "ForegroundStaging",
+    "NativeHWNDHost",
+];
+
+/// Windows that never trigger a settings change when coming to foreground, the current settings are kept instead
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct IgnoreList {
+    /// Whether to use the built-in ignore list. Defaults to true
+    pub defaults: Option<bool>,
+    /// Names of programs to ignore
+    #[serde(default)]
+    pub programs: Vec<String>,
+    /// Window classes to ignore
+    #[serde(default)]
+    pub window_classes: Vec<String>,
+}
+
+impl IgnoreList {
+    pub fn is_ignored(&self, program_exe: &str, window_class: &str) -> bool {
+        let use_defaults = self.defaults.unwrap_or(true);
+        let program_ignored = self
+            .programs
+            .iter()
+            .map(String::as_str)
+            .chain(
+                DEFAULT_IGNORED_PROGRAMS
+                    .iter()
+                    .copied()
+                    .filter(|_| use_defaults),
+            )
+            .any(|ignored| ignored.eq_ignore_ascii_case(program_exe));
+
+        program_ignored
+            || self
+                .window_classes
+                .iter()
+                .map(String::as_str)
+                .chain(
+                    DEFAULT_IGNORED_WINDOW_CLASSES
+                        .iter()
+                        .copied()
+                        .filter(|_| use_defaults),
+                )
+                .any(|ignored| ignored == window_class)
+    }
+}
+
+/// Desktop vibrance to use during a schedule
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DesktopSchedule {
+    /// Vibrance to restore when any non-selected program comes to foreground while the schedule is active
+    #[schemars(range(max = 100))]
+    pub vibrance: u8,
+    /// When this vibrance is active
+    pub when: Schedule,
+}
+
+/// Environment variable holding the path of the configuration file
+pub const CONFIG_PATH_ENV: &str = "VIVID_CONFIG";
+
+/// How the configuration file has been found
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ConfigSource {
+    /// `--config` command line option
+    CommandLine,
+    /// `VIVID_CONFIG` environment variable
+    Environment,
+    /// Per-user configuration directory
+    UserDirectory,
+    /// Next to the executable (portable mode)
+    Portable,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CommandLine => write!(f, "--config option"),
+            Self::Environment => write!(f, "{} environment variable", CONFIG_PATH_ENV),
+            Self::UserDirectory => write!(f, "user configuration directory"),
+            Self::Portable => write!(f, "next to the executable, portable mode"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigLocation {
+    pub path: std::path::PathBuf,
+    pub source: ConfigSource,
+}
+
+impl ConfigLocation {
+    /// Explicitly provided locations are used even if the file doesn't exist
+    pub fn is_explicit(&self) -> bool {
+        matches!(
+            self.source,
+            ConfigSource::CommandLine | ConfigSource::Environment
+        )
+    }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct Config {
     /// Vibrance to restore when any non-selected program comes to foreground, included explorer.exe
+    #[schemars(range(max = 100))]
     desktop_vibrance: u8,
     /// Default desktop resolution
     resolution: Option<VideoMode>,
     /// Program-specific settings
+    #[serde(default)]
     program_settings: Vec<Program>,
+    /// Rules with conditions and priorities, checked before program settings of the same priority
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rules: Vec<Rule>,
+    /// Programs and windows that never trigger a settings change
+    ignore: Option<IgnoreList>,
+    /// Coordinates used to compute sunrise and sunset times in schedules
+    location: Option<Coordinates>,
+    /// Desktop vibrance overrides by schedule, the first active one wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    desktop_schedules: Vec<DesktopSchedule>,
+    /// Test cases for the rules, run by `vivid check` and `vivid test`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tests: Vec<TestCase>,
+}
+
+/// A configuration file before merging: every key is optional, and it can include other files
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct ConfigFragment {
+    /// Files merged before this one, relative to it
+    #[serde(default)]
+    pub include: Vec<String>,
+    desktop_vibrance: Option<u8>,
+    resolution: Option<VideoMode>,
+    #[serde(default)]
+    program_settings: Vec<Program>,
+    #[serde(default)]
+    rules: Vec<Rule>,
+    ignore: Option<IgnoreList>,
+    location: Option<Coordinates>,
+    #[serde(default)]
+    desktop_schedules: Vec<DesktopSchedule>,
+    #[serde(default)]
+    tests: Vec<TestCase>,
+}
+
+/// Removes `.` and `..` components, so a file included through different relative paths is recognized
+fn normalize(path: &std::path::Path) -> std::path::PathBuf {
+    use std::path::Component;
+    let mut normalized = std::path::PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Just the includes of a configuration file, read before the file is merged
+#[derive(Debug, Default, serde::Deserialize)]
+struct Includes {
+    #[serde(default)]
+    include: Vec<String>,
+}
+
+/// Position of a merged entry in the files it has been merged from
+#[derive(Debug, Clone, Copy)]
+pub struct Origin {
+    /// Index of the file, in merge order
+    pub file: usize,
+    /// Index of the entry within its section in this file
+    pub index: usize,
+}
+
+/// Where the keys of a merged configuration come from
+#[derive(Debug, Clone, Default)]
+pub struct Origins {
+    /// File each top-level key (`desktop_vibrance`, `resolution`, `location`) comes from
+    pub keys: std::collections::BTreeMap<&'static str, usize>,
+    /// Origin of each entry, by section
+    pub entries: std::collections::BTreeMap<&'static str, Vec<Origin>>,
 }
 
 impl Default for Config {
@@ -43,100 +353,547 @@ impl Default for Config {
         Self {
             desktop_vibrance: 50,
             program_settings: vec![],
+            rules: vec![],
             resolution: None,
+            ignore: None,
+            location: None,
+            desktop_schedules: vec![],
+            tests: vec![],
         }
     }
 }
 
 impl Config {
-    fn sample() -> crate::VividResult<Self> {
-        let vibrance = unsafe { crate::GPU.as_ref()?.write().get_vibrance()? };
-        let mut default = Self::default();
-        default.desktop_vibrance = vibrance;
-        default.program_settings.push(Program {
-            exe_name: "sample_program.exe".into(),
-            vibrance,
-            fullscreen_only: Some(false),
-            resolution: None,
-        });
+    /// Per-user configuration directory: `%APPDATA%\vivid` on Windows, `$XDG_CONFIG_HOME/vivid` elsewhere
+    pub fn user_directory() -> Option<std::path::PathBuf> {
+        #[cfg(windows)]
+        let base = std::env::var_os("APPDATA").map(std::path::PathBuf::from);
+        #[cfg(not(windows))]
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(std::path::PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| std::path::PathBuf::from(home).join(".config"))
+            });
 
-        Ok(default)
-    }
-
-    fn config_path() -> crate::VividResult<std::path::PathBuf> {
-        let mut path = std::env::current_exe()?;
-        path.set_file_name(DEFAULT_CONFIG_FILENAME);
-        Ok(path)
-    }
-
-    fn load_file(maybe_path: Option<String>) -> crate::VividResult<std::fs::File> {
-        use std::io::Write as _;
-        let path = maybe_path.map_or_else(|| Self::config_path(), |path| Ok(path.into()))?;
-        let res = std::fs::OpenOptions::new()
-            .write(true)
-            .read(true)
-            .create_new(true)
-            .open(path.clone());
-
-        if let Ok(mut file) = res {
-            write!(file, "{}", toml::to_string_pretty(&Self::sample()?)?)?;
-            Ok(file)
-        } else {
-            let file = std::fs::OpenOptions::new()
-                .write(true)
-                .read(true)
-                .truncate(false)
-                .open(path)?;
-
-            Ok(file)
-        }
-    }
-
-    /// Loads the configuration file at the standard location (alongside the .exe)
-    pub fn load(maybe_path: Option<String>) -> crate::VividResult<Self> {
-        use std::io::Read as _;
-        let mut file = Self::load_file(maybe_path)?;
-        let mut file_contents = vec![];
-        file.read_to_end(&mut file_contents)?;
-        toml::from_slice(&file_contents).map_err(Into::into)
-    }
-
-    /// Launches windows standard editor for this file.
-    pub fn edit() -> crate::VividResult<()> {
-        let _ = Self::load_file(None)?;
-        let file_path =
-            std::ffi::CString::new(Self::config_path()?.to_str().unwrap().as_bytes()).unwrap();
-        let hwnd = unsafe {
-            ShellExecuteA(
-                NULL as _,
-                NULL as _,
-                file_path.as_ptr(),
-                NULL as _,
-                NULL as _,
-                SW_SHOWNORMAL,
-            )
-        };
+        base.map(|base| base.join("vivid"))
+    }
 
-        if hwnd as u32 > 32 {
-            Ok(())
-        } else {
-            return Err(VividError::windows_error());
+    /// Every location the configuration file is looked up at, in order
+    pub fn candidates(maybe_path: Option<String>) -> Vec<ConfigLocation> {
+        let mut candidates = vec![];
+        if let Some(path) = maybe_path {
+            candidates.push(ConfigLocation {
+                path: path.into(),
+                source: ConfigSource::CommandLine,
+            });
         }
+        if let Some(path) = std::env::var_os(CONFIG_PATH_ENV).filter(|path| !path.is_empty()) {
+            candidates.push(ConfigLocation {
+                path: path.into(),
+                source: ConfigSource::Environment,
+            });
+        }
+        // vivid.toml, then vivid.json and vivid.yaml
+        let file_names = || {
+            Format::ALL.iter().map(|format| {
+                std::path::Path::new(DEFAULT_CONFIG_FILENAME).with_extension(format.extension())
+            })
+        };
+        if let Some(directory) = Self::user_directory() {
+            candidates.extend(file_names().map(|file_name| ConfigLocation {
+                path: directory.join(file_name),
+                source: ConfigSource::UserDirectory,
+            }));
+        }
+        if let Ok(executable) = std::env::current_exe() {
+            candidates.extend(file_names().map(|file_name| ConfigLocation {
+                path: executable.with_file_name(file_name),
+                source: ConfigSource::Portable,
+            }));
+        }
+
+        candidates
     }
 
-    pub fn vibrance_for_program(&self, program_exe: &str) -> Option<(u8, bool)> {
-        self.program_settings
+    /// Finds the configuration file: `--config`, `VIVID_CONFIG`, the user directory, then next to the executable.
+    /// Explicitly provided paths are used even if they don't exist
+    pub fn discover(maybe_path: Option<String>) -> crate::VividResult<ConfigLocation> {
+        let candidates = Self::candidates(maybe_path);
+        candidates
             .iter()
-            .find(|&program| program.exe_name == program_exe)
-            .map(|program| {
-                (
-                    program.vibrance,
-                    program.fullscreen_only.unwrap_or_default(),
+            .find(|candidate| candidate.is_explicit() || candidate.path.is_file())
+            .cloned()
+            .ok_or_else(|| {
+                VividError::NoConfigurationFound(
+                    candidates
+                        .iter()
+                        .map(|candidate| format!("  {}", candidate.path.display()))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
                 )
             })
     }
 
-    pub fn default_vibrance(&self) -> u8 {
+    /// Loads and validates the configuration file. The file is never written to
+    pub fn load(maybe_path: Option<String>, format: Option<Format>) -> crate::VividResult<Self> {
+        let location = Self::discover(maybe_path)?;
+        log::info!(
+            "Loading configuration from {} ({})",
+            location.path.display(),
+            location.source
+        );
+        Self::load_path(&location.path, format)
+    }
+
+    /// Loads and validates a configuration file along with the files it includes and its drop-in directory
+    pub fn load_path(path: &std::path::Path, format: Option<Format>) -> crate::VividResult<Self> {
+        let files = Self::read_files(path, format)?;
+        for file in files.iter().filter(|file| file.path != path) {
+            log::info!("Merging {}", file.path.display());
+        }
+        for file in &files {
+            match crate::migrate::version(&file.source, file.format) {
+                Ok(version) if version < crate::migrate::CURRENT_VERSION => log::info!(
+                    "{} is written for version {} of the configuration format, run `vivid migrate` to upgrade it",
+                    file.path.display(),
+                    version
+                ),
+                _ => {}
+            }
+        }
+        Self::from_files(&files)
+    }
+
+    /// Reads a configuration file, the files it includes and the files in `vivid.d` next to it, in merge order:
+    /// included files come before the file including them, and drop-in files come last, sorted by name.
+    /// A file included several times is only merged once.
+    /// The format of each file comes from its extension, `--format` applies to the main one
+    pub fn read_files(
+        path: &std::path::Path,
+        format: Option<Format>,
+    ) -> crate::VividResult<Vec<crate::validate::SourceFile>> {
+        let mut files = vec![];
+        Self::read_with_includes(path, Format::of_main(path, format), &mut vec![], &mut files)?;
+
+        let mut drop_ins: Vec<std::path::PathBuf> =
+            match std::fs::read_dir(path.with_file_name(DROP_IN_DIRECTORY)) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.is_file() && Format::from_extension(path).is_some())
+                    .collect(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+                Err(e) => {
+                    return Err(VividError::ConfigurationFileError(
+                        path.with_file_name(DROP_IN_DIRECTORY),
+                        e,
+                    ))
+                }
+            };
+        drop_ins.sort();
+        for drop_in in drop_ins {
+            Self::read_with_includes(&drop_in, Format::of(&drop_in), &mut vec![], &mut files)?;
+        }
+
+        Ok(files)
+    }
+
+    fn read_with_includes(
+        path: &std::path::Path,
+        format: Format,
+        including: &mut Vec<std::path::PathBuf>,
+        files: &mut Vec<crate::validate::SourceFile>,
+    ) -> crate::VividResult<()> {
+        if including.iter().any(|parent| parent == path) {
+            return Err(VividError::IncludeCycle(path.to_path_buf()));
+        }
+        if files.iter().any(|file| file.path == path) {
+            return Ok(());
+        }
+
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| VividError::ConfigurationFileError(path.to_path_buf(), e))?;
+        // Syntax errors are reported when the file is validated
+        let includes = format.parse::<Includes>(&source).unwrap_or_default();
+        including.push(path.to_path_buf());
+        for include in includes.include {
+            let include_path = normalize(&path.with_file_name(include));
+            let include_format = Format::of(&include_path);
+            Self::read_with_includes(&include_path, include_format, including, files)?;
+        }
+        including.pop();
+
+        files.push(crate::validate::SourceFile {
+            path: path.to_path_buf(),
+            format,
+            source,
+        });
+        Ok(())
+    }
+
+    /// Merges configuration files, provided in merge order. Files merged later take precedence:
+    /// - `desktop_vibrance`, `resolution` and `location` are taken from the last file setting them
+    /// - `program_settings`, `rules` and `desktop_schedules` entries of later files come first, so they win ties,
+    ///   while entries of a same file keep their order
+    /// - `ignore.defaults` is taken from the last file setting it, ignored programs and window classes add up
+    /// - `tests` add up, in merge order
+    pub fn merge(fragments: Vec<ConfigFragment>) -> Result<(Self, Origins), String> {
+        fn prepend<T>(
+            merged: &mut Vec<T>,
+            origins: &mut Vec<Origin>,
+            file: usize,
+            entries: Vec<T>,
+        ) {
+            origins.splice(0..0, (0..entries.len()).map(|index| Origin { file, index }));
+            merged.splice(0..0, entries);
+        }
+
+        let mut config = Self::default();
+        let mut origins = Origins::default();
+        let mut desktop_vibrance = None;
+        for (file, fragment) in fragments.into_iter().enumerate() {
+            if fragment.desktop_vibrance.is_some() {
+                desktop_vibrance = fragment.desktop_vibrance;
+                origins.keys.insert("desktop_vibrance", file);
+            }
+            if fragment.resolution.is_some() {
+                config.resolution = fragment.resolution;
+                origins.keys.insert("resolution", file);
+            }
+            if fragment.location.is_some() {
+                config.location = fragment.location;
+                origins.keys.insert("location", file);
+            }
+            if let Some(ignore) = fragment.ignore {
+                let merged = config.ignore.get_or_insert_with(Default::default);
+                if ignore.defaults.is_some() {
+                    merged.defaults = ignore.defaults;
+                }
+                merged.programs.extend(ignore.programs);
+                merged.window_classes.extend(ignore.window_classes);
+            }
+
+            prepend(
+                &mut config.program_settings,
+                origins.entries.entry("program_settings").or_default(),
+                file,
+                fragment.program_settings,
+            );
+            prepend(
+                &mut config.rules,
+                origins.entries.entry("rules").or_default(),
+                file,
+                fragment.rules,
+            );
+            prepend(
+                &mut config.desktop_schedules,
+                origins.entries.entry("desktop_schedules").or_default(),
+                file,
+                fragment.desktop_schedules,
+            );
+            origins
+                .entries
+                .entry("tests")
+                .or_default()
+                .extend((0..fragment.tests.len()).map(|index| Origin { file, index }));
+            config.tests.extend(fragment.tests);
+        }
+
+        config.desktop_vibrance =
+            desktop_vibrance.ok_or("missing field `desktop_vibrance`, no file sets it")?;
+        Ok((config, origins))
+    }
+
+    /// Parses and validates a TOML configuration that doesn't come from a file
+    pub fn from_source(source: &str) -> crate::VividResult<Self> {
+        Self::from_files(&[crate::validate::SourceFile {
+            path: Default::default(),
+            format: Format::Toml,
+            source: source.into(),
+        }])
+    }
+
+    /// Parses and validates a TOML fixture, without looking at the system's displays
+    #[cfg(test)]
+    pub fn fixture(source: &str) -> Self {
+        let report = crate::validate::check_files(
+            &[crate::validate::SourceFile {
+                path: Default::default(),
+                format: Format::Toml,
+                source: source.into(),
+            }],
+            &Default::default(),
+            crate::validate::Tests::Checked,
+        );
+        let errors: Vec<String> = report.errors().map(ToString::to_string).collect();
+        match report.config {
+            Some(config) if errors.is_empty() => config,
+            _ => panic!("invalid fixture:\n{}", errors.join("\n")),
+        }
+    }
+
+    /// Merges and validates configuration files. Warnings are logged, errors refuse the configuration.
+    /// Test cases aren't checked, they don't change what Vivid does
+    pub fn from_files(files: &[crate::validate::SourceFile]) -> crate::VividResult<Self> {
+        let report = crate::validate::check_files(
+            files,
+            &Self::environment(),
+            crate::validate::Tests::Skipped,
+        );
+        for warning in report.warnings() {
+            log::warn!("Configuration {}", warning);
+        }
+
+        match report.config {
+            Some(config) if !report.has_errors() => Ok(config),
+            _ => Err(VividError::InvalidConfiguration(
+                report
+                    .errors()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )),
+        }
+    }
+
+    /// Connected displays and supported video modes, to validate configurations against
+    pub fn environment() -> crate::validate::Environment {
+        let displays = crate::adapter::Gpu::get_monitor_names();
+        let video_modes = crate::adapter::Gpu::get_primary_monitor_name()
+            .and_then(|name| crate::adapter::Gpu::get_video_modes(&name));
+        crate::validate::Environment {
+            displays: displays.ok().filter(|displays| !displays.is_empty()),
+            video_modes: video_modes.ok().filter(|modes| !modes.is_empty()),
+        }
+    }
+
+    /// Desktop vibrance, taking schedules into account
+    pub fn desktop_vibrance_at(&self, now: &chrono::DateTime<chrono::FixedOffset>) -> u8 {
+        self.desktop_schedules
+            .iter()
+            .find(|schedule| schedule.when.is_active(now, self.location.as_ref()))
+            .map_or(self.desktop_vibrance, |schedule| schedule.vibrance)
+    }
+
+    /// Next point in time where any schedule might become active or inactive
+    pub fn next_schedule_boundary(
+        &self,
+        now: &chrono::DateTime<chrono::FixedOffset>,
+    ) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        self.schedules()
+            .into_iter()
+            .filter_map(|schedule| schedule.next_boundary(now, self.location.as_ref()))
+            .min()
+    }
+
+    /// Every schedule of `program_settings`, `rules` and `desktop_schedules`
+    pub fn schedules(&self) -> Vec<&Schedule> {
+        self.program_settings
+            .iter()
+            .filter_map(|program| program.schedule.as_ref())
+            .chain(self.rules.iter().flat_map(|rule| rule.when.schedules()))
+            .chain(self.desktop_schedules.iter().map(|schedule| &schedule.when))
+            .collect()
+    }
+
+    /// Every power condition of `program_settings` and `rules`
+    pub fn power_conditions(&self) -> Vec<&PowerCondition> {
+        self.program_settings
+            .iter()
+            .filter_map(|program| program.power.as_ref())
+            .chain(
+                self.rules
+                    .iter()
+                    .flat_map(|rule| rule.when.power_conditions()),
+            )
+            .collect()
+    }
+
+    /// Tells if this window should be treated as a non-event
+    pub fn is_ignored(&self, program_exe: &str, window_class: &str) -> bool {
+        self.ignore.as_ref().map_or_else(
+            || IgnoreList::default().is_ignored(program_exe, window_class),
+            |ignore| ignore.is_ignored(program_exe, window_class),
+        )
+    }
+
+    pub fn desktop_vibrance(&self) -> u8 {
         self.desktop_vibrance
     }
+
+    pub fn desktop_schedules(&self) -> &[DesktopSchedule] {
+        &self.desktop_schedules
+    }
+
+    pub fn program_settings(&self) -> &[Program] {
+        &self.program_settings
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn tests(&self) -> &[TestCase] {
+        &self.tests
+    }
+
+    pub fn location(&self) -> Option<&Coordinates> {
+        self.location.as_ref()
+    }
+
+    pub fn desktop_resolution(&self) -> Option<&VideoMode> {
+        self.resolution.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("merge")
+    }
+
+    /// Files of the fixture, with paths relative to its directory
+    fn read_fixture(name: &str) -> Vec<crate::validate::SourceFile> {
+        let root = fixtures();
+        let mut files = Config::read_files(&root.join(name), None).unwrap();
+        for file in &mut files {
+            file.path = file.path.strip_prefix(&root).unwrap().to_path_buf();
+        }
+        files
+    }
+
+    fn merge_fixture(name: &str) -> (Config, Origins) {
+        let fragments = read_fixture(name)
+            .iter()
+            .map(|file| file.format.parse::<ConfigFragment>(&file.source).unwrap())
+            .collect();
+        Config::merge(fragments).unwrap()
+    }
+
+    fn origins(origins: &Origins, section: &str) -> Vec<(usize, usize)> {
+        origins.entries[section]
+            .iter()
+            .map(|origin| (origin.file, origin.index))
+            .collect()
+    }
+
+    #[test]
+    fn merge_order() {
+        let paths: Vec<String> = read_fixture("vivid.toml")
+            .iter()
+            .map(|file| file.path.to_string_lossy().replace('\\', "/"))
+            .collect();
+        // Included files first, each of them once, then drop-in files sorted by name
+        assert_eq!(
+            paths,
+            [
+                "common.toml",
+                "games/shooters.toml",
+                "vivid.toml",
+                "vivid.d/10-work.json",
+                "vivid.d/20-night.toml",
+            ]
+        );
+    }
+
+    #[test]
+    fn include_cycles() {
+        let path = fixtures().join("cycle").join("a.toml");
+        match Config::read_files(&path, None) {
+            Err(VividError::IncludeCycle(cycle)) => assert_eq!(cycle, path),
+            other => panic!("expected an include cycle, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn last_file_wins_for_scalar_keys() {
+        let (config, origins) = merge_fixture("vivid.toml");
+        assert_eq!(config.desktop_vibrance(), 30);
+        assert_eq!(config.desktop_resolution().unwrap().width, 1920);
+        assert_eq!(config.location().unwrap().latitude, 48.85);
+        assert_eq!(
+            origins
+                .keys
+                .iter()
+                .map(|(key, file)| (*key, *file))
+                .collect::<Vec<_>>(),
+            [("desktop_vibrance", 4), ("location", 0), ("resolution", 1)]
+        );
+
+        let ignore = config.ignore.as_ref().unwrap();
+        assert_eq!(ignore.defaults, Some(false));
+        assert_eq!(ignore.programs, ["common.exe", "night.exe"]);
+        assert_eq!(ignore.window_classes, ["WorkClass"]);
+    }
+
+    #[test]
+    fn later_files_win_ties() {
+        let (config, origins) = merge_fixture("vivid.toml");
+        let rules: Vec<_> = config
+            .rules()
+            .iter()
+            .map(|rule| rule.name.as_deref().unwrap())
+            .collect();
+        assert_eq!(rules, ["Night", "Main", "Common"]);
+        assert_eq!(self::origins(&origins, "rules"), [(4, 0), (2, 0), (0, 0)]);
+
+        let programs: Vec<_> = config
+            .program_settings()
+            .iter()
+            .map(|program| program.exe_name.as_str())
+            .collect();
+        assert_eq!(programs, ["work.exe", "editor.exe", "csgo.exe"]);
+        assert_eq!(
+            self::origins(&origins, "program_settings"),
+            [(3, 0), (2, 0), (1, 0)]
+        );
+
+        // Tests keep merge order
+        let tests: Vec<_> = config
+            .tests()
+            .iter()
+            .map(|test| test.name.as_deref().unwrap())
+            .collect();
+        assert_eq!(tests, ["common", "main", "night"]);
+        assert_eq!(self::origins(&origins, "tests"), [(0, 0), (2, 0), (4, 0)]);
+    }
+
+    #[test]
+    fn provenance() {
+        let report = crate::validate::check_files(
+            &read_fixture("vivid.toml"),
+            &Default::default(),
+            crate::validate::Tests::Checked,
+        );
+        assert!(!report.has_errors());
+        assert_eq!(
+            report
+                .provenance
+                .iter()
+                .map(|provenance| provenance.to_string().replace('\\', "/"))
+                .collect::<Vec<_>>(),
+            [
+                // Keys are those of the entries in their own file
+                "`rules[0]` (Night) from vivid.d/20-night.toml:5:8",
+                "`rules[0]` (Main) from vivid.toml:5:8",
+                "`rules[0]` (Common) from common.toml:6:8",
+                "`program_settings[0]` (work.exe) from vivid.d/10-work.json",
+                "`program_settings[0]` (editor.exe) from vivid.toml:10:12",
+                "`program_settings[0]` (csgo.exe) from games/shooters.toml:6:12",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_desktop_vibrance() {
+        assert_eq!(
+            Config::merge(vec![ConfigFragment::default(), ConfigFragment::default()]).unwrap_err(),
+            "missing field `desktop_vibrance`, no file sets it"
+        );
+    }
 }
@@ -0,0 +1,729 @@
+//! In-place edits of `[[program_settings]]` and `[[rules]]` entries. Serializing the configuration
+//! again would lose comments and formatting, so edits are applied to the text instead: only the
+//! lines of the edited entry change.
+//! Like validation, nothing in here touches the OS.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+use toml::Spanned;
+
+/// Sections whose entries can be edited
+pub const SECTIONS: &[&str] = &["program_settings", "rules"];
+
+/// Keys that can be set on entries of a section
+pub fn settable_keys(section: &str) -> &'static [&'static str] {
+    match section {
+        "program_settings" => &[
+            "exe_name",
+            "vibrance",
+            "fullscreen_only",
+            "scope",
+            "sticky",
+            "priority",
+            "resolution",
+            "window_states",
+            "schedule",
+            "power",
+            "processes",
+        ],
+        "rules" => &[
+            "name",
+            "priority",
+            "vibrance",
+            "sticky",
+            "when",
+            "resolution",
+        ],
+        _ => &[],
+    }
+}
+
+/// Spans of the values of editable entries
+#[derive(Debug, Default, serde::Deserialize)]
+struct SpanMap {
+    #[serde(default)]
+    program_settings: Vec<BTreeMap<String, Spanned<toml::Value>>>,
+    #[serde(default)]
+    rules: Vec<BTreeMap<String, Spanned<toml::Value>>>,
+}
+
+impl SpanMap {
+    fn entries(&self, section: &str) -> &[BTreeMap<String, Spanned<toml::Value>>] {
+        match section {
+            "program_settings" => &self.program_settings,
+            "rules" => &self.rules,
+            _ => &[],
+        }
+    }
+}
+
+/// Lines from a table header up to the next one
+#[derive(Debug)]
+struct Block {
+    /// Table name, `None` for top-level keys
+    name: Option<String>,
+    /// Whether the header is an array of tables one, i.e. `[[rules]]`
+    array: bool,
+    /// Start of the comments right above the header, or of the header itself
+    start: usize,
+    /// End of the header line
+    header_end: usize,
+    /// End of the last line holding a key or a comment, before trailing blank lines
+    content_end: usize,
+    end: usize,
+}
+
+/// Lexer state carried across lines
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Lexer {
+    Code,
+    /// Inside a multi-line string closed by this delimiter
+    MultiLine(&'static str),
+}
+
+/// Formats a value the way it's written in a `key = value` line: tables are written inline
+pub fn format_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::Table(table) if table.is_empty() => "{}".into(),
+        toml::Value::Table(table) => format!(
+            "{{ {} }}",
+            table
+                .iter()
+                .map(|(key, value)| format!("{} = {}", format_key(key), format_value(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        toml::Value::Array(values) => format!(
+            "[{}]",
+            values
+                .iter()
+                .map(format_value)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        value => value.to_string(),
+    }
+}
+
+fn format_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.into()
+    } else {
+        toml::Value::String(key.into()).to_string()
+    }
+}
+
+/// Parses a value as written on the command line: TOML (`80`, `true`, `{ exe = "game.exe" }`),
+/// falling back to a plain string so `running` or `game.exe` don't need quotes
+pub fn parse_value(text: &str) -> toml::Value {
+    toml::from_str::<BTreeMap<String, toml::Value>>(&format!("value = {}", text))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(text.into()))
+}
+
+/// A configuration file being edited
+#[derive(Debug, Clone)]
+pub struct Document {
+    source: String,
+}
+
+impl Document {
+    pub fn new(source: String) -> Self {
+        Self { source }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    fn newline(&self) -> &'static str {
+        if self.source.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+
+    /// Splits the file into blocks, one per table header
+    fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![Block {
+            name: None,
+            array: false,
+            start: 0,
+            header_end: 0,
+            content_end: 0,
+            end: self.source.len(),
+        }];
+        let mut lexer = Lexer::Code;
+        // Start of the comment lines right above the current line
+        let mut comments_start: Option<usize> = None;
+        let mut offset = 0;
+
+        for line in self.source.split_inclusive('\n') {
+            let line_start = offset;
+            offset += line.len();
+            let starts_in_code = lexer == Lexer::Code;
+            lexer = lex_line(line, lexer);
+            let trimmed = line.trim();
+
+            if !starts_in_code {
+                blocks.last_mut().unwrap().content_end = offset;
+                comments_start = None;
+                continue;
+            }
+            if trimmed.starts_with('#') {
+                comments_start.get_or_insert(line_start);
+                continue;
+            }
+            if trimmed.is_empty() {
+                comments_start = None;
+                continue;
+            }
+
+            match parse_header(trimmed) {
+                Some((name, array)) => {
+                    let start = comments_start.take().unwrap_or(line_start);
+                    let previous = blocks.last_mut().unwrap();
+                    previous.end = start;
+                    previous.content_end = previous.content_end.min(start);
+                    blocks.push(Block {
+                        name: Some(name),
+                        array,
+                        start,
+                        header_end: offset,
+                        content_end: offset,
+                        end: self.source.len(),
+                    });
+                }
+                None => {
+                    comments_start = None;
+                    blocks.last_mut().unwrap().content_end = offset;
+                }
+            }
+        }
+
+        blocks
+    }
+
+    fn spans(&self) -> Result<SpanMap, String> {
+        toml::from_str(&self.source).map_err(|e| e.to_string())
+    }
+
+    /// Blocks of the entries of a section: the `[[section]]` header block and its sub-tables
+    fn entries(&self, section: &str) -> Result<Vec<Vec<Block>>, String> {
+        let prefix = format!("{}.", section);
+        let mut entries: Vec<Vec<Block>> = vec![];
+        // Whether the previous block belongs to the last entry
+        let mut in_entry = false;
+        for block in self.blocks() {
+            match &block.name {
+                Some(name) if name == section && block.array => {
+                    entries.push(vec![block]);
+                    in_entry = true;
+                }
+                Some(name) if name.starts_with(&prefix) && !entries.is_empty() => {
+                    if !in_entry {
+                        return Err(format!(
+                            "[{}] is separated from its [[{}]] entry, it has to be edited by hand",
+                            name, section
+                        ));
+                    }
+                    entries.last_mut().unwrap().push(block);
+                }
+                _ => in_entry = false,
+            }
+        }
+
+        // Entries written as an inline array can't be edited line by line
+        if entries.len() != self.spans()?.entries(section).len() {
+            return Err(format!(
+                "`{}` isn't only written as [[{}]] tables, it has to be edited by hand",
+                section, section
+            ));
+        }
+
+        Ok(entries)
+    }
+
+    fn entry(&self, section: &str, index: usize) -> Result<Vec<Block>, String> {
+        let mut entries = self.entries(section)?;
+        if index >= entries.len() {
+            return Err(format!(
+                "there's no `{}[{}]` in this file, it has {} entries",
+                section,
+                index,
+                entries.len()
+            ));
+        }
+        Ok(entries.swap_remove(index))
+    }
+
+    /// Line (starting at 1) of the header of an entry
+    pub fn entry_line(&self, section: &str, index: usize) -> Option<usize> {
+        let entry = self.entry(section, index).ok()?;
+        let header_start = self.source[..entry[0].header_end]
+            .trim_end_matches(|c| c == '\n' || c == '\r')
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+        Some(self.source[..header_start].matches('\n').count() + 1)
+    }
+
+    /// Range of the entry, from its leading comments to its last sub-table and the blank lines after it.
+    /// Comments after it are kept, unless they're right above the next header, and so part of the next block
+    fn entry_range(&self, section: &str, index: usize) -> Result<Range<usize>, String> {
+        let entry = self.entry(section, index)?;
+        let mut start = entry[0].start;
+        let content_end = entry.last().unwrap().content_end;
+        let rest = &self.source[content_end..];
+        let blank = rest.len() - rest.trim_start().len();
+        if blank == rest.len() {
+            // Last in the file: blank lines before it go too
+            let before = self.source[..start].trim_end();
+            if !before.is_empty() {
+                start = before.len() + self.newline().len();
+            }
+            return Ok(start..self.source.len());
+        }
+
+        let end = content_end + rest[..blank].rfind('\n').map_or(0, |index| index + 1);
+        Ok(start..end)
+    }
+
+    /// Removes an entry, along with its sub-tables and the comments right above it
+    pub fn remove(&mut self, section: &str, index: usize) -> Result<(), String> {
+        let range = self.entry_range(section, index)?;
+        self.source.replace_range(range, "");
+        Ok(())
+    }
+
+    /// Adds an entry after the last one of its section, or at the end of the file
+    pub fn append(&mut self, section: &str, fields: &[(&str, toml::Value)]) -> Result<(), String> {
+        let newline = self.newline();
+        let mut text = format!("{}[[{}]]{}", newline, section, newline);
+        for (key, value) in fields {
+            text += &format!("{} = {}{}", format_key(key), format_value(value), newline);
+        }
+
+        let entries = self.entries(section)?;
+        let at = match entries.last() {
+            Some(entry) => entry.last().unwrap().content_end,
+            None => {
+                if !self.source.is_empty() && !self.source.ends_with('\n') {
+                    self.source += newline;
+                }
+                self.source.len()
+            }
+        };
+        // The new entry comes right after the previous one, blank lines after it are kept
+        self.source.insert_str(at, &text);
+        Ok(())
+    }
+
+    /// Sets a key of an entry, replacing its value in place, or adding it after the entry's other keys
+    pub fn set(
+        &mut self,
+        section: &str,
+        index: usize,
+        key: &str,
+        value: &toml::Value,
+    ) -> Result<(), String> {
+        let entry = self.entry(section, index)?;
+        let spans = self.spans()?;
+        let text = format_value(value);
+
+        match spans.entries(section)[index].get(key) {
+            Some(span) if span.end() > 0 => {
+                self.source.replace_range(span.start()..span.end(), &text);
+            }
+            Some(_) => {
+                return Err(format!(
+                    "`{}` is written as a [{}.{}] table, it has to be edited by hand",
+                    key, section, key
+                ))
+            }
+            None => {
+                let newline = self.newline();
+                let at = entry[0].content_end;
+                let mut line = format!("{} = {}{}", format_key(key), text, newline);
+                if !self.source[..at].ends_with('\n') {
+                    line.insert_str(0, newline);
+                }
+                self.source.insert_str(at, &line);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets a top-level key, replacing its value in place, or adding it before the other top-level keys
+    /// and the comments right above them
+    pub fn set_top_level(&mut self, key: &str, value: &toml::Value) -> Result<(), String> {
+        let spans: BTreeMap<String, Spanned<toml::Value>> =
+            toml::from_str(&self.source).map_err(|e| e.to_string())?;
+        let text = format_value(value);
+        match spans.get(key) {
+            Some(span) if span.end() > 0 => {
+                self.source.replace_range(span.start()..span.end(), &text);
+                return Ok(());
+            }
+            Some(_) => return Err(format!("`{}` is a table", key)),
+            None => {}
+        }
+
+        let newline = self.newline();
+        let top_level_end = self.blocks()[0].end;
+        let mut lexer = Lexer::Code;
+        let mut comments_start: Option<usize> = None;
+        let mut offset = 0;
+        let mut at = top_level_end;
+        for line in self.source[..top_level_end].split_inclusive('\n') {
+            let line_start = offset;
+            offset += line.len();
+            let starts_in_code = lexer == Lexer::Code;
+            lexer = lex_line(line, lexer);
+            let trimmed = line.trim();
+            if starts_in_code && trimmed.starts_with('#') {
+                comments_start.get_or_insert(line_start);
+            } else if starts_in_code && trimmed.is_empty() {
+                comments_start = None;
+            } else {
+                at = comments_start.unwrap_or(line_start);
+                break;
+            }
+        }
+
+        let mut line = format!("{} = {}{}", format_key(key), text, newline);
+        if at < self.source.len() {
+            line += newline;
+        }
+        self.source.insert_str(at, &line);
+        Ok(())
+    }
+}
+
+/// Parses a table header line, i.e. `[[rules]] # comment`, into its name and whether it's an array of tables
+fn parse_header(line: &str) -> Option<(String, bool)> {
+    let (array, rest) = if let Some(rest) = line.strip_prefix("[[") {
+        (true, rest)
+    } else {
+        (false, line.strip_prefix('[')?)
+    };
+    let close = if array { "]]" } else { "]" };
+    let name = &rest[..rest.find(close)?];
+    let name = name
+        .split('.')
+        .map(|part| part.trim().trim_matches('"').trim_matches('\''))
+        .collect::<Vec<_>>()
+        .join(".");
+    Some((name, array))
+}
+
+/// Follows strings through a line, to tell whether the next one starts inside a multi-line string
+fn lex_line(line: &str, mut lexer: Lexer) -> Lexer {
+    let mut rest = line;
+    loop {
+        match lexer {
+            Lexer::MultiLine(delimiter) => match rest.find(delimiter) {
+                Some(end) => {
+                    rest = &rest[end + delimiter.len()..];
+                    lexer = Lexer::Code;
+                }
+                None => return lexer,
+            },
+            Lexer::Code => {
+                let next = rest.find(|c| c == '"' || c == '\'' || c == '#');
+                let index = match next {
+                    Some(index) => index,
+                    None => return lexer,
+                };
+                rest = &rest[index..];
+                if rest.starts_with('#') {
+                    return lexer;
+                }
+                if let Some(delimiter) = ["\"\"\"", "'''"]
+                    .iter()
+                    .find(|delimiter| rest.starts_with(**delimiter))
+                {
+                    rest = &rest[3..];
+                    lexer = Lexer::MultiLine(*delimiter);
+                    continue;
+                }
+
+                // Single-line string, only basic ones have escapes
+                let quote = rest.chars().next().unwrap();
+                let mut escaped = false;
+                let mut end = None;
+                for (index, c) in rest.char_indices().skip(1) {
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' && quote == '"' {
+                        escaped = true;
+                    } else if c == quote {
+                        end = Some(index);
+                        break;
+                    }
+                }
+                match end {
+                    Some(end) => rest = &rest[end + 1..],
+                    None => return lexer,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Document {
+        Document::new(include_str!("../tests/fixtures/config_edit/vivid.toml").into())
+    }
+
+    /// Lines of the fixture, without the ones in `removed`
+    fn fixture_without(removed: Range<usize>) -> String {
+        include_str!("../tests/fixtures/config_edit/vivid.toml")
+            .split_inclusive('\n')
+            .enumerate()
+            .filter(|(index, _)| !removed.contains(&(index + 1)))
+            .map(|(_, line)| line)
+            .collect()
+    }
+
+    #[test]
+    fn entry_lines() {
+        let document = fixture();
+        assert_eq!(document.entry_line("program_settings", 0), Some(5));
+        assert_eq!(document.entry_line("program_settings", 1), Some(14));
+        assert_eq!(document.entry_line("rules", 0), Some(18));
+        assert_eq!(document.entry_line("rules", 1), Some(26));
+        assert_eq!(document.entry_line("rules", 2), None);
+    }
+
+    #[test]
+    fn remove_with_sub_tables() {
+        let mut document = fixture();
+        document.remove("program_settings", 0).unwrap();
+        assert_eq!(document.source(), fixture_without(4..13));
+    }
+
+    #[test]
+    fn remove_with_multi_line_string() {
+        let mut document = fixture();
+        document.remove("rules", 0).unwrap();
+        assert_eq!(document.source(), fixture_without(18..25));
+    }
+
+    #[test]
+    fn remove_last() {
+        let mut document = fixture();
+        document.remove("rules", 1).unwrap();
+        // Comments that aren't right above or in the entry are kept
+        assert_eq!(document.source(), fixture_without(25..31));
+
+        let mut document = Document::new(fixture_without(30..32));
+        document.remove("rules", 1).unwrap();
+        assert_eq!(document.source(), fixture_without(24..32));
+    }
+
+    #[test]
+    fn append() {
+        let mut document = fixture();
+        document
+            .append(
+                "program_settings",
+                &[("exe_name", "browser.exe".into()), ("vibrance", 55.into())],
+            )
+            .unwrap();
+        let expected = fixture_without(0..0).replacen(
+            "vibrance = 40\n",
+            "vibrance = 40\n\n[[program_settings]]\nexe_name = \"browser.exe\"\nvibrance = 55\n",
+            1,
+        );
+        assert_eq!(document.source(), expected);
+    }
+
+    #[test]
+    fn append_after_last() {
+        let mut document = fixture();
+        document
+            .append(
+                "rules",
+                &[
+                    ("name", "video".into()),
+                    ("vibrance", 65.into()),
+                    ("when", parse_value("{ exe = \"vlc.exe\" }")),
+                ],
+            )
+            .unwrap();
+        let expected = fixture_without(0..0).replacen(
+            "when = { running = \"obs64.exe\" }\n",
+            "when = { running = \"obs64.exe\" }\n\n[[rules]]\nname = \"video\"\nvibrance = 65\nwhen = { exe = \"vlc.exe\" }\n",
+            1,
+        );
+        assert_eq!(document.source(), expected);
+    }
+
+    #[test]
+    fn append_new_section() {
+        let mut document = Document::new("desktop_vibrance = 50 # Default".into());
+        document
+            .append("rules", &[("vibrance", 60.into())])
+            .unwrap();
+        assert_eq!(
+            document.source(),
+            "desktop_vibrance = 50 # Default\n\n[[rules]]\nvibrance = 60\n"
+        );
+    }
+
+    #[test]
+    fn set_in_place() {
+        let mut document = fixture();
+        document
+            .set("program_settings", 0, "exe_name", &"game-gog.exe".into())
+            .unwrap();
+        document
+            .set(
+                "rules",
+                1,
+                "when",
+                &parse_value("{ running = [\"obs64.exe\", \"xsplit.exe\"] }"),
+            )
+            .unwrap();
+        let expected = fixture_without(0..0)
+            .replacen(
+                "\"game.exe\" # Steam version",
+                "\"game-gog.exe\" # Steam version",
+                1,
+            )
+            .replacen(
+                "{ running = \"obs64.exe\" }",
+                "{ running = [\"obs64.exe\", \"xsplit.exe\"] }",
+                1,
+            );
+        assert_eq!(document.source(), expected);
+    }
+
+    #[test]
+    fn set_new_key() {
+        let mut document = fixture();
+        // Goes before the entry's sub-tables
+        document
+            .set("program_settings", 0, "sticky", &true.into())
+            .unwrap();
+        document.set("rules", 1, "priority", &10.into()).unwrap();
+        let expected = fixture_without(0..0)
+            .replacen(
+                "fullscreen_only = true\n",
+                "fullscreen_only = true\nsticky = true\n",
+                1,
+            )
+            .replacen(
+                "when = { running = \"obs64.exe\" }\n",
+                "when = { running = \"obs64.exe\" }\npriority = 10\n",
+                1,
+            );
+        assert_eq!(document.source(), expected);
+    }
+
+    #[test]
+    fn set_top_level() {
+        let mut document = fixture();
+        document
+            .set_top_level("desktop_vibrance", &45.into())
+            .unwrap();
+        document
+            .set_top_level(
+                "location",
+                &parse_value("{ latitude = 48.85, longitude = 2.35 }"),
+            )
+            .unwrap();
+        let expected = fixture_without(0..0).replacen(
+            "# Vivid configuration\ndesktop_vibrance = 50 # Most of the time\n",
+            "location = { latitude = 48.85, longitude = 2.35 }\n\n# Vivid configuration\ndesktop_vibrance = 45 # Most of the time\n",
+            1,
+        );
+        assert_eq!(document.source(), expected);
+    }
+
+    #[test]
+    fn crlf() {
+        let source = fixture_without(0..0).replace('\n', "\r\n");
+        let mut document = Document::new(source.clone());
+        document.set("rules", 1, "priority", &10.into()).unwrap();
+        assert_eq!(
+            document.source(),
+            source.replacen(
+                "when = { running = \"obs64.exe\" }\r\n",
+                "when = { running = \"obs64.exe\" }\r\npriority = 10\r\n",
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn sub_tables() {
+        let mut document = fixture();
+        assert_eq!(
+            document
+                .set("program_settings", 0, "window_states", &parse_value("{ windowed = 50 }"))
+                .unwrap_err(),
+            "`window_states` is written as a [program_settings.window_states] table, it has to be edited by hand"
+        );
+        assert_eq!(document.source(), fixture_without(0..0));
+
+        let mut document = Document::new(
+            "[[program_settings]]\nexe_name = \"game.exe\"\nvibrance = 80\n\n[desktop]\n\n[program_settings.window_states]\nborderless = 65\n"
+                .into(),
+        );
+        assert_eq!(
+            document.remove("program_settings", 0).unwrap_err(),
+            "[program_settings.window_states] is separated from its [[program_settings]] entry, it has to be edited by hand"
+        );
+    }
+
+    #[test]
+    fn inline_arrays() {
+        let mut document =
+            Document::new("rules = [{ vibrance = 60, when = { exe = \"game.exe\" } }]\n".into());
+        assert_eq!(
+            document
+                .set("rules", 0, "vibrance", &70.into())
+                .unwrap_err(),
+            "`rules` isn't only written as [[rules]] tables, it has to be edited by hand"
+        );
+        assert_eq!(
+            document.remove("rules", 1).unwrap_err(),
+            "`rules` isn't only written as [[rules]] tables, it has to be edited by hand"
+        );
+    }
+
+    #[test]
+    fn missing_entry() {
+        let mut document = fixture();
+        assert_eq!(
+            document.remove("rules", 2).unwrap_err(),
+            "there's no `rules[2]` in this file, it has 2 entries"
+        );
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(parse_value("80"), toml::Value::Integer(80));
+        assert_eq!(
+            parse_value("game.exe"),
+            toml::Value::String("game.exe".into())
+        );
+        assert_eq!(
+            format_value(&parse_value(
+                "{ window_state = [\"fullscreen\"], \"a key\" = {} }"
+            )),
+            "{ \"a key\" = {}, window_state = [\"fullscreen\"] }"
+        );
+    }
+}
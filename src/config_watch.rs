@@ -0,0 +1,193 @@
+use crate::config::Config;
+use crate::error::{VividError, VividResult};
+use crate::foreground_watch::{ForegroundWatcher, ForegroundWatcherEventKind};
+use crate::format::Format;
+use winapi::shared::{
+    basetsd::UINT_PTR,
+    minwindef::{DWORD, UINT},
+    ntdef::NULL,
+    windef::HWND,
+};
+use winapi::um::{handleapi, synchapi, winbase, winnt, winuser};
+
+/// Interval between two checks of the configuration files modification times
+pub const POLL_INTERVAL_MS: UINT = 2000;
+
+/// Event set to ask a running instance to reload its configuration, i.e. after `vivid rule` edits
+const RELOAD_EVENT_NAME: &str = "Local\\VividReloadConfiguration";
+
+/// Configuration file, its format passed with `--format`, and the last known modification time of every file
+/// it's merged from
+type Watched = (
+    std::path::PathBuf,
+    Option<Format>,
+    Vec<(std::path::PathBuf, Option<std::time::SystemTime>)>,
+);
+
+lazy_static::lazy_static! {
+    static ref WATCHED: parking_lot::Mutex<Option<Watched>> = parking_lot::Mutex::new(None);
+    /// Reload event handle, as an address (HANDLEs aren't `Send`)
+    static ref RELOAD_EVENT: parking_lot::Mutex<Option<usize>> = parking_lot::Mutex::new(None);
+}
+
+/// Asks a running instance to reload its configuration right away, returns whether one is running
+pub fn notify_reload() -> bool {
+    let name = std::ffi::CString::new(RELOAD_EVENT_NAME).unwrap();
+    unsafe {
+        let event = synchapi::OpenEventA(winnt::EVENT_MODIFY_STATE, 0, name.as_ptr());
+        if event.is_null() {
+            return false;
+        }
+        let notified = synchapi::SetEvent(event) != 0;
+        handleapi::CloseHandle(event);
+        notified
+    }
+}
+
+/// Reloads the configuration when its file, a file it includes or its drop-in directory changes.
+/// Invalid configurations are refused and the current one is kept
+#[derive(Debug, Clone)]
+pub struct ConfigWatcher {
+    path: std::path::PathBuf,
+    format: Option<Format>,
+    timer: Option<UINT_PTR>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: std::path::PathBuf, format: Option<Format>) -> Self {
+        Self {
+            path,
+            format,
+            timer: None,
+        }
+    }
+
+    pub fn is_registered(&self) -> bool {
+        self.timer.is_some()
+    }
+
+    pub fn register(&mut self) -> VividResult<()> {
+        *WATCHED.lock() = Some((
+            self.path.clone(),
+            self.format,
+            Self::stamps(&self.path, self.format),
+        ));
+
+        let name = std::ffi::CString::new(RELOAD_EVENT_NAME).unwrap();
+        let event = unsafe { synchapi::CreateEventA(NULL as _, 0, 0, name.as_ptr()) };
+        if event.is_null() {
+            log::warn!(
+                "ConfigWatcher::register() -> no reload event: {}",
+                std::io::Error::last_os_error()
+            );
+        } else {
+            *RELOAD_EVENT.lock() = Some(event as usize);
+        }
+
+        let timer =
+            unsafe { winuser::SetTimer(NULL as _, 0, POLL_INTERVAL_MS, Some(Self::timer_proc)) };
+
+        if timer == 0 {
+            log::error!("ConfigWatcher::register() -> failed");
+            return Err(VividError::windows_error());
+        }
+
+        self.timer = Some(timer);
+        log::trace!("ConfigWatcher::register() -> successful");
+        Ok(())
+    }
+
+    pub fn unregister(&mut self) -> VividResult<()> {
+        if let Some(timer) = self.timer.take() {
+            if unsafe { winuser::KillTimer(NULL as _, timer) } == 0 {
+                log::error!("ConfigWatcher::unregister() -> failed");
+                return Err(VividError::windows_error());
+            }
+            *WATCHED.lock() = None;
+            if let Some(event) = RELOAD_EVENT.lock().take() {
+                unsafe { handleapi::CloseHandle(event as _) };
+            }
+            log::trace!("ConfigWatcher::unregister() -> successful");
+        }
+
+        Ok(())
+    }
+
+    fn modified(path: &std::path::Path) -> Option<std::time::SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Modification times of the files a configuration is merged from. The drop-in directory is
+    /// included, as adding or removing a file in it changes its modification time
+    fn stamps(
+        path: &std::path::Path,
+        format: Option<Format>,
+    ) -> Vec<(std::path::PathBuf, Option<std::time::SystemTime>)> {
+        let mut paths = Config::read_files(path, format)
+            .map(|files| files.into_iter().map(|file| file.path).collect())
+            .unwrap_or_else(|_| vec![path.to_path_buf()]);
+        paths.push(path.with_file_name(crate::config::DROP_IN_DIRECTORY));
+        paths
+            .into_iter()
+            .map(|path| {
+                let modified = Self::modified(&path);
+                (path, modified)
+            })
+            .collect()
+    }
+
+    /// Loads the files again, and applies them if they're valid
+    pub fn reload(path: &std::path::Path, format: Option<Format>) -> VividResult<()> {
+        let config = Config::load_path(path, format)?;
+        unsafe {
+            crate::CONFIG = Ok(config);
+        }
+
+        crate::schedule_watch::ScheduleWatcher::arm()?;
+        ForegroundWatcher::dispatch_current(ForegroundWatcherEventKind::ConfigReloaded);
+        Ok(())
+    }
+
+    unsafe extern "system" fn timer_proc(_: HWND, _: UINT, _: UINT_PTR, _: DWORD) {
+        let (path, format) = {
+            let mut watched = WATCHED.lock();
+            let (path, format, stamps) = match watched.as_mut() {
+                Some(watched) => watched,
+                None => return,
+            };
+            let notified = RELOAD_EVENT.lock().map_or(false, |event| {
+                synchapi::WaitForSingleObject(event as _, 0) == winbase::WAIT_OBJECT_0
+            });
+            let unchanged = stamps
+                .iter()
+                .all(|(file, modified)| Self::modified(file) == *modified);
+            if unchanged && !notified {
+                return;
+            }
+            // Includes may have changed too
+            *stamps = Self::stamps(path, *format);
+            (path.clone(), *format)
+        };
+
+        log::trace!(
+            "ConfigWatcher::timer_proc: {} changed, reloading",
+            path.display()
+        );
+        match Self::reload(&path, format) {
+            Ok(()) => log::info!("Configuration reloaded from {}", path.display()),
+            Err(e) => log::error!(
+                "Cannot reload {}, keeping the current configuration: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        let _ = self.unregister();
+    }
+}
@@ -0,0 +1,144 @@
+//! Games installed with the Epic Games Launcher. It writes a manifest per installed game, a JSON file with an
+//! `.item` extension, in `%ProgramData%\Epic\EpicGamesLauncher\Data\Manifests`:
+//!
+//! ```json
+//! { "DisplayName": "Fortnite", "AppName": "Fortnite", "InstallLocation": "C:\\Program Files\\Epic Games\\Fortnite",
+//!   "LaunchExecutable": "FortniteGame/Binaries/Win64/FortniteLauncher.exe", "AppCategories": ["public", "games"] }
+//! ```
+
+use super::{Game, Launcher};
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Manifest {
+    display_name: String,
+    app_name: String,
+    install_location: String,
+    /// Relative to the install location
+    #[serde(default)]
+    launch_executable: String,
+    #[serde(default)]
+    app_categories: Vec<String>,
+    #[serde(rename = "bIsIncompleteInstall", default)]
+    incomplete: bool,
+}
+
+/// Directory the manifests are written to
+fn manifests_directory() -> Option<std::path::PathBuf> {
+    std::env::var_os("ProgramData").map(|data| {
+        std::path::PathBuf::from(data)
+            .join("Epic")
+            .join("EpicGamesLauncher")
+            .join("Data")
+            .join("Manifests")
+    })
+}
+
+/// Game described by a manifest, none for incomplete installs and applications that aren't games
+pub fn manifest_game(source: &str) -> Result<Option<Game>, serde_json::Error> {
+    let manifest: Manifest = serde_json::from_str(source)?;
+    // Unreal Engine and other tools aren't in the `games` category
+    let is_game = manifest.app_categories.is_empty()
+        || manifest
+            .app_categories
+            .iter()
+            .any(|category| category == "games");
+    if manifest.incomplete || !is_game {
+        return Ok(None);
+    }
+
+    let install_dir = std::path::PathBuf::from(manifest.install_location);
+    let executable = Some(manifest.launch_executable)
+        .filter(|executable| !executable.is_empty())
+        .map(|executable| install_dir.join(executable));
+    Ok(Some(Game::new(
+        manifest.app_name,
+        manifest.display_name,
+        install_dir,
+        executable,
+    )))
+}
+
+/// Games installed with the Epic Games Launcher
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Epic;
+
+impl Launcher for Epic {
+    fn name(&self) -> &'static str {
+        "Epic Games Launcher"
+    }
+
+    fn installed(&self) -> Result<Vec<Game>, String> {
+        let directory = manifests_directory()
+            .filter(|directory| directory.is_dir())
+            .ok_or_else(|| {
+                "the Epic Games Launcher isn't installed, games installed with Heroic are listed by `vivid discover heroic`"
+                    .to_string()
+            })?;
+        let mut manifests: Vec<std::path::PathBuf> = std::fs::read_dir(&directory)
+            .map_err(|e| format!("cannot read {}: {}", directory.display(), e))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |extension| extension.eq_ignore_ascii_case("item"))
+            })
+            .collect();
+        manifests.sort();
+
+        let mut games = vec![];
+        for manifest in manifests {
+            match std::fs::read_to_string(&manifest)
+                .map_err(|e| e.to_string())
+                .and_then(|source| manifest_game(&source).map_err(|e| e.to_string()))
+            {
+                Ok(game) => games.extend(game),
+                Err(e) => log::warn!("Cannot read {}: {}", manifest.display(), e),
+            }
+        }
+
+        Ok(games)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_manifest() {
+        let game = manifest_game(include_str!("../../tests/fixtures/epic/Fortnite.item"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(game.id, "Fortnite");
+        assert_eq!(game.name, "Fortnite");
+        assert_eq!(
+            game.install_dir,
+            std::path::PathBuf::from("C:\\Program Files\\Epic Games\\Fortnite")
+        );
+        assert_eq!(
+            game.path_prefix(),
+            format!(
+                "C:\\Program Files\\Epic Games\\Fortnite{}",
+                std::path::MAIN_SEPARATOR
+            )
+        );
+    }
+
+    #[test]
+    fn left_out_manifests() {
+        // Not in the `games` category
+        assert!(
+            manifest_game(include_str!("../../tests/fixtures/epic/UnrealEngine.item"))
+                .unwrap()
+                .is_none()
+        );
+        // Still downloading
+        assert!(
+            manifest_game(include_str!("../../tests/fixtures/epic/RocketLeague.item"))
+                .unwrap()
+                .is_none()
+        );
+        assert!(manifest_game("{ \"DisplayName\": \"Fortnite\" }").is_err());
+    }
+}
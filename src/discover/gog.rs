@@ -0,0 +1,165 @@
+//! Games installed with GOG Galaxy or GOG installers. The installers register each game under
+//! `HKEY_LOCAL_MACHINE\SOFTWARE\WOW6432Node\GOG.com\Games\<game id>` with its `path`, and write a
+//! `goggame-<game id>.info` file in the install directory, telling which program is started:
+//!
+//! ```json
+//! { "gameId": "1207658924", "name": "Unreal Tournament 2004",
+//!   "playTasks": [{ "isPrimary": true, "category": "game", "type": "FileTask", "path": "System\\UT2004.exe" }] }
+//! ```
+
+use super::{Game, Launcher};
+
+/// Key the games are registered under
+#[cfg(windows)]
+const GAMES_KEY: &str = "SOFTWARE\\WOW6432Node\\GOG.com\\Games";
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Info {
+    game_id: String,
+    name: String,
+    #[serde(default)]
+    play_tasks: Vec<PlayTask>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlayTask {
+    #[serde(default)]
+    is_primary: bool,
+    #[serde(default)]
+    category: String,
+    /// Relative to the install directory, set for programs only
+    path: Option<String>,
+}
+
+/// Game described by the `goggame-<game id>.info` file of its install directory
+pub fn info_game(source: &str, install_dir: &std::path::Path) -> Result<Game, serde_json::Error> {
+    let info: Info = serde_json::from_str(source)?;
+    // The primary task starts the game, others open manuals, settings or websites
+    let executable = info
+        .play_tasks
+        .iter()
+        .filter(|task| task.path.is_some())
+        .filter(|task| task.category.is_empty() || task.category == "game")
+        .max_by_key(|task| task.is_primary)
+        .and_then(|task| task.path.as_deref())
+        // Written with backslashes, joined with the platform's separator
+        .map(|path| {
+            install_dir.join(
+                path.split(|c| c == '\\' || c == '/')
+                    .collect::<std::path::PathBuf>(),
+            )
+        });
+
+    Ok(Game::new(
+        info.game_id,
+        info.name,
+        install_dir.to_path_buf(),
+        executable,
+    ))
+}
+
+/// Games installed with GOG Galaxy or GOG installers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gog;
+
+impl Launcher for Gog {
+    fn name(&self) -> &'static str {
+        "GOG"
+    }
+
+    #[cfg(windows)]
+    fn installed(&self) -> Result<Vec<Game>, String> {
+        use super::registry;
+
+        let ids = registry::subkeys(registry::HKEY_LOCAL_MACHINE, GAMES_KEY);
+        if ids.is_empty() {
+            return Err("no GOG game is registered".into());
+        }
+
+        let mut games = vec![];
+        for id in ids {
+            let key = format!("{}\\{}", GAMES_KEY, id);
+            let install_dir = match registry::string(registry::HKEY_LOCAL_MACHINE, &key, "path") {
+                Some(path) => std::path::PathBuf::from(path),
+                None => continue,
+            };
+            let info = install_dir.join(format!("goggame-{}.info", id));
+            match std::fs::read_to_string(&info)
+                .map_err(|e| e.to_string())
+                .and_then(|source| info_game(&source, &install_dir).map_err(|e| e.to_string()))
+            {
+                Ok(game) => games.push(game),
+                // Older installers only register the game
+                Err(e) => {
+                    log::info!("Cannot read {}: {}", info.display(), e);
+                    let name = registry::string(registry::HKEY_LOCAL_MACHINE, &key, "gameName")
+                        .unwrap_or_else(|| id.clone());
+                    let executable = registry::string(registry::HKEY_LOCAL_MACHINE, &key, "exe")
+                        .map(std::path::PathBuf::from);
+                    games.push(Game::new(id, name, install_dir, executable));
+                }
+            }
+        }
+
+        Ok(games)
+    }
+
+    #[cfg(not(windows))]
+    fn installed(&self) -> Result<Vec<Game>, String> {
+        Err("GOG games are only registered on Windows, games installed with Heroic are listed by `vivid discover heroic`".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install_dir(name: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("gog")
+            .join(name)
+    }
+
+    #[test]
+    fn primary_play_task() {
+        let install_dir = install_dir("Unreal Tournament 2004");
+        let game = info_game(
+            include_str!("../../tests/fixtures/gog/Unreal Tournament 2004/goggame-1207658924.info"),
+            &install_dir,
+        )
+        .unwrap();
+        assert_eq!(game.id, "1207658924");
+        assert_eq!(game.name, "Unreal Tournament 2004");
+        assert_eq!(game.install_dir, install_dir);
+        // The manual and the server aren't the game, even though the server is larger
+        assert_eq!(
+            game.executable,
+            Some(install_dir.join("System").join("UT2004.exe"))
+        );
+    }
+
+    #[test]
+    fn no_play_task() {
+        let install_dir = install_dir("Gothic II");
+        let game = info_game(
+            include_str!("../../tests/fixtures/gog/Gothic II/goggame-1207664643.info"),
+            &install_dir,
+        )
+        .unwrap();
+        assert_eq!(game.name, "Gothic 2 Gold Edition");
+        // Guessed from the install directory, leaving the uninstaller out
+        assert_eq!(
+            game.executable,
+            Some(install_dir.join("System").join("Gothic2.exe"))
+        );
+    }
+
+    #[test]
+    fn invalid_info() {
+        assert!(info_game("{ \"name\": \"Gothic\" }", &install_dir("Gothic II")).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+//! Games installed with Heroic, from Epic, GOG and Amazon or added by hand. Heroic caches the libraries in its
+//! configuration directory (`~/.config/heroic`, `%APPDATA%\heroic` on Windows):
+//!
+//! * `store_cache/legendary_library.json` (Epic) and `store_cache/nile_library.json` (Amazon), a `library` list
+//! * `store_cache/gog_library.json`, a `games` list, install paths being in `gog_store/installed.json`
+//! * `sideload_apps/library.json`, a `games` list
+//!
+//! ```json
+//! { "library": [{ "app_name": "Fortnite", "title": "Fortnite", "is_installed": true,
+//!   "install": { "install_path": "/home/user/Games/Heroic/Fortnite", "executable": "FortniteGame/Binaries/Win64/FortniteClient.exe" } }] }
+//! ```
+
+use super::{Game, Launcher};
+
+/// Libraries cached by Heroic, relative to its configuration directory
+const LIBRARIES: &[&str] = &[
+    "store_cache/legendary_library.json",
+    "store_cache/gog_library.json",
+    "store_cache/nile_library.json",
+    "sideload_apps/library.json",
+];
+
+/// GOG games installed with Heroic, relative to its configuration directory
+const GOG_INSTALLED: &str = "gog_store/installed.json";
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct Library {
+    #[serde(default, alias = "games")]
+    library: Vec<LibraryGame>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct LibraryGame {
+    app_name: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    is_installed: bool,
+    #[serde(default)]
+    install: Install,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct Install {
+    install_path: Option<String>,
+    /// Absolute, or relative to the install path
+    executable: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct GogInstalled {
+    #[serde(default)]
+    installed: Vec<GogInstall>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GogInstall {
+    app_name: String,
+    #[serde(rename = "install_path")]
+    install_path: String,
+}
+
+/// Configuration directories of Heroic, the native one first and then the Flatpak one
+fn directories() -> Vec<std::path::PathBuf> {
+    let mut directories = vec![];
+    #[cfg(windows)]
+    directories.extend(
+        std::env::var_os("APPDATA").map(|appdata| std::path::PathBuf::from(appdata).join("heroic")),
+    );
+    #[cfg(not(windows))]
+    {
+        directories.extend(
+            super::xdg_directory("XDG_CONFIG_HOME", ".config").map(|config| config.join("heroic")),
+        );
+        if let Some(home) = super::home_directory() {
+            directories.push(
+                home.join(".var")
+                    .join("app")
+                    .join("com.heroicgameslauncher.hgl")
+                    .join("config")
+                    .join("heroic"),
+            );
+        }
+    }
+
+    directories
+}
+
+/// Install paths of GOG games by app name, from `gog_store/installed.json`
+pub fn gog_install_paths(source: &str) -> Result<Vec<(String, String)>, serde_json::Error> {
+    let installed: GogInstalled = serde_json::from_str(source)?;
+    Ok(installed
+        .installed
+        .into_iter()
+        .map(|install| (install.app_name, install.install_path))
+        .collect())
+}
+
+/// Installed games of a cached library. `install_paths` completes the ones whose install path isn't cached
+pub fn library_games(
+    source: &str,
+    install_paths: &[(String, String)],
+) -> Result<Vec<Game>, serde_json::Error> {
+    let library: Library = serde_json::from_str(source)?;
+    Ok(library
+        .library
+        .into_iter()
+        .filter_map(|game| {
+            let install_path = install_paths
+                .iter()
+                .find(|(app_name, _)| *app_name == game.app_name)
+                .map(|(_, install_path)| install_path.clone());
+            if !game.is_installed && install_path.is_none() {
+                return None;
+            }
+
+            let executable = game.install.executable.filter(|path| !path.is_empty());
+            let install_dir = game
+                .install
+                .install_path
+                .filter(|path| !path.is_empty())
+                .or(install_path)
+                .map(std::path::PathBuf::from)
+                // Games added by hand only have their executable
+                .or_else(|| {
+                    executable
+                        .as_deref()
+                        .and_then(|executable| std::path::Path::new(executable).parent())
+                        .map(std::path::Path::to_path_buf)
+                })?;
+            let executable = executable.map(|executable| install_dir.join(executable));
+            let name = if game.title.is_empty() {
+                game.app_name.clone()
+            } else {
+                game.title
+            };
+
+            Some(Game::new(game.app_name, name, install_dir, executable))
+        })
+        .collect())
+}
+
+/// Games installed with Heroic
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Heroic;
+
+impl Launcher for Heroic {
+    fn name(&self) -> &'static str {
+        "Heroic"
+    }
+
+    fn installed(&self) -> Result<Vec<Game>, String> {
+        let directory = directories()
+            .into_iter()
+            .find(|directory| directory.is_dir())
+            .ok_or_else(|| "Heroic isn't installed".to_string())?;
+        let read = |path: &std::path::Path| match std::fs::read_to_string(path) {
+            Ok(source) => Some(source),
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("Cannot read {}: {}", path.display(), e);
+                }
+                None
+            }
+        };
+
+        let gog_installed = directory.join(GOG_INSTALLED);
+        let install_paths = read(&gog_installed)
+            .map(|source| {
+                gog_install_paths(&source).unwrap_or_else(|e| {
+                    log::warn!("Cannot read {}: {}", gog_installed.display(), e);
+                    vec![]
+                })
+            })
+            .unwrap_or_default();
+
+        let mut games = vec![];
+        for library in LIBRARIES {
+            let path = directory.join(library);
+            if let Some(source) = read(&path) {
+                match library_games(&source, &install_paths) {
+                    Ok(library_games) => games.extend(library_games),
+                    Err(e) => log::warn!("Cannot read {}: {}", path.display(), e),
+                }
+            }
+        }
+
+        Ok(games)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Id, name, install directory and executable of games
+    fn summary(games: &[Game]) -> Vec<(&str, &str, std::path::PathBuf)> {
+        games
+            .iter()
+            .map(|game| {
+                (
+                    game.id.as_str(),
+                    game.name.as_str(),
+                    game.install_dir.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn epic_library() {
+        let games = library_games(
+            include_str!("../../tests/fixtures/heroic/store_cache/legendary_library.json"),
+            &[],
+        )
+        .unwrap();
+        // Rocket League isn't installed, and Flour has no title
+        assert_eq!(
+            summary(&games),
+            [
+                (
+                    "Fortnite",
+                    "Fortnite",
+                    "/home/user/Games/Heroic/Fortnite".into()
+                ),
+                ("Flour", "Flour", "D:\\Games\\Epic\\Flour".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn gog_library() {
+        let install_paths = gog_install_paths(include_str!(
+            "../../tests/fixtures/heroic/gog_store/installed.json"
+        ))
+        .unwrap();
+        assert_eq!(
+            install_paths,
+            [(
+                "1207658924".to_string(),
+                "/home/user/Games/Heroic/Unreal Tournament 2004".to_string()
+            )]
+        );
+
+        // GOG games are only installed according to `installed.json`
+        let games = library_games(
+            include_str!("../../tests/fixtures/heroic/store_cache/gog_library.json"),
+            &install_paths,
+        )
+        .unwrap();
+        assert_eq!(
+            summary(&games),
+            [(
+                "1207658924",
+                "Unreal Tournament 2004",
+                "/home/user/Games/Heroic/Unreal Tournament 2004".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn sideloaded_apps() {
+        let games = library_games(
+            include_str!("../../tests/fixtures/heroic/sideload_apps/library.json"),
+            &[],
+        )
+        .unwrap();
+        // Only the executable is known
+        assert_eq!(
+            summary(&games),
+            [(
+                "3k2s9xR8uXa5tKq4Hc1Lp",
+                "Doom (GZDoom)",
+                "/opt/gzdoom".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn invalid_library() {
+        assert!(library_games("{ \"library\": {} }", &[]).is_err());
+        assert!(library_games("{}", &[]).unwrap().is_empty());
+        assert!(gog_install_paths("{ \"installed\": {} }").is_err());
+    }
+}
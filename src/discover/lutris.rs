@@ -0,0 +1,352 @@
+//! Games installed with Lutris. Lutris keeps the settings of each installed game in `games/<slug>-<timestamp>.yml`,
+//! in its configuration directory (`~/.config/lutris`) or, since Lutris 0.5.13, its data directory
+//! (`~/.local/share/lutris`):
+//!
+//! ```yaml
+//! game:
+//!   exe: drive_c/Program Files/Game/game.exe
+//!   prefix: /home/user/Games/game
+//! ```
+//!
+//! The program path is either absolute, as installers write it, or relative to the Wine prefix or working directory.
+//! Lutris' game database (`pga.db`) isn't read: the settings files are enough to find installed games.
+
+use super::{Game, Launcher};
+
+/// Game as named by its settings file
+#[derive(Debug, Clone)]
+pub struct Record {
+    /// Name of the settings file, without its extension
+    pub configpath: String,
+    /// Identifier of the game in Lutris, i.e. `the-witcher-3-wild-hunt`
+    pub slug: String,
+}
+
+impl Record {
+    /// Record of a settings file, named after the game's slug and the Unix time it was installed at
+    pub fn new(configpath: &str) -> Self {
+        let slug = configpath
+            .rfind('-')
+            .filter(|index| {
+                let timestamp = &configpath[index + 1..];
+                timestamp.len() >= 10 && timestamp.chars().all(|c| c.is_ascii_digit())
+            })
+            .map_or(configpath, |index| &configpath[..index]);
+        Self {
+            configpath: configpath.into(),
+            slug: slug.into(),
+        }
+    }
+}
+
+/// `game` section of the settings of a game
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct GameSettings {
+    pub exe: Option<String>,
+    /// Wine prefix
+    pub prefix: Option<String>,
+    pub working_dir: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct Settings {
+    #[serde(default)]
+    game: GameSettings,
+}
+
+/// Data and configuration directories of Lutris, native ones first and then Flatpak ones
+#[cfg(unix)]
+fn directories() -> Vec<(std::path::PathBuf, std::path::PathBuf)> {
+    let mut directories = vec![];
+    if let (Some(data), Some(config)) = (
+        super::xdg_directory("XDG_DATA_HOME", ".local/share"),
+        super::xdg_directory("XDG_CONFIG_HOME", ".config"),
+    ) {
+        directories.push((data.join("lutris"), config.join("lutris")));
+    }
+    if let Some(home) = super::home_directory() {
+        let flatpak = home.join(".var").join("app").join("net.lutris.Lutris");
+        directories.push((
+            flatpak.join("data").join("lutris"),
+            flatpak.join("config").join("lutris"),
+        ));
+    }
+
+    directories
+}
+
+/// Settings files in the `games` directory of each directory, sorted by slug.
+/// A file found in several directories is only listed for the first one
+pub fn records(directories: &[&std::path::Path]) -> Vec<(Record, std::path::PathBuf)> {
+    let mut records: Vec<(Record, std::path::PathBuf)> = vec![];
+    for directory in directories {
+        let entries = match std::fs::read_dir(directory.join("games")) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        let mut paths: Vec<std::path::PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |extension| extension == "yml")
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            let configpath = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(configpath) => configpath,
+                None => continue,
+            };
+            if records
+                .iter()
+                .all(|(record, _)| record.configpath != configpath)
+            {
+                records.push((Record::new(configpath), path.clone()));
+            }
+        }
+    }
+    records.sort_by(|(a, _), (b, _)| a.slug.cmp(&b.slug));
+
+    records
+}
+
+/// `game` section of a settings file
+pub fn game_settings(source: &str) -> Result<GameSettings, serde_yaml::Error> {
+    // Settings files that are empty or only hold other sections are valid
+    if source.trim().is_empty() {
+        return Ok(GameSettings::default());
+    }
+    serde_yaml::from_str::<Option<Settings>>(source)
+        .map(|settings| settings.unwrap_or_default().game)
+}
+
+/// Builds a game from its record and settings
+pub fn game(record: &Record, settings: &GameSettings) -> Option<Game> {
+    let non_empty = |value: &Option<String>| value.clone().filter(|value| !value.is_empty());
+    let install_dir = non_empty(&settings.prefix)
+        .or_else(|| non_empty(&settings.working_dir))
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            non_empty(&settings.exe)
+                .map(std::path::PathBuf::from)
+                .filter(|exe| exe.is_absolute())
+                .and_then(|exe| exe.parent().map(std::path::Path::to_path_buf))
+        })?;
+    let executable = non_empty(&settings.exe).map(|exe| install_dir.join(exe));
+
+    Some(Game::new(
+        record.configpath.as_str(),
+        record.slug.as_str(),
+        install_dir,
+        executable,
+    ))
+}
+
+/// Games installed with Lutris
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lutris;
+
+impl Launcher for Lutris {
+    fn name(&self) -> &'static str {
+        "Lutris"
+    }
+
+    #[cfg(unix)]
+    fn installed(&self) -> Result<Vec<Game>, String> {
+        let (data, config) = directories()
+            .into_iter()
+            .find(|(data, config)| data.join("games").is_dir() || config.join("games").is_dir())
+            .ok_or_else(|| "Lutris isn't installed".to_string())?;
+        Ok(read_games(&[&config, &data]))
+    }
+
+    #[cfg(not(unix))]
+    fn installed(&self) -> Result<Vec<Game>, String> {
+        Err("Lutris only runs on Linux".into())
+    }
+}
+
+/// Games of the settings files of the directories
+fn read_games(directories: &[&std::path::Path]) -> Vec<Game> {
+    let mut games = vec![];
+    for (record, path) in records(directories) {
+        let settings = match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| game_settings(&source).map_err(|e| e.to_string()))
+        {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::warn!("Cannot read {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        match game(&record, &settings) {
+            Some(game) => games.push(game),
+            None => log::warn!("{} has no install directory, leaving it out", record.slug),
+        }
+    }
+
+    games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("lutris")
+    }
+
+    #[test]
+    fn settings_file_names() {
+        let record = Record::new("the-witcher-3-wild-hunt-1600000100");
+        assert_eq!(record.configpath, "the-witcher-3-wild-hunt-1600000100");
+        assert_eq!(record.slug, "the-witcher-3-wild-hunt");
+        // Without a timestamp, numbers are part of the slug
+        assert_eq!(Record::new("half-life-2").slug, "half-life-2");
+        assert_eq!(Record::new("quake").slug, "quake");
+
+        let fixtures = fixtures();
+        let listed: Vec<_> = records(&[&fixtures.join("config"), &fixtures.join("data")])
+            .into_iter()
+            .map(|(record, path)| {
+                (
+                    record.slug,
+                    path.strip_prefix(&fixtures).unwrap().to_path_buf(),
+                )
+            })
+            .collect();
+        let path =
+            |directory: &str, name: &str| std::path::Path::new(directory).join("games").join(name);
+        // Sorted by slug, the configuration directory coming first
+        assert_eq!(
+            listed,
+            [
+                (
+                    "broken-settings".to_string(),
+                    path("config", "broken-settings-1600000400.yml")
+                ),
+                ("celeste".into(), path("config", "celeste-1600000000.yml")),
+                (
+                    "no-directory".into(),
+                    path("config", "no-directory-1600000500.yml")
+                ),
+                ("quake".into(), path("config", "quake-1600000300.yml")),
+                (
+                    "the-witcher-3-wild-hunt".into(),
+                    path("data", "the-witcher-3-wild-hunt-1600000100.yml")
+                ),
+            ]
+        );
+        assert!(records(&[&fixtures.join("missing")]).is_empty());
+    }
+
+    #[test]
+    fn installed_games() {
+        let fixtures = fixtures();
+        let games = read_games(&[&fixtures.join("config"), &fixtures.join("data")]);
+        let summary: Vec<_> = games
+            .iter()
+            .map(|game| {
+                (
+                    game.id.as_str(),
+                    game.name.as_str(),
+                    game.install_dir.clone(),
+                )
+            })
+            .collect();
+        // Games with broken settings or without any install directory are left out
+        assert_eq!(
+            summary,
+            [
+                (
+                    "celeste-1600000000",
+                    "celeste",
+                    "/home/user/Games/celeste".into()
+                ),
+                ("quake-1600000300", "quake", "/opt/quake".into()),
+                (
+                    "the-witcher-3-wild-hunt-1600000100",
+                    "the-witcher-3-wild-hunt",
+                    "/home/user/Games/the-witcher-3".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn settings_files() {
+        let settings = game_settings(include_str!(
+            "../../tests/fixtures/lutris/data/games/the-witcher-3-wild-hunt-1600000100.yml"
+        ))
+        .unwrap();
+        assert_eq!(
+            settings.exe.as_deref(),
+            Some("drive_c/GOG Games/The Witcher 3/bin/x64/witcher3.exe")
+        );
+        assert_eq!(
+            settings.prefix.as_deref(),
+            Some("/home/user/Games/the-witcher-3")
+        );
+        assert_eq!(settings.working_dir, None);
+
+        assert!(game_settings("").unwrap().exe.is_none());
+        assert!(game_settings("system:\n  disable_runtime: true\n")
+            .unwrap()
+            .exe
+            .is_none());
+        assert!(game_settings(include_str!(
+            "../../tests/fixtures/lutris/config/games/broken-settings-1600000400.yml"
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn install_directories() {
+        let install_dir = |source: &str| {
+            game(&Record::new("celeste"), &game_settings(source).unwrap())
+                .map(|game| game.install_dir)
+        };
+        // The Wine prefix comes first
+        assert_eq!(
+            install_dir(
+                "game:\n  exe: Celeste\n  prefix: /home/user/.wine\n  working_dir: /home/user/Games\n"
+            ),
+            Some("/home/user/.wine".into())
+        );
+        assert_eq!(
+            install_dir("game:\n  working_dir: /home/user/Games/celeste\n"),
+            Some("/home/user/Games/celeste".into())
+        );
+        assert_eq!(
+            install_dir("game:\n  exe: /home/user/Games/celeste/Celeste\n  prefix: ''\n"),
+            Some("/home/user/Games/celeste".into())
+        );
+        // Relative programs need a directory
+        assert_eq!(install_dir("game:\n  exe: Celeste\n"), None);
+        assert_eq!(install_dir(""), None);
+    }
+
+    #[test]
+    fn executables() {
+        let install_dir = fixtures().join("games").join("celeste");
+        let settings = |exe: &str| {
+            game_settings(&format!(
+                "game:\n  exe: {}\n  working_dir: {}\n",
+                exe,
+                install_dir.display()
+            ))
+            .unwrap()
+        };
+        let built = game(&Record::new("celeste"), &settings("Celeste.exe")).unwrap();
+        assert_eq!(built.executable, Some(install_dir.join("Celeste.exe")));
+
+        // Guessed from the install directory when it doesn't exist
+        let built = game(&Record::new("celeste"), &settings("Missing.exe")).unwrap();
+        assert_eq!(built.executable, Some(install_dir.join("Celeste.exe")));
+    }
+}
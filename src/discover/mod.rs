@@ -0,0 +1,163 @@
+//! Discovery of games installed through launchers, to generate rules for them
+
+pub mod epic;
+pub mod gog;
+pub mod heroic;
+pub mod lutris;
+#[cfg(windows)]
+mod registry;
+pub mod steam;
+
+/// Directories holding installers and redistributables rather than the game itself
+const IGNORED_DIRECTORIES: &[&str] = &[
+    "_commonredist",
+    "commonredist",
+    "redist",
+    "redistributables",
+    "directx",
+    "vcredist",
+    "__installer",
+    "installers",
+];
+
+/// Programs that ship with games but aren't the game itself
+const IGNORED_PROGRAMS: &[&str] = &[
+    "unins", "setup", "crash", "redist", "vc_", "dxsetup", "install", "report", "helper", "update",
+];
+
+/// How deep in the install directory executables are looked for
+const MAX_DEPTH: usize = 4;
+
+/// Game installed through a launcher
+#[derive(Debug, Clone)]
+pub struct Game {
+    /// Id of the game in its launcher, i.e. the Steam app id
+    pub id: String,
+    pub name: String,
+    pub install_dir: std::path::PathBuf,
+    /// Main executable, when one could be guessed
+    pub executable: Option<std::path::PathBuf>,
+}
+
+impl Game {
+    /// Game started with the executable its launcher tells, or the one guessed from its install directory
+    pub fn new(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        install_dir: std::path::PathBuf,
+        executable: Option<std::path::PathBuf>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            executable: executable
+                .filter(|executable| executable.is_file())
+                .or_else(|| main_executable(&install_dir)),
+            install_dir,
+        }
+    }
+
+    /// Prefix of the paths of every program of the game, for `path` conditions
+    pub fn path_prefix(&self) -> String {
+        let mut prefix = self.install_dir.to_string_lossy().into_owned();
+        // Leaves out directories sharing the beginning of the name, i.e. `Game 2` for `Game`
+        if !prefix.ends_with(std::path::is_separator) {
+            prefix.push(std::path::MAIN_SEPARATOR);
+        }
+        prefix
+    }
+
+    /// Tells if the game is the one passed on the command line, either by id or by name (case insensitive)
+    pub fn matches(&self, selector: &str) -> bool {
+        self.id == selector || self.name.eq_ignore_ascii_case(selector)
+    }
+}
+
+/// Launcher games are installed with
+pub trait Launcher {
+    /// Name of the launcher, for messages
+    fn name(&self) -> &'static str;
+    /// Games installed with the launcher. Errors are only returned when the launcher isn't installed or its
+    /// game list can't be read at all, games that can't be read are logged and left out
+    fn installed(&self) -> Result<Vec<Game>, String>;
+}
+
+/// Home directory of the user
+pub(crate) fn home_directory() -> Option<std::path::PathBuf> {
+    #[cfg(windows)]
+    let home = std::env::var_os("USERPROFILE");
+    #[cfg(not(windows))]
+    let home = std::env::var_os("HOME");
+
+    home.filter(|home| !home.is_empty())
+        .map(std::path::PathBuf::from)
+}
+
+/// `$XDG_CONFIG_HOME` or `$XDG_DATA_HOME`, falling back to their default under the home directory
+#[cfg(not(windows))]
+pub(crate) fn xdg_directory(variable: &str, default: &str) -> Option<std::path::PathBuf> {
+    std::env::var_os(variable)
+        .filter(|directory| !directory.is_empty())
+        .map(std::path::PathBuf::from)
+        .or_else(|| home_directory().map(|home| home.join(default)))
+}
+
+fn is_program(path: &std::path::Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("exe") => true,
+        // Native Linux builds
+        #[cfg(unix)]
+        Some("x86_64") | Some("x86") | None => {
+            use std::os::unix::fs::PermissionsExt as _;
+            path.metadata()
+                .map_or(false, |metadata| metadata.permissions().mode() & 0o111 != 0)
+        }
+        _ => false,
+    }
+}
+
+fn collect_programs(
+    directory: &std::path::Path,
+    depth: usize,
+    programs: &mut Vec<(u64, std::path::PathBuf)>,
+) {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(_) => continue,
+        };
+        if file_type.is_dir() {
+            if depth < MAX_DEPTH && !IGNORED_DIRECTORIES.contains(&name.as_str()) {
+                collect_programs(&path, depth + 1, programs);
+            }
+        } else if file_type.is_file()
+            && is_program(&path)
+            && !IGNORED_PROGRAMS
+                .iter()
+                .any(|ignored| name.contains(ignored))
+        {
+            let size = entry.metadata().map_or(0, |metadata| metadata.len());
+            programs.push((size, path));
+        }
+    }
+}
+
+/// Guesses the main executable of a game: the largest program of its install directory, leaving out
+/// installers, crash reporters and redistributables
+pub fn main_executable(install_dir: &std::path::Path) -> Option<std::path::PathBuf> {
+    let mut programs = vec![];
+    collect_programs(install_dir, 0, &mut programs);
+    programs
+        .into_iter()
+        .max_by_key(|(size, _)| *size)
+        .map(|(_, path)| path)
+}
@@ -0,0 +1,85 @@
+//! Reading launcher settings from the Windows registry
+
+pub use winapi::um::winreg::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+
+use std::os::windows::ffi::{OsStrExt as _, OsStringExt as _};
+use winapi::shared::minwindef::HKEY;
+use winapi::um::{winnt, winreg};
+
+/// Longest key name and string value read, in characters
+const MAX_LENGTH: usize = 1024;
+
+fn wide(text: &str) -> Vec<u16> {
+    std::ffi::OsStr::new(text)
+        .encode_wide()
+        .chain(Some(0))
+        .collect()
+}
+
+/// String value of a key, i.e. `string(HKEY_CURRENT_USER, "Software\\Valve\\Steam", "SteamPath")`
+pub fn string(root: HKEY, key: &str, value: &str) -> Option<String> {
+    let (key, value) = (wide(key), wide(value));
+    let mut buffer = [0u16; MAX_LENGTH];
+    let mut size = std::mem::size_of_val(&buffer) as u32;
+    let status = unsafe {
+        winreg::RegGetValueW(
+            root,
+            key.as_ptr(),
+            value.as_ptr(),
+            winreg::RRF_RT_REG_SZ,
+            std::ptr::null_mut(),
+            buffer.as_mut_ptr() as _,
+            &mut size,
+        )
+    };
+    if status != 0 {
+        return None;
+    }
+
+    // The size includes the terminating null character
+    let length = (size as usize / 2).saturating_sub(1);
+    Some(
+        std::ffi::OsString::from_wide(&buffer[..length])
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+/// Names of the subkeys of a key, none if it doesn't exist
+pub fn subkeys(root: HKEY, key: &str) -> Vec<String> {
+    let key = wide(key);
+    let mut handle: HKEY = std::ptr::null_mut();
+    if unsafe { winreg::RegOpenKeyExW(root, key.as_ptr(), 0, winnt::KEY_READ, &mut handle) } != 0 {
+        return vec![];
+    }
+
+    let mut names = vec![];
+    let mut buffer = [0u16; MAX_LENGTH];
+    loop {
+        let mut length = buffer.len() as u32;
+        let status = unsafe {
+            winreg::RegEnumKeyExW(
+                handle,
+                names.len() as u32,
+                buffer.as_mut_ptr(),
+                &mut length,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        // Fails with `ERROR_NO_MORE_ITEMS` after the last one
+        if status != 0 {
+            break;
+        }
+        names.push(
+            std::ffi::OsString::from_wide(&buffer[..length as usize])
+                .to_string_lossy()
+                .into_owned(),
+        );
+    }
+    unsafe { winreg::RegCloseKey(handle) };
+
+    names
+}
@@ -0,0 +1,318 @@
+//! Games installed with Steam. Steam lists its library folders in `steamapps/libraryfolders.vdf` under its
+//! install directory, and every library folder has an `appmanifest_<app id>.acf` file per installed game in
+//! its `steamapps` directory, the game itself being in `steamapps/common/<installdir>`.
+
+use super::{Game, Launcher};
+use crate::vdf;
+
+/// Tools installed like games: Proton, the Steam Linux Runtime and redistributables
+const TOOLS: &[&str] = &[
+    "Proton",
+    "Steam Linux Runtime",
+    "Steamworks Common Redistributables",
+];
+
+/// Install directory of Steam, as saved in the registry by its installer
+#[cfg(windows)]
+fn registry_path() -> Option<std::path::PathBuf> {
+    let path = super::registry::string(
+        super::registry::HKEY_CURRENT_USER,
+        "Software\\Valve\\Steam",
+        "SteamPath",
+    )?;
+    // Steam writes it with forward slashes, program paths compared to rules have backslashes
+    Some(path.replace('/', "\\").into())
+}
+
+/// Directories Steam may be installed in, the first existing one being used
+pub fn roots() -> Vec<std::path::PathBuf> {
+    let mut roots = vec![];
+    #[cfg(windows)]
+    {
+        roots.extend(registry_path());
+        for variable in &["ProgramFiles(x86)", "ProgramFiles"] {
+            if let Some(directory) = std::env::var_os(variable) {
+                roots.push(std::path::PathBuf::from(directory).join("Steam"));
+            }
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        if let Some(home) = super::home_directory() {
+            roots.push(home.join(".steam").join("steam"));
+            roots.push(home.join(".local").join("share").join("Steam"));
+            // Flatpak
+            roots.push(
+                home.join(".var")
+                    .join("app")
+                    .join("com.valvesoftware.Steam")
+                    .join(".local")
+                    .join("share")
+                    .join("Steam"),
+            );
+        }
+    }
+
+    roots
+}
+
+/// Library folders listed in `libraryfolders.vdf`. Recent versions of Steam write an object per library,
+/// older ones its path only:
+///
+/// ```text
+/// "libraryfolders" { "0" { "path" "C:\\Program Files (x86)\\Steam" } "1" { "path" "D:\\SteamLibrary" } }
+/// "LibraryFolders" { "TimeNextStatsReport" "1600000000" "1" "D:\\SteamLibrary" }
+/// ```
+pub fn library_folders(source: &str) -> Result<Vec<std::path::PathBuf>, vdf::Error> {
+    let root = vdf::parse(source)?;
+    let folders = root
+        .get("libraryfolders")
+        .map(vdf::Value::entries)
+        .unwrap_or_default();
+
+    Ok(folders
+        .iter()
+        // Libraries are numbered, other keys are metadata
+        .filter(|(key, _)| key.chars().all(|c| c.is_ascii_digit()))
+        .filter_map(|(_, library)| library.as_str().or_else(|| library.get_str("path")))
+        .map(std::path::PathBuf::from)
+        .collect())
+}
+
+/// App id, name and install directory name of a game, from its `appmanifest_<app id>.acf` file
+pub fn app_manifest(source: &str) -> Result<(String, String, String), String> {
+    let root = vdf::parse(source).map_err(|e| e.to_string())?;
+    let state = root
+        .get("AppState")
+        .ok_or_else(|| "there's no AppState".to_string())?;
+    let field = |key: &str| {
+        state
+            .get_str(key)
+            .map(ToString::to_string)
+            .ok_or_else(|| format!("there's no {}", key))
+    };
+
+    Ok((field("appid")?, field("name")?, field("installdir")?))
+}
+
+/// Games installed in a library folder
+fn library_games(library: &std::path::Path, games: &mut Vec<Game>) {
+    let steamapps = library.join("steamapps");
+    let entries = match std::fs::read_dir(&steamapps) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("Cannot read the Steam library {}: {}", library.display(), e);
+            return;
+        }
+    };
+
+    let mut manifests: Vec<std::path::PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_lowercase())
+                .map_or(false, |name| {
+                    name.starts_with("appmanifest_") && name.ends_with(".acf")
+                })
+        })
+        .collect();
+    manifests.sort();
+
+    for manifest in manifests {
+        let parsed = std::fs::read_to_string(&manifest)
+            .map_err(|e| e.to_string())
+            .and_then(|source| app_manifest(&source));
+        let (id, name, install_dir) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                log::warn!("Cannot read {}: {}", manifest.display(), e);
+                continue;
+            }
+        };
+        if TOOLS.iter().any(|tool| name.starts_with(tool)) {
+            continue;
+        }
+
+        // Manifests don't tell which program is started
+        let install_dir = steamapps.join("common").join(install_dir);
+        games.push(Game::new(id, name, install_dir, None));
+    }
+}
+
+/// Games installed with Steam, in every library folder
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Steam;
+
+impl Launcher for Steam {
+    fn name(&self) -> &'static str {
+        "Steam"
+    }
+
+    fn installed(&self) -> Result<Vec<Game>, String> {
+        let root = roots()
+            .into_iter()
+            .find(|root| root.join("steamapps").is_dir())
+            .ok_or_else(|| "Steam isn't installed".to_string())?;
+        let listing = root.join("steamapps").join("libraryfolders.vdf");
+        let mut libraries = vec![];
+        // Steam only writes the list once a library is added
+        if listing.exists() {
+            let source = std::fs::read_to_string(&listing)
+                .map_err(|e| format!("cannot read {}: {}", listing.display(), e))?;
+            libraries = library_folders(&source)
+                .map_err(|e| format!("cannot read {}: {}", listing.display(), e))?;
+        }
+        // Older versions of Steam leave its own directory out of the list
+        libraries.push(root);
+
+        // `~/.steam/steam` is usually a link to another root, libraries are only listed once, the listed path first
+        let mut seen = std::collections::HashSet::new();
+        let mut games = vec![];
+        for library in libraries {
+            if seen.insert(library.canonicalize().unwrap_or_else(|_| library.clone())) {
+                library_games(&library, &mut games);
+            }
+        }
+
+        Ok(games)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("steam")
+    }
+
+    #[test]
+    fn library_folders_windows() {
+        assert_eq!(
+            library_folders(include_str!(
+                "../../tests/fixtures/steam/libraryfolders.vdf"
+            ))
+            .unwrap(),
+            [
+                std::path::PathBuf::from("C:\\Program Files (x86)\\Steam"),
+                std::path::PathBuf::from("D:\\SteamLibrary"),
+            ]
+        );
+    }
+
+    #[test]
+    fn library_folders_old_format() {
+        assert_eq!(
+            library_folders(include_str!(
+                "../../tests/fixtures/steam/libraryfolders_old.vdf"
+            ))
+            .unwrap(),
+            [
+                std::path::PathBuf::from("D:\\SteamLibrary"),
+                std::path::PathBuf::from("E:\\Games\\Steam"),
+            ]
+        );
+    }
+
+    #[test]
+    fn library_folders_linux() {
+        assert_eq!(
+            library_folders(include_str!(
+                "../../tests/fixtures/steam/libraryfolders_linux.vdf"
+            ))
+            .unwrap(),
+            [
+                std::path::PathBuf::from("/home/user/.local/share/Steam"),
+                std::path::PathBuf::from("/mnt/games/SteamLibrary"),
+            ]
+        );
+    }
+
+    #[test]
+    fn library_folders_errors() {
+        assert!(library_folders("").unwrap().is_empty());
+        assert_eq!(
+            library_folders(
+                "\"libraryfolders\"\n{\n\t\"0\"\n\t{\n\t\t\"path\"\t\"D:\\\\SteamLibrary\"\n}\n"
+            )
+            .unwrap_err()
+            .to_string(),
+            "line 7: missing `}`"
+        );
+    }
+
+    #[test]
+    fn app_manifests() {
+        assert_eq!(
+            app_manifest(include_str!(
+                "../../tests/fixtures/steam/library/steamapps/appmanifest_730.acf"
+            )),
+            Ok((
+                "730".into(),
+                "Counter-Strike: Global Offensive".into(),
+                "Counter-Strike Global Offensive".into()
+            ))
+        );
+        assert_eq!(
+            app_manifest(include_str!(
+                "../../tests/fixtures/steam/library/steamapps/appmanifest_440.acf"
+            )),
+            Err("line 5: missing `}`".into())
+        );
+        assert_eq!(
+            app_manifest("\"AppState\" { \"appid\" \"730\" \"name\" \"Counter-Strike\" }"),
+            Err("there's no installdir".into())
+        );
+        assert_eq!(
+            app_manifest("\"Other\" {}"),
+            Err("there's no AppState".into())
+        );
+    }
+
+    #[test]
+    fn library() {
+        let library = fixtures().join("library");
+        let mut games = vec![];
+        library_games(&library, &mut games);
+
+        // Proton is a tool and the cut-short manifest is left out
+        let common = library.join("steamapps").join("common");
+        let summary: Vec<_> = games
+            .iter()
+            .map(|game| {
+                (
+                    game.id.as_str(),
+                    game.name.as_str(),
+                    game.install_dir.clone(),
+                    game.executable.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "620",
+                    "Portal 2",
+                    common.join("Portal 2"),
+                    Some(common.join("Portal 2").join("portal2.exe"))
+                ),
+                (
+                    "730",
+                    "Counter-Strike: Global Offensive",
+                    common.join("Counter-Strike Global Offensive"),
+                    // Redistributables and uninstallers aren't the game, even when they're larger
+                    Some(
+                        common
+                            .join("Counter-Strike Global Offensive")
+                            .join("csgo.exe")
+                    )
+                ),
+            ]
+        );
+    }
+}
@@ -31,8 +31,30 @@ Please launch the app with the appropriate flag to choose which driver you use t
     NoGpuDetected,
     #[error("Vivid couldn't detect any Displays on your system. How are you seeing this?")]
     NoDisplayDetected,
+    #[error("Vivid doesn't support this on {0} GPUs yet")]
+    UnsupportedAdapter(&'static str),
     #[error("Configuration isn't loaded just yet!")]
     NoConfigurationLoaded,
+    #[error("Vivid couldn't find a configuration file, it looked for:\n{0}\nRun `vivid init` to create one.")]
+    NoConfigurationFound(String),
+    #[error("{0} already exists, use --force to overwrite it")]
+    ConfigurationExists(std::path::PathBuf),
+    #[error("Vivid couldn't read the configuration file {0}: {1}")]
+    ConfigurationFileError(std::path::PathBuf, std::io::Error),
+    #[error("{0} includes itself")]
+    IncludeCycle(std::path::PathBuf),
+    #[error("Cannot edit the configuration: {0}")]
+    ConfigurationEdit(String),
+    #[error("Cannot convert the configuration: {0}")]
+    ConfigurationConversion(String),
+    #[error("Cannot import the settings: {0}")]
+    Import(String),
+    #[error("Cannot discover games: {0}")]
+    Discovery(String),
+    #[error("Cannot migrate {0}: {1}")]
+    Migration(std::path::PathBuf, String),
+    #[error("The configuration file is invalid:\n{0}")]
+    InvalidConfiguration(String),
     #[error(
         "Vivid couldn't inspect the process with PID #{0}. Probably because it's system owned."
     )]
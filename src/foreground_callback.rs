@@ -1,48 +1,373 @@
+use crate::config::Config;
 use crate::error::VividResult;
+use crate::foreground_watch::{ForegroundWatcherEvent, ForegroundWatcherEventKind};
+use crate::rules::{Decision, Evaluation, RuleContext};
+use crate::schedule::Clock as _;
+use winapi::{shared::windef::HWND, um::winuser};
 
-#[no_mangle]
-pub fn handler(args: &crate::foreground_watch::ForegroundWatcherEvent) -> VividResult<()> {
-    let gpu = unsafe { crate::GPU.as_ref()? };
-    let previous_vibrance = gpu.write().get_vibrance()?;
-    log::trace!("callback args: {:#?}", args);
-    let (vibrance, fullscreen_only) = unsafe { crate::CONFIG.as_ref()? }
-        .vibrance_for_program(&args.process_exe)
-        .map_or_else(
-            || VividResult::Ok((unsafe { crate::CONFIG.as_ref()? }.default_vibrance(), false)),
-            |values| Ok(values),
-        )?;
-
-    let apply = if fullscreen_only {
-        log::trace!("{} requires fullscreen, detecting...", args.process_exe);
-        use winapi::um::shellapi;
-        let mut notification_state: shellapi::QUERY_USER_NOTIFICATION_STATE =
-            shellapi::QUERY_USER_NOTIFICATION_STATE::default();
-        let api_result = unsafe { shellapi::SHQueryUserNotificationState(&mut notification_state) };
-        if api_result == winapi::shared::winerror::S_OK {
-            log::trace!("Found notification state: {}", notification_state);
-            match notification_state {
-                shellapi::QUNS_RUNNING_D3D_FULL_SCREEN
-                | shellapi::QUNS_PRESENTATION_MODE
-                | shellapi::QUNS_ACCEPTS_NOTIFICATIONS => true,
-                _ => false,
-            }
-        } else {
-            false
+lazy_static::lazy_static! {
+    /// Monitors held by sticky programs: monitor device name -> window (as an address, HWNDs aren't `Sync`)
+    static ref STICKY_HOLDS: parking_lot::Mutex<std::collections::HashMap<String, usize>> = Default::default();
+    /// Last window settings were decided for that isn't ignored (as an address), see `subject`
+    static ref LAST_WINDOW: parking_lot::Mutex<Option<usize>> = Default::default();
+}
+
+/// Tells if a sticky window still holds its monitor: it has to be alive, visible and still displayed on it
+fn holds_monitor(hwnd: HWND, monitor: &str) -> bool {
+    let displayed = unsafe {
+        winuser::IsWindow(hwnd) != 0
+            && winuser::IsWindowVisible(hwnd) != 0
+            && winuser::IsIconic(hwnd) == 0
+    };
+    displayed
+        && crate::adapter::Gpu::get_window_monitor_name(hwnd)
+            .map_or(false, |window_monitor| window_monitor == monitor)
+}
+
+/// Window an event decides settings for, when it isn't the foreground window of the event.
+/// Foreground and state changes are about the window itself. Other re-evaluations (process, schedule, power or
+/// configuration changes) keep being about the last window that isn't ignored rather than an ignored foreground
+/// window: with an overlay focused over a game, the game's settings keep applying. Once that window is closed, the
+/// ignored one is kept and current settings with it
+pub fn subject(config: &Config, args: &ForegroundWatcherEvent) -> Option<ForegroundWatcherEvent> {
+    if !config.is_ignored(&args.process_exe, &args.window_class) {
+        *LAST_WINDOW.lock() = Some(args.hwnd as usize);
+        return None;
+    }
+    if matches!(
+        args.kind,
+        ForegroundWatcherEventKind::Foreground | ForegroundWatcherEventKind::StateChanged
+    ) {
+        return None;
+    }
+
+    let hwnd = (*LAST_WINDOW.lock())? as HWND;
+    crate::foreground_watch::ForegroundWatcher::window_event(args.kind, hwnd)
+}
+
+/// Decides which settings apply for an event.
+/// Shared by the event handler and `vivid explain`, so both always agree
+pub fn decide(
+    config: &Config,
+    args: &ForegroundWatcherEvent,
+    now: chrono::DateTime<chrono::FixedOffset>,
+    power: crate::power::PowerStatus,
+    is_running: &dyn Fn(&str) -> bool,
+) -> Decision {
+    let context = RuleContext {
+        process_exe: &args.process_exe,
+        process_path: &args.process_path,
+        window_title: &args.window_title,
+        window_class: &args.window_class,
+        window_state: args.window_state,
+        monitor: &args.monitor,
+        now,
+        power,
+        is_running,
+    };
+    crate::rules::decide(config, &context)
+}
+
+/// Tells if settings stick to the window's monitor, which requires knowing it
+pub fn is_sticky(evaluation: &Evaluation, args: &ForegroundWatcherEvent) -> bool {
+    evaluation.sticky && !args.monitor.is_empty()
+}
+
+/// Monitor settings apply to: the window's own one, focusing a window only affects its monitor. Windows on an
+/// unknown monitor affect the primary one
+pub fn target_monitor(args: &ForegroundWatcherEvent) -> VividResult<String> {
+    if args.monitor.is_empty() {
+        crate::adapter::Gpu::get_primary_monitor_name()
+    } else {
+        Ok(args.monitor.clone())
+    }
+}
+
+/// Monitors held by sticky programs, after an event
+#[derive(Debug, Default)]
+pub struct Holds {
+    /// Monitors their sticky program released
+    pub released: Vec<String>,
+    /// The monitor settings apply to is held by another sticky window, its settings are kept
+    pub held: bool,
+    /// Other monitors no sticky program holds, going back to desktop settings: settings of programs that aren't
+    /// sticky only last while they're focused
+    pub idle: Vec<String>,
+}
+
+/// Releases the monitors sticky programs don't hold anymore, and holds the target monitor for a sticky program.
+/// Shared by the event handler and `vivid watch`, so both always agree
+pub fn update_holds(
+    args: &ForegroundWatcherEvent,
+    sticky: bool,
+    target_monitor: &str,
+) -> VividResult<Holds> {
+    let monitors = crate::adapter::Gpu::get_monitor_names()?;
+    Ok(update_hold_map(
+        &mut STICKY_HOLDS.lock(),
+        args,
+        sticky,
+        target_monitor,
+        &monitors,
+        &|hwnd, monitor| holds_monitor(hwnd as HWND, monitor),
+    ))
+}
+
+/// Bookkeeping of `update_holds`, `holds_monitor` telling if a window (as an address) is still displayed on a monitor
+fn update_hold_map(
+    holds: &mut std::collections::HashMap<String, usize>,
+    args: &ForegroundWatcherEvent,
+    sticky: bool,
+    target_monitor: &str,
+    monitors: &[String],
+    holds_monitor: &dyn Fn(usize, &str) -> bool,
+) -> Holds {
+    let hwnd = args.hwnd as usize;
+    let mut released = vec![];
+    holds.retain(|monitor, holder| {
+        // Focusing another window on the same monitor releases it
+        let focus_moved = *holder != hwnd && *monitor == args.monitor;
+        // So does the window being re-evaluated to settings that aren't sticky anymore
+        let unstuck = *holder == hwnd && !sticky && monitor == target_monitor;
+        let kept = !focus_moved && !unstuck && holds_monitor(*holder, monitor);
+        if !kept {
+            released.push(monitor.clone());
         }
+        kept
+    });
+    released.sort();
+
+    let held = if sticky {
+        holds.insert(target_monitor.into(), hwnd);
+        false
     } else {
-        true
+        // Only other windows are left holding it
+        holds.contains_key(target_monitor)
     };
+    let idle = monitors
+        .iter()
+        .filter(|&monitor| monitor != target_monitor && !holds.contains_key(monitor))
+        .cloned()
+        .collect();
+    Holds {
+        released,
+        held,
+        idle,
+    }
+}
 
+/// Applies a vibrance to a monitor, unless it's already set
+fn apply_vibrance(
+    kind: ForegroundWatcherEventKind,
+    monitor: &str,
+    vibrance: u8,
+) -> VividResult<()> {
+    let gpu = unsafe { crate::GPU.as_ref()? };
+    let previous_vibrance = gpu.write().get_display_vibrance(monitor)?;
     log::trace!(
-        "Vibrance: old = {} / new = {} | to be applied = {}",
+        "[{:?}] Vibrance on {}: old = {} / new = {}",
+        kind,
+        monitor,
         previous_vibrance,
-        vibrance,
-        apply
+        vibrance
     );
-    if apply && vibrance != previous_vibrance {
+    if vibrance != previous_vibrance {
         log::trace!("Applying new vibrance = {}", vibrance);
-        gpu.write().set_vibrance(vibrance)?;
+        gpu.write().set_display_vibrance(monitor, vibrance)?;
+    }
+
+    Ok(())
+}
+
+#[no_mangle]
+pub fn handler(args: &ForegroundWatcherEvent) -> VividResult<()> {
+    log::trace!("callback args: {:#?}", args);
+    let config = unsafe { crate::CONFIG.as_ref()? };
+    let subject = subject(config, args);
+    if let Some(subject) = &subject {
+        log::trace!(
+            "{} is ignored, re-evaluating {} instead",
+            args.process_exe,
+            subject.process_exe
+        );
+    }
+    let args = subject.as_ref().unwrap_or(args);
+    let now = crate::schedule::SystemClock.now();
+    let evaluation = match decide(
+        config,
+        args,
+        now,
+        crate::power::current_status(),
+        &crate::process_watch::is_running,
+    ) {
+        Decision::Ignored => {
+            log::trace!("{} is ignored, keeping current settings", args.process_exe);
+            return Ok(());
+        }
+        Decision::Evaluated(evaluation) => evaluation,
+    };
+
+    match evaluation.winning_rule() {
+        Some(outcome) => log::trace!("rule {:?} wins: {:?}", outcome.rule.name, outcome.result),
+        None => log::trace!("no rule matched, using desktop settings"),
+    }
+    let sticky = is_sticky(&evaluation, args);
+    let target_monitor = target_monitor(args)?;
+    let holds = update_holds(args, sticky, &target_monitor)?;
+
+    for monitor in &holds.released {
+        log::trace!("{} has been released by its sticky program", monitor);
+    }
+    let desktop_vibrance = config.desktop_vibrance_at(&now);
+    for monitor in &holds.idle {
+        apply_vibrance(args.kind, monitor, desktop_vibrance)?;
+    }
+    if holds.held {
+        log::trace!(
+            "{} is held by a sticky program, keeping current settings",
+            target_monitor
+        );
+        return Ok(());
+    }
+
+    apply_vibrance(args.kind, &target_monitor, evaluation.vibrance)
+}
+
+/// Re-evaluates settings against the current foreground window when a program settings depend on starts or exits
+pub fn process_handler(args: &crate::process_watch::ProcessWatcherEvent) -> VividResult<()> {
+    if !crate::rules::is_watched_process(unsafe { crate::CONFIG.as_ref()? }, &args.process_exe) {
+        return Ok(());
     }
 
+    log::trace!("process callback args: {:#?}", args);
+    crate::foreground_watch::ForegroundWatcher::dispatch_current(
+        ForegroundWatcherEventKind::ProcessChanged,
+    );
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(
+        kind: ForegroundWatcherEventKind,
+        hwnd: usize,
+        monitor: &str,
+    ) -> ForegroundWatcherEvent {
+        ForegroundWatcherEvent {
+            kind,
+            hwnd: hwnd as HWND,
+            process_id: hwnd,
+            process_exe: format!("program{}.exe", hwnd),
+            process_path: std::path::PathBuf::new(),
+            window_class: String::new(),
+            window_title: String::new(),
+            window_state: crate::config::WindowState::Fullscreen,
+            monitor: monitor.into(),
+        }
+    }
+
+    fn monitors() -> Vec<String> {
+        vec!["D1".into(), "D2".into(), "D3".into()]
+    }
+
+    /// Focuses a window, windows being displayed as long as they're in `displayed`
+    fn focus(
+        holds: &mut std::collections::HashMap<String, usize>,
+        args: &ForegroundWatcherEvent,
+        sticky: bool,
+        displayed: &[usize],
+    ) -> Holds {
+        let target_monitor = if args.monitor.is_empty() {
+            "D1".to_string()
+        } else {
+            args.monitor.clone()
+        };
+        update_hold_map(
+            holds,
+            args,
+            sticky,
+            &target_monitor,
+            &monitors(),
+            &|hwnd, _| displayed.contains(&hwnd),
+        )
+    }
+
+    #[test]
+    fn sticky_window_holds_its_monitor() {
+        let mut holds = Default::default();
+        let game = event(ForegroundWatcherEventKind::Foreground, 1, "D1");
+        let result = focus(&mut holds, &game, true, &[1]);
+        assert!(!result.held);
+        assert_eq!(result.idle, ["D2", "D3"]);
+
+        // Focusing another monitor keeps it held, other monitors go back to desktop settings
+        let browser = event(ForegroundWatcherEventKind::Foreground, 2, "D2");
+        let result = focus(&mut holds, &browser, false, &[1, 2]);
+        assert!(result.released.is_empty());
+        assert!(!result.held);
+        assert_eq!(result.idle, ["D3"]);
+
+        // Until the game is minimized or closed
+        let result = focus(&mut holds, &browser, false, &[2]);
+        assert_eq!(result.released, ["D1"]);
+        assert_eq!(result.idle, ["D1", "D3"]);
+        assert!(holds.is_empty());
+    }
+
+    #[test]
+    fn other_programs_only_affect_the_focused_monitor() {
+        let mut holds = Default::default();
+        let game = event(ForegroundWatcherEventKind::Foreground, 1, "D1");
+        let result = focus(&mut holds, &game, false, &[1]);
+        assert!(holds.is_empty());
+        assert_eq!(result.idle, ["D2", "D3"]);
+
+        // Alt-tabbing to another monitor resets the game's one
+        let browser = event(ForegroundWatcherEventKind::Foreground, 2, "D2");
+        let result = focus(&mut holds, &browser, false, &[1, 2]);
+        assert_eq!(result.idle, ["D1", "D3"]);
+    }
+
+    #[test]
+    fn focusing_the_same_monitor_releases_it() {
+        let mut holds = Default::default();
+        let game = event(ForegroundWatcherEventKind::Foreground, 1, "D1");
+        focus(&mut holds, &game, true, &[1]);
+
+        let browser = event(ForegroundWatcherEventKind::Foreground, 2, "D1");
+        let result = focus(&mut holds, &browser, false, &[1, 2]);
+        assert_eq!(result.released, ["D1"]);
+        assert!(!result.held);
+        assert_eq!(result.idle, ["D2", "D3"]);
+    }
+
+    #[test]
+    fn re_evaluating_to_settings_that_arent_sticky_releases_the_monitor() {
+        let mut holds = Default::default();
+        let game = event(ForegroundWatcherEventKind::Foreground, 1, "D1");
+        focus(&mut holds, &game, true, &[1]);
+
+        let schedule = event(ForegroundWatcherEventKind::ScheduleChanged, 1, "D1");
+        let result = focus(&mut holds, &schedule, false, &[1]);
+        assert_eq!(result.released, ["D1"]);
+        assert!(!result.held);
+        assert!(holds.is_empty());
+    }
+
+    #[test]
+    fn held_by_another_window() {
+        let mut holds = Default::default();
+        let game = event(ForegroundWatcherEventKind::Foreground, 1, "D1");
+        focus(&mut holds, &game, true, &[1]);
+
+        // Windows on an unknown monitor affect the primary one, without moving the focus away from it
+        let unknown = event(ForegroundWatcherEventKind::Foreground, 2, "");
+        let result = focus(&mut holds, &unknown, false, &[1, 2]);
+        assert!(result.released.is_empty());
+        assert!(result.held);
+        assert_eq!(result.idle, ["D2", "D3"]);
+    }
+}
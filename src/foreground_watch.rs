@@ -1,3 +1,4 @@
+use crate::config::WindowState;
 use crate::error::{VividError, VividResult, WindowsHookError};
 use winapi::shared::windef::HWND;
 use winapi::{
@@ -7,6 +8,8 @@ use winapi::{
 
 lazy_static::lazy_static! {
     static ref CALLBACKS: parking_lot::RwLock<Vec<fn(&ForegroundWatcherEvent) -> VividResult<()>>> = parking_lot::RwLock::new(vec![]);
+    /// Last known foreground window (as an address, HWNDs aren't `Sync`) along with its state
+    static ref FOREGROUND_STATE: parking_lot::Mutex<Option<(usize, WindowState)>> = parking_lot::Mutex::new(None);
     pub(crate) static ref SYSTEM: parking_lot::RwLock<sysinfo::System> = {
         use sysinfo::SystemExt as _;
         parking_lot::RwLock::new(
@@ -17,21 +20,88 @@ lazy_static::lazy_static! {
     };
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ForegroundWatcherEventKind {
+    /// A new window came to foreground
+    Foreground,
+    /// The current foreground window changed its state (windowed, borderless, fullscreen)
+    StateChanged,
+    /// A process with settings attached started or exited
+    ProcessChanged,
+    /// A schedule boundary has been reached
+    ScheduleChanged,
+    /// The power source or battery charge changed
+    PowerChanged,
+    /// The configuration file has been reloaded
+    ConfigReloaded,
+}
+
 #[derive(Debug)]
 pub struct ForegroundWatcherEvent {
+    pub kind: ForegroundWatcherEventKind,
     pub hwnd: HWND,
     pub process_id: usize,
     pub process_exe: String,
     pub process_path: std::path::PathBuf,
+    pub window_class: String,
+    pub window_title: String,
+    pub window_state: WindowState,
+    /// Device name of the monitor the window is displayed on
+    pub monitor: String,
 }
 
 #[derive(Default, Clone)]
 pub struct ForegroundWatcher {
     registered: bool,
-    hook: Option<windef::HWINEVENTHOOK>,
+    hooks: Vec<windef::HWINEVENTHOOK>,
     proc: winuser::WINEVENTPROC,
 }
 
+/// Fetches the class name of the provided window
+pub fn window_class(hwnd: HWND) -> String {
+    let mut buffer = [0u16; 256];
+    let len = unsafe { winuser::GetClassNameW(hwnd, buffer.as_mut_ptr(), buffer.len() as _) };
+    String::from_utf16_lossy(&buffer[..len.max(0) as usize])
+}
+
+/// Fetches the title of the provided window
+pub fn window_title(hwnd: HWND) -> String {
+    let mut buffer = [0u16; 512];
+    let len = unsafe { winuser::GetWindowTextW(hwnd, buffer.as_mut_ptr(), buffer.len() as _) };
+    String::from_utf16_lossy(&buffer[..len.max(0) as usize])
+}
+
+/// Detects the state of the provided window from its rectangle and the one of its monitor: a window covering its whole
+/// monitor is fullscreen, exclusively (D3D fullscreen) when it also stays on top of every other window, borderless otherwise
+pub fn window_state(hwnd: HWND) -> WindowState {
+    let mut window_rect = windef::RECT::default();
+    let monitor = unsafe { winuser::MonitorFromWindow(hwnd, winuser::MONITOR_DEFAULTTONULL) };
+    let mut monitor_info = winuser::MONITORINFO::default();
+    monitor_info.cbSize = std::mem::size_of::<winuser::MONITORINFO>() as u32;
+    if unsafe { winuser::GetWindowRect(hwnd, &mut window_rect) } == 0
+        || monitor.is_null()
+        || unsafe { winuser::GetMonitorInfoW(monitor, &mut monitor_info) } == 0
+    {
+        return WindowState::Windowed;
+    }
+
+    let monitor_rect = monitor_info.rcMonitor;
+    let covers_monitor = window_rect.left <= monitor_rect.left
+        && window_rect.top <= monitor_rect.top
+        && window_rect.right >= monitor_rect.right
+        && window_rect.bottom >= monitor_rect.bottom;
+    if !covers_monitor {
+        return WindowState::Windowed;
+    }
+
+    let extended_style = unsafe { winuser::GetWindowLongW(hwnd, winuser::GWL_EXSTYLE) } as u32;
+    if extended_style & winuser::WS_EX_TOPMOST != 0 {
+        WindowState::Fullscreen
+    } else {
+        WindowState::Borderless
+    }
+}
+
 impl ForegroundWatcher {
     pub fn new() -> Self {
         Self::default()
@@ -47,49 +117,62 @@ impl ForegroundWatcher {
 
     pub fn register(&mut self) -> VividResult<()> {
         self.proc = Some(Self::event_proc);
-        let inner_hook = unsafe {
-            winuser::SetWinEventHook(
-                winuser::EVENT_SYSTEM_FOREGROUND,
-                winuser::EVENT_SYSTEM_FOREGROUND,
-                NULL as _,
-                self.proc,
-                0,
-                0,
-                winuser::WINEVENT_OUTOFCONTEXT | winuser::WINEVENT_SKIPOWNPROCESS,
-            )
-        };
+        // Foreground changes, and location/state changes to detect windowed <-> fullscreen switches
+        for &event in &[
+            winuser::EVENT_SYSTEM_FOREGROUND,
+            winuser::EVENT_OBJECT_LOCATIONCHANGE,
+        ] {
+            let inner_hook = unsafe {
+                winuser::SetWinEventHook(
+                    event,
+                    event,
+                    NULL as _,
+                    self.proc,
+                    0,
+                    0,
+                    winuser::WINEVENT_OUTOFCONTEXT | winuser::WINEVENT_SKIPOWNPROCESS,
+                )
+            };
 
-        if inner_hook != NULL as _ {
-            self.hook = Some(inner_hook);
-            self.registered = true;
-            log::trace!("ForegroundWatcher::register() -> successful");
-        } else {
-            self.proc = None;
-            log::error!("ForegroundWatcher::register() -> failed");
-            return Err(WindowsHookError::SetWinEventHook(std::io::Error::last_os_error()).into());
+            if inner_hook != NULL as _ {
+                self.hooks.push(inner_hook);
+            } else {
+                log::error!("ForegroundWatcher::register() -> failed");
+                let error = WindowsHookError::SetWinEventHook(std::io::Error::last_os_error());
+                let _ = self.unregister();
+                return Err(error.into());
+            }
         }
 
+        self.registered = true;
+        log::trace!("ForegroundWatcher::register() -> successful");
+
         Ok(())
     }
 
     pub fn unregister(&mut self) -> VividResult<()> {
-        if let Some(hook) = self.hook.take() {
-            if unsafe { winuser::UnhookWinEvent(hook) } != 0 {
-                log::trace!("ForegroundWatcher::unregister() -> successful");
-                self.proc = None;
-                self.registered = false;
-                return Ok(());
-            } else {
+        if self.hooks.is_empty() {
+            return Err(
+                WindowsHookError::NoHookToUnRegister(std::io::Error::last_os_error()).into(),
+            );
+        }
+
+        let mut result = Ok(());
+        for hook in self.hooks.drain(..) {
+            if unsafe { winuser::UnhookWinEvent(hook) } == 0 {
                 log::error!("ForegroundWatcher::unregister() -> failed");
-                self.proc = None;
-                self.registered = false;
-                return Err(
-                    WindowsHookError::UnhookWinEvent(std::io::Error::last_os_error()).into(),
-                );
+                result =
+                    Err(WindowsHookError::UnhookWinEvent(std::io::Error::last_os_error()).into());
             }
         }
 
-        Err(WindowsHookError::NoHookToUnRegister(std::io::Error::last_os_error()).into())
+        if result.is_ok() {
+            log::trace!("ForegroundWatcher::unregister() -> successful");
+        }
+
+        self.proc = None;
+        self.registered = false;
+        result
     }
 
     unsafe extern "system" fn event_proc(
@@ -101,7 +184,21 @@ impl ForegroundWatcher {
         id_event_thread: DWORD,
         dwms_event_time: DWORD,
     ) {
-        use sysinfo::{ProcessExt as _, SystemExt as _};
+        let kind = match event {
+            winuser::EVENT_SYSTEM_FOREGROUND => ForegroundWatcherEventKind::Foreground,
+            winuser::EVENT_OBJECT_LOCATIONCHANGE => {
+                // Only care about the foreground window itself, not its children, the caret or the cursor
+                if id_object != winuser::OBJID_WINDOW
+                    || id_child != winuser::CHILDID_SELF
+                    || hwnd != winuser::GetForegroundWindow()
+                {
+                    return;
+                }
+                ForegroundWatcherEventKind::StateChanged
+            }
+            _ => return,
+        };
+
         log::trace!(
             "ForegroundWatcher::event_proc({:?}, {}, {:?}, {}, {}, {}, {})",
             event_hook,
@@ -112,14 +209,42 @@ impl ForegroundWatcher {
             id_event_thread,
             dwms_event_time
         );
+
+        let window_state = window_state(hwnd);
+        {
+            let mut state = FOREGROUND_STATE.lock();
+            let new_state = Some((hwnd as usize, window_state));
+            if kind == ForegroundWatcherEventKind::StateChanged && *state == new_state {
+                // Window moved or got resized, but its state didn't change
+                return;
+            }
+            *state = new_state;
+        }
+
+        match Self::inspect_window(kind, hwnd, window_state) {
+            Ok(event) => Self::dispatch(&event),
+            Err(e) => log::error!("{}", e),
+        }
+    }
+
+    /// Builds an event out of the provided window by inspecting its owning process
+    fn inspect_window(
+        kind: ForegroundWatcherEventKind,
+        hwnd: HWND,
+        window_state: WindowState,
+    ) -> VividResult<ForegroundWatcherEvent> {
+        use sysinfo::{ProcessExt as _, SystemExt as _};
         let mut process_id = 0u32;
-        let _ = winapi::um::winuser::GetWindowThreadProcessId(hwnd, &mut process_id);
+        let _ = unsafe { winuser::GetWindowThreadProcessId(hwnd, &mut process_id) };
         let process_id = process_id as usize;
         log::trace!("Found process id #{} from hwnd", process_id);
+        let window_class = window_class(hwnd);
+        let window_title = window_title(hwnd);
+        let monitor = crate::adapter::Gpu::get_window_monitor_name(hwnd).unwrap_or_default();
 
         let _ = (*SYSTEM).write().refresh_process(process_id);
 
-        let mut inspection_result: Option<ForegroundWatcherEvent> = (*SYSTEM)
+        let inspection_result: Option<ForegroundWatcherEvent> = (*SYSTEM)
             .read()
             .get_process(process_id)
             .map(move |process| {
@@ -138,22 +263,63 @@ impl ForegroundWatcher {
                     }
                 }
                 ForegroundWatcherEvent {
+                    kind,
                     hwnd,
                     process_id,
                     process_exe,
                     process_path,
+                    window_class,
+                    window_title,
+                    window_state,
+                    monitor,
                 }
             });
 
-        if let Some(event) = inspection_result.take() {
-            CALLBACKS.read().iter().for_each(|f| {
-                if let Err(e) = f(&event) {
-                    log::error!("ForegroundWatcher::event_proc: Error in callback: {}", e);
-                }
-            })
-        } else {
-            log::error!("{}", VividError::ProcessNotAvailable(process_id));
+        inspection_result.ok_or_else(|| VividError::ProcessNotAvailable(process_id))
+    }
+
+    fn dispatch(event: &ForegroundWatcherEvent) {
+        CALLBACKS.read().iter().for_each(|f| {
+            if let Err(e) = f(event) {
+                log::error!("ForegroundWatcher::dispatch: Error in callback: {}", e);
+            }
+        })
+    }
+
+    /// Re-runs the callbacks against the current foreground window, for changes that aren't related to windows.
+    /// If there's no usable foreground window, callbacks get an anonymous event that won't match any program
+    pub fn dispatch_current(kind: ForegroundWatcherEventKind) {
+        let event = Self::current_event(kind);
+        *FOREGROUND_STATE.lock() = Some((event.hwnd as usize, event.window_state));
+        Self::dispatch(&event);
+    }
+
+    /// Builds an event out of the provided window, unless it has been closed meanwhile
+    pub fn window_event(
+        kind: ForegroundWatcherEventKind,
+        hwnd: HWND,
+    ) -> Option<ForegroundWatcherEvent> {
+        if hwnd.is_null() || unsafe { winuser::IsWindow(hwnd) } == 0 {
+            return None;
         }
+
+        Self::inspect_window(kind, hwnd, window_state(hwnd)).ok()
+    }
+
+    /// Builds an event out of the current foreground window
+    pub fn current_event(kind: ForegroundWatcherEventKind) -> ForegroundWatcherEvent {
+        let hwnd = unsafe { winuser::GetForegroundWindow() };
+        Self::window_event(kind, hwnd).unwrap_or_else(|| ForegroundWatcherEvent {
+            kind,
+            hwnd,
+            process_id: 0,
+            process_exe: String::new(),
+            process_path: std::path::PathBuf::new(),
+            window_class: String::new(),
+            window_title: String::new(),
+            window_state: WindowState::Windowed,
+            monitor: String::new(),
+        })
     }
 }
 
@@ -0,0 +1,322 @@
+//! Formats configuration files can be written in. TOML is the reference: comments and formatting are only
+//! kept when editing TOML files, but every format is validated the same way.
+
+/// Format of a configuration file
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Format {
+    Toml,
+    Json,
+    Yaml,
+}
+
+/// Error parsing a file, with the position it points to
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub message: String,
+    /// Line and column, starting at 1
+    pub position: Option<(usize, usize)>,
+}
+
+impl ParseError {
+    fn new(message: String, position: Option<(usize, usize)>) -> Self {
+        let mut message = message;
+        // The position is reported separately
+        if let (Some(_), Some(index)) = (position, message.rfind(" at line ")) {
+            message.truncate(index);
+        }
+        Self { message, position }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "line {}, column {}: {}", line, column, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "toml" => Ok(Self::Toml),
+            "json" => Ok(Self::Json),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            _ => Err(format!(
+                "unknown format \"{}\", expected toml, json or yaml",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Toml => "TOML",
+            Self::Json => "JSON",
+            Self::Yaml => "YAML",
+        })
+    }
+}
+
+/// Writes a path of `serde_ignored` the way keys are written in diagnostics, i.e. `rules[0].when.exe`
+fn key_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}[{}]", key_path(parent), index),
+        Path::Map { parent, key } => match key_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => key_path(parent),
+    }
+}
+
+impl Format {
+    /// Every format, TOML first as it's the one looked up first
+    pub const ALL: [Format; 3] = [Self::Toml, Self::Json, Self::Yaml];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Toml => "toml",
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+        }
+    }
+
+    /// Format of a file according to its extension
+    pub fn from_extension(path: &std::path::Path) -> Option<Self> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse().ok())
+    }
+
+    /// Format of a file: its extension, TOML if it has none or an unknown one
+    pub fn of(path: &std::path::Path) -> Self {
+        Self::from_extension(path).unwrap_or(Self::Toml)
+    }
+
+    /// Format of the main configuration file: the one passed with `--format`, or its extension
+    pub fn of_main(path: &std::path::Path, format: Option<Self>) -> Self {
+        format.unwrap_or_else(|| Self::of(path))
+    }
+
+    pub fn parse<T: serde::de::DeserializeOwned>(self, source: &str) -> Result<T, ParseError> {
+        self.parse_with_unknown_keys(source).map(|(value, _)| value)
+    }
+
+    /// Parses a file, also returning the keys it has that `T` doesn't know about and are ignored,
+    /// written as `program_settings[0].fulscreen_only`
+    pub fn parse_with_unknown_keys<T: serde::de::DeserializeOwned>(
+        self,
+        source: &str,
+    ) -> Result<(T, Vec<String>), ParseError> {
+        let mut unknown = vec![];
+        let on_unknown = |path: serde_ignored::Path| unknown.push(key_path(&path));
+        let value = match self {
+            Self::Toml => {
+                let mut deserializer = toml::Deserializer::new(source);
+                serde_ignored::deserialize(&mut deserializer, on_unknown)
+                    .and_then(|value| deserializer.end().map(|_| value))
+                    .map_err(|e| {
+                        let position = e.line_col().map(|(line, column)| (line + 1, column + 1));
+                        ParseError::new(e.to_string(), position)
+                    })
+            }
+            Self::Json => {
+                let mut deserializer = serde_json::Deserializer::from_str(source);
+                serde_ignored::deserialize(&mut deserializer, on_unknown)
+                    .and_then(|value| deserializer.end().map(|_| value))
+                    .map_err(|e| {
+                        let position = Some((e.line(), e.column())).filter(|(line, _)| *line > 0);
+                        ParseError::new(e.to_string(), position)
+                    })
+            }
+            Self::Yaml => {
+                serde_ignored::deserialize(serde_yaml::Deserializer::from_str(source), on_unknown)
+                    .map_err(|e| {
+                        let position = e
+                            .location()
+                            .map(|location| (location.line(), location.column()));
+                        ParseError::new(e.to_string(), position)
+                    })
+            }
+        }?;
+        Ok((value, unknown))
+    }
+
+    pub fn write<T: serde::Serialize>(self, value: &T) -> Result<String, String> {
+        match self {
+            Self::Toml => toml::to_string_pretty(value).map_err(|e| e.to_string()),
+            Self::Json => serde_json::to_string_pretty(value)
+                .map(|json| json + "\n")
+                .map_err(|e| e.to_string()),
+            Self::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Rewrites a file in another format. Comments are lost, and TOML has no null: keys set to null are left out
+    pub fn convert(self, source: &str, to: Self) -> Result<String, ParseError> {
+        fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+            use serde_json::Value;
+            match value {
+                Value::Object(map) => Value::Object(
+                    map.into_iter()
+                        .filter(|(_, value)| !value.is_null())
+                        .map(|(key, value)| (key, without_nulls(value)))
+                        .collect(),
+                ),
+                Value::Array(values) => {
+                    Value::Array(values.into_iter().map(without_nulls).collect())
+                }
+                value => value,
+            }
+        }
+
+        let value = self.parse::<serde_json::Value>(source)?;
+        let written = match to {
+            // TOML requires plain values to be written before tables, `toml::Value` orders them
+            Self::Toml => toml::Value::try_from(without_nulls(value))
+                .map_err(|e| e.to_string())
+                .and_then(|value| to.write(&value)),
+            _ => to.write(&value),
+        };
+        written.map_err(|message| ParseError::new(message, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!("toml".parse(), Ok(Format::Toml));
+        assert_eq!("JSON".parse(), Ok(Format::Json));
+        assert_eq!("Yaml".parse(), Ok(Format::Yaml));
+        assert_eq!("yml".parse(), Ok(Format::Yaml));
+        assert_eq!(
+            "ini".parse::<Format>(),
+            Err("unknown format \"ini\", expected toml, json or yaml".into())
+        );
+        for format in &Format::ALL {
+            assert_eq!(format.extension().parse(), Ok(*format));
+        }
+    }
+
+    #[test]
+    fn formats_of_files() {
+        let path = std::path::Path::new;
+        assert_eq!(Format::of(path("vivid.json")), Format::Json);
+        assert_eq!(Format::of(path("vivid.YML")), Format::Yaml);
+        assert_eq!(Format::from_extension(path("vivid")), None);
+        assert_eq!(Format::of(path("vivid")), Format::Toml);
+        assert_eq!(Format::of(path("vivid.ini")), Format::Toml);
+        assert_eq!(Format::of_main(path("vivid.json"), None), Format::Json);
+        // `--format` wins over the extension
+        assert_eq!(
+            Format::of_main(path("vivid.json"), Some(Format::Yaml)),
+            Format::Yaml
+        );
+        assert_eq!(
+            Format::of_main(path("vivid.conf"), Some(Format::Json)),
+            Format::Json
+        );
+    }
+
+    #[test]
+    fn parse_error_positions() {
+        let error = ParseError::new("expected a value at line 3 column 9".into(), Some((3, 9)));
+        assert_eq!(error.message, "expected a value");
+        assert_eq!(error.to_string(), "line 3, column 9: expected a value");
+        // Without a position the message is kept as is
+        let error = ParseError::new("expected a value at line 3 column 9".into(), None);
+        assert_eq!(error.message, "expected a value at line 3 column 9");
+        assert_eq!(error.to_string(), "expected a value at line 3 column 9");
+
+        let position = |format: Format, source: &str| {
+            format
+                .parse::<serde_json::Value>(source)
+                .unwrap_err()
+                .position
+        };
+        assert_eq!(position(Format::Toml, "a = 1\nb = \n"), Some((2, 5)));
+        assert_eq!(position(Format::Json, "{\n  \"a\": 1,\n}"), Some((3, 1)));
+        assert_eq!(position(Format::Yaml, "a: 1\nb: [\n"), Some((3, 1)));
+    }
+
+    #[test]
+    fn conversion_drops_nulls() {
+        let json = r#"{"a": 1, "b": null, "c": {"d": null, "e": "f"}, "g": [{"h": null, "i": 2}]}"#;
+        let toml = Format::Json.convert(json, Format::Toml).unwrap();
+        assert_eq!(
+            Format::Toml.parse::<serde_json::Value>(&toml).unwrap(),
+            serde_json::json!({"a": 1, "c": {"e": "f"}, "g": [{"i": 2}]})
+        );
+        // Other formats have null
+        assert_eq!(
+            Format::Json
+                .convert(r#"{"a": null}"#, Format::Yaml)
+                .unwrap(),
+            "---\na: ~\n"
+        );
+    }
+
+    #[test]
+    fn conversion_orders_toml_values() {
+        // Plain values are written before tables, whatever their order
+        let json = r#"{"a_table": {"x": 1}, "b_tables": [{"y": 2}], "c_value": 3}"#;
+        let toml = Format::Json.convert(json, Format::Toml).unwrap();
+        assert!(toml.starts_with("c_value = 3\n"), "{}", toml);
+        assert_eq!(
+            Format::Toml.parse::<serde_json::Value>(&toml).unwrap(),
+            Format::Json.parse::<serde_json::Value>(json).unwrap()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("format")
+            .join("vivid.toml");
+        let toml = std::fs::read_to_string(path).unwrap();
+        let json = Format::Toml.convert(&toml, Format::Json).unwrap();
+        let yaml = Format::Json.convert(&json, Format::Yaml).unwrap();
+        let back = Format::Yaml.convert(&yaml, Format::Toml).unwrap();
+
+        let value =
+            |format: Format, source: &str| format.parse::<serde_json::Value>(source).unwrap();
+        assert_eq!(value(Format::Json, &json), value(Format::Toml, &toml));
+        assert_eq!(value(Format::Yaml, &yaml), value(Format::Toml, &toml));
+        assert_eq!(value(Format::Toml, &back), value(Format::Toml, &toml));
+
+        // Each of them is still a valid configuration, without unknown keys
+        for (format, source) in &[
+            (Format::Json, &json),
+            (Format::Yaml, &yaml),
+            (Format::Toml, &back),
+        ] {
+            let report = crate::validate::check_files(
+                &[crate::validate::SourceFile {
+                    path: format!("vivid.{}", format.extension()).into(),
+                    format: *format,
+                    source: source.to_string(),
+                }],
+                &Default::default(),
+                crate::validate::Tests::Checked,
+            );
+            let diagnostics: Vec<String> =
+                report.diagnostics.iter().map(ToString::to_string).collect();
+            assert!(diagnostics.is_empty(), "{}: {:?}", format, diagnostics);
+        }
+    }
+}
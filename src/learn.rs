@@ -0,0 +1,241 @@
+//! Learn mode: records how long each program stays in foreground and in which state, to propose settings for
+//! the programs used in fullscreen
+
+use crate::config::WindowState;
+use std::time::{Duration, Instant};
+
+/// Time a program spent in foreground
+#[derive(Debug, Clone)]
+pub struct Usage {
+    /// Name of the program, as spelled by its process
+    pub exe: String,
+    pub path: std::path::PathBuf,
+    pub foreground: Duration,
+    /// Part of the time in foreground spent fullscreen, either exclusive or borderless
+    pub fullscreen: Duration,
+    /// How many times it came to foreground
+    pub activations: u32,
+}
+
+impl Usage {
+    /// Tells if the program is mostly used fullscreen
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen * 2 >= self.foreground && self.fullscreen > Duration::from_secs(0)
+    }
+}
+
+/// Foreground programs seen so far
+#[derive(Debug, Default)]
+pub struct Recorder {
+    usages: Vec<Usage>,
+    /// Program in foreground (its index in `usages`), its window state and since when
+    current: Option<(usize, WindowState, Instant)>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a foreground change, or a state change of the foreground window.
+    /// Returns the program's usage when it's seen for the first time
+    pub fn record(
+        &mut self,
+        exe: &str,
+        path: &std::path::Path,
+        state: WindowState,
+        at: Instant,
+    ) -> Option<&Usage> {
+        let previous = self.current.map(|(index, _, _)| index);
+        self.stop(at);
+        // Windows that couldn't be inspected
+        if exe.is_empty() {
+            return None;
+        }
+
+        let (index, is_new) = match self
+            .usages
+            .iter()
+            .position(|usage| usage.exe.eq_ignore_ascii_case(exe))
+        {
+            Some(index) => (index, false),
+            None => {
+                self.usages.push(Usage {
+                    exe: exe.into(),
+                    path: path.into(),
+                    foreground: Duration::default(),
+                    fullscreen: Duration::default(),
+                    activations: 0,
+                });
+                (self.usages.len() - 1, true)
+            }
+        };
+        if previous != Some(index) {
+            self.usages[index].activations += 1;
+        }
+        self.current = Some((index, state, at));
+
+        Some(&self.usages[index]).filter(|_| is_new)
+    }
+
+    /// Adds the time since the last change to the program in foreground
+    pub fn stop(&mut self, at: Instant) {
+        if let Some((index, state, since)) = self.current.take() {
+            let elapsed = at.saturating_duration_since(since);
+            let usage = &mut self.usages[index];
+            usage.foreground += elapsed;
+            if state != WindowState::Windowed {
+                usage.fullscreen += elapsed;
+            }
+        }
+    }
+
+    /// Every program seen, the most used first
+    pub fn usages(&self) -> Vec<&Usage> {
+        let mut usages: Vec<&Usage> = self.usages.iter().collect();
+        usages.sort_by(|a, b| b.foreground.cmp(&a.foreground));
+        usages
+    }
+
+    /// Programs to propose settings for: mostly used fullscreen, for at least `min_fullscreen`, the most used first
+    pub fn proposals(&self, min_fullscreen: Duration) -> Vec<&Usage> {
+        self.usages()
+            .into_iter()
+            .filter(|usage| usage.is_fullscreen() && usage.fullscreen >= min_fullscreen)
+            .collect()
+    }
+}
+
+/// Formats a duration for humans, i.e. `1h 05m`, `4m 10s` or `12s`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, seconds) => format!("{}s", seconds),
+        (0, minutes, seconds) => format!("{}m {:02}s", minutes, seconds),
+        (hours, minutes, _) => format!("{}h {:02}m", hours, minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minutes(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+
+    /// Records a session: each program with its state, for a number of minutes
+    fn session(events: &[(&str, WindowState, u64)]) -> Recorder {
+        let mut recorder = Recorder::new();
+        let mut at = Instant::now();
+        for (exe, state, duration) in events {
+            let path = std::path::Path::new("C:\\Games").join(exe);
+            recorder.record(exe, &path, *state, at);
+            at += minutes(*duration);
+        }
+        recorder.stop(at);
+        recorder
+    }
+
+    fn summary(usages: &[&Usage]) -> Vec<(String, u32, u64, u64)> {
+        usages
+            .iter()
+            .map(|usage| {
+                (
+                    usage.exe.clone(),
+                    usage.activations,
+                    usage.foreground.as_secs() / 60,
+                    usage.fullscreen.as_secs() / 60,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn new_programs_are_returned_once() {
+        let mut recorder = Recorder::new();
+        let at = Instant::now();
+        let path = std::path::Path::new("C:\\Games\\game.exe");
+        assert_eq!(
+            recorder
+                .record("game.exe", path, WindowState::Windowed, at)
+                .map(|usage| usage.exe.clone()),
+            Some("game.exe".into())
+        );
+        // Names are compared case insensitively, the first spelling is kept
+        assert!(recorder
+            .record("GAME.EXE", path, WindowState::Fullscreen, at)
+            .is_none());
+        // Windows that couldn't be inspected aren't recorded
+        assert!(recorder
+            .record("", path, WindowState::Windowed, at)
+            .is_none());
+        assert_eq!(recorder.usages().len(), 1);
+    }
+
+    #[test]
+    fn activations_and_fullscreen_share() {
+        let recorder = session(&[
+            ("game.exe", WindowState::Windowed, 5),
+            // State changes of the foreground window aren't activations
+            ("game.exe", WindowState::Fullscreen, 30),
+            ("browser.exe", WindowState::Windowed, 10),
+            ("game.exe", WindowState::Borderless, 20),
+            // Unknown windows stop the time of the previous program
+            ("", WindowState::Windowed, 60),
+            ("browser.exe", WindowState::Borderless, 15),
+            ("video.exe", WindowState::Fullscreen, 3),
+        ]);
+        // The most used first
+        assert_eq!(
+            summary(&recorder.usages()),
+            [
+                ("game.exe".into(), 2, 55, 50),
+                ("browser.exe".into(), 2, 25, 15),
+                ("video.exe".into(), 1, 3, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn proposals() {
+        let recorder = session(&[
+            ("game.exe", WindowState::Fullscreen, 30),
+            // Exactly half of the time fullscreen
+            ("video.exe", WindowState::Windowed, 10),
+            ("video.exe", WindowState::Borderless, 10),
+            ("browser.exe", WindowState::Borderless, 9),
+            ("browser.exe", WindowState::Windowed, 11),
+            ("editor.exe", WindowState::Windowed, 60),
+            ("menu.exe", WindowState::Fullscreen, 2),
+        ]);
+        let names = |min_fullscreen| -> Vec<String> {
+            recorder
+                .proposals(min_fullscreen)
+                .iter()
+                .map(|usage| usage.exe.clone())
+                .collect()
+        };
+        assert_eq!(names(minutes(0)), ["game.exe", "video.exe", "menu.exe"]);
+        assert_eq!(names(minutes(10)), ["game.exe", "video.exe"]);
+        assert_eq!(names(minutes(11)), ["game.exe"]);
+        assert!(names(minutes(31)).is_empty());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "0s");
+        assert_eq!(format_duration(Duration::from_millis(12_900)), "12s");
+        assert_eq!(format_duration(Duration::from_secs(59)), "59s");
+        assert_eq!(format_duration(Duration::from_secs(60)), "1m 00s");
+        assert_eq!(format_duration(Duration::from_secs(4 * 60 + 10)), "4m 10s");
+        assert_eq!(format_duration(Duration::from_secs(3599)), "59m 59s");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h 00m");
+        // Seconds aren't shown once there are hours
+        assert_eq!(
+            format_duration(Duration::from_secs(3600 + 5 * 60 + 59)),
+            "1h 05m"
+        );
+        assert_eq!(format_duration(Duration::from_secs(26 * 3600)), "26h 00m");
+    }
+}
@@ -6,12 +6,29 @@
 // TODO: Support changing desktop resolution on application start
 
 mod adapter;
+mod commands;
 mod config;
+mod config_edit;
+mod config_watch;
+mod discover;
 mod foreground_callback;
 mod foreground_watch;
+mod format;
+mod learn;
+mod migrate;
+mod power;
+mod power_watch;
+mod process_watch;
+mod rule_tests;
+mod rules;
+mod schedule;
+mod schedule_watch;
+mod validate;
+mod vdf;
+mod vibrancegui;
+mod w32_console;
 mod w32_msgloop;
 // mod w32_notifyicon;
-#[cfg(debug_assertions)]
 mod w32_ctrlc;
 
 mod error;
@@ -30,12 +47,15 @@ pub(crate) fn arcmutex<T: Into<parking_lot::Mutex<T>>>(x: T) -> ArcMutex<T> {
     author = "by Mathieu Amiot / @OtaK_"
 )]
 struct Opts {
-    /// Launch an editor to edit the config file
+    /// Edit the config file in $VISUAL, $EDITOR or the associated program, it's only saved once valid
     #[structopt(short, long)]
     edit: bool,
     /// Pass a custom configuration file path
     #[structopt(short = "c", long = "config")]
     config_file: Option<String>,
+    /// Format of the configuration file (toml, json or yaml), guessed from its extension by default
+    #[structopt(long)]
+    format: Option<format::Format>,
     /// Bypasses GPU detection and forces to load the NVidia-specific code.
     /// It can provoke errors if you don't own an NVidia GPU or if drivers cannot be found on your system.
     #[structopt(long)]
@@ -45,6 +65,8 @@ struct Opts {
     /// Warning: This is a placeholder flag and will not work, as AMD GPUs are not currently supported.
     #[structopt(long)]
     amd: bool,
+    #[structopt(subcommand)]
+    command: Option<commands::Command>,
 }
 
 pub static mut GPU: VividResult<parking_lot::RwLock<adapter::Gpu>> = Err(VividError::NoGpuDetected);
@@ -59,12 +81,18 @@ fn main(opts: Opts) -> error::VividResult<()> {
     pretty_env_logger::init();
 
     if opts.edit {
-        config::Config::edit()?;
-        return Ok(());
+        w32_console::attach_parent_console();
+        return commands::edit::run(opts.config_file, opts.format);
+    }
+
+    if let Some(command) = opts.command {
+        w32_console::attach_parent_console();
+        return command.run(opts.config_file, opts.format);
     }
 
+    let config_location = config::Config::discover(opts.config_file.clone())?;
     unsafe {
-        CONFIG = config::Config::load(opts.config_file);
+        CONFIG = config::Config::load(opts.config_file, opts.format);
     }
 
     let adapter = if opts.nvidia {
@@ -90,6 +118,38 @@ fn main(opts: Opts) -> error::VividResult<()> {
     watcher.register()?;
     log::trace!("is watcher registered? -> {}", watcher.is_registered());
 
+    let mut process_watcher = process_watch::ProcessWatcher::new();
+    process_watcher.add_event_callback(foreground_callback::process_handler);
+    process_watcher.register()?;
+    log::trace!(
+        "is process watcher registered? -> {}",
+        process_watcher.is_registered()
+    );
+
+    let mut schedule_watcher = schedule_watch::ScheduleWatcher::new();
+    schedule_watcher.register()?;
+    log::trace!(
+        "is schedule watcher registered? -> {}",
+        schedule_watcher.is_registered()
+    );
+
+    // Only laptops can switch between AC and battery
+    let mut power_watcher = power_watch::PowerWatcher::new();
+    if unsafe { GPU.as_ref()? }.read().system_type == adapter::SystemType::Laptop {
+        power_watcher.register()?;
+    }
+    log::trace!(
+        "is power watcher registered? -> {}",
+        power_watcher.is_registered()
+    );
+
+    let mut config_watcher = config_watch::ConfigWatcher::new(config_location.path, opts.format);
+    config_watcher.register()?;
+    log::trace!(
+        "is config watcher registered? -> {}",
+        config_watcher.is_registered()
+    );
+
     //w32_notifyicon::register()?;
 
     let mut msg = unsafe { std::mem::zeroed() };
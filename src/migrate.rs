@@ -0,0 +1,346 @@
+//! Configuration format versions, and the migrations upgrading files written for older ones.
+//! Migrations edit the text like `vivid rule` does, so comments and formatting are kept.
+//! JSON and YAML files are upgraded through TOML and written back, losing their formatting.
+
+use crate::config_edit::Document;
+use crate::format::Format;
+
+/// Version of the configuration format, written as `version` at the top of configuration files
+pub const CURRENT_VERSION: u32 = 2;
+
+/// Version of files without a `version` key, written for Vivid 0.2
+pub const UNVERSIONED: u32 = 1;
+
+/// A step upgrading a configuration file from a version to the next one
+pub struct Migration {
+    /// Version this migration upgrades from
+    pub from: u32,
+    pub description: &'static str,
+    /// Rewrites the layout of the file, the `version` key is updated once every migration is applied
+    apply: fn(&mut Document) -> Result<(), String>,
+}
+
+impl std::fmt::Debug for Migration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> {}: {}",
+            self.from,
+            self.from + 1,
+            self.description
+        )
+    }
+}
+
+/// Every migration, in order
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    description: "the Vivid 0.2 layout is a subset of version 2, only the version is added",
+    apply: |_| Ok(()),
+}];
+
+/// The `version` key of a file, `T` being a `toml::Spanned` to know its position in TOML files
+#[derive(Debug, Default, serde::Deserialize)]
+struct Versioned<T> {
+    version: Option<T>,
+}
+
+/// Offset of the `version` key of a file, if it has one. Only TOML files have positions
+pub fn version_offset(source: &str, format: Format) -> Option<usize> {
+    Some(format)
+        .filter(|format| *format == Format::Toml)
+        .and_then(|_| toml::from_str::<Versioned<toml::Spanned<toml::Value>>>(source).ok())
+        .and_then(|versioned| versioned.version)
+        .map(|version| version.start())
+}
+
+/// Version a file is written for.
+/// Files that can't be parsed are considered up to date, validation reports their errors
+pub fn version(source: &str, format: Format) -> Result<u32, String> {
+    let version = match format.parse::<Versioned<toml::Value>>(source) {
+        Ok(Versioned {
+            version: Some(version),
+        }) => version,
+        Ok(Versioned { version: None }) => return Ok(UNVERSIONED),
+        Err(_) => return Ok(CURRENT_VERSION),
+    };
+
+    match version.as_integer() {
+        Some(number) if number >= 1 && number <= u32::MAX as i64 => Ok(number as u32),
+        _ => Err(format!(
+            "`version` is the version of the configuration format, {} isn't one",
+            version
+        )),
+    }
+}
+
+/// Outcome of migrating a file
+#[derive(Debug)]
+pub struct Migrated {
+    /// Version the file was written for
+    pub from: u32,
+    /// Contents of the file, upgraded to the current version
+    pub source: String,
+    pub applied: Vec<&'static Migration>,
+}
+
+impl Migrated {
+    pub fn is_upgrade(&self) -> bool {
+        !self.applied.is_empty()
+    }
+}
+
+fn upgrade(source: &str, format: Format, stamp: bool) -> Result<Migrated, String> {
+    let from = version(source, format)?;
+    if from > CURRENT_VERSION {
+        return Err(format!(
+            "this file is written for version {} of the configuration format, this Vivid only supports up to version {}",
+            from, CURRENT_VERSION
+        ));
+    }
+
+    let applied: Vec<&'static Migration> = MIGRATIONS
+        .iter()
+        .filter(|migration| migration.from >= from)
+        .collect();
+    if applied.is_empty() {
+        return Ok(Migrated {
+            from,
+            source: source.into(),
+            applied,
+        });
+    }
+
+    let toml = match format {
+        Format::Toml => source.into(),
+        _ => format
+            .convert(source, Format::Toml)
+            .map_err(|e| e.to_string())?,
+    };
+    let mut document = Document::new(toml);
+    for migration in &applied {
+        (migration.apply)(&mut document)
+            .map_err(|e| format!("cannot upgrade from version {}: {}", migration.from, e))?;
+    }
+    if stamp {
+        document.set_top_level("version", &toml::Value::Integer(CURRENT_VERSION.into()))?;
+    }
+
+    let source = match format {
+        Format::Toml => document.source().into(),
+        _ => Format::Toml
+            .convert(document.source(), format)
+            .map_err(|e| e.to_string())?,
+    };
+    Ok(Migrated {
+        from,
+        source,
+        applied,
+    })
+}
+
+/// Upgrades a file to the current version, setting its `version` key
+pub fn migrate(source: &str, format: Format) -> Result<Migrated, String> {
+    upgrade(source, format, true)
+}
+
+/// Upgrades the layout of a file to load it, without adding a `version` key: lines of TOML files only move
+/// if migrations move them, so positions in diagnostics still match the file
+pub fn upgrade_layout(source: &str, format: Format) -> Result<Migrated, String> {
+    upgrade(source, format, false)
+}
+
+/// Line by line differences, in the unified format with 3 lines of context
+pub fn diff(before: &str, after: &str) -> String {
+    const CONTEXT: usize = 3;
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // Longest common subsequence of the lines following each position
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    // Operations, with the positions in both files before applying them
+    let mut operations: Vec<(char, usize, usize)> = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            operations.push((' ', i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            operations.push(('-', i, j));
+            i += 1;
+        } else {
+            operations.push(('+', i, j));
+            j += 1;
+        }
+    }
+
+    // Hunks: changes with their context, merged when their contexts overlap
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for (index, _) in operations
+        .iter()
+        .enumerate()
+        .filter(|(_, (kind, _, _))| *kind != ' ')
+    {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(operations.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut output = String::new();
+    for (start, end) in hunks {
+        let operations = &operations[start..end];
+        let old_count = operations
+            .iter()
+            .filter(|(kind, _, _)| *kind != '+')
+            .count();
+        let new_count = operations
+            .iter()
+            .filter(|(kind, _, _)| *kind != '-')
+            .count();
+        // Empty ranges start at the line before them
+        let start = |line: usize, count: usize| if count == 0 { line } else { line + 1 };
+        output += &format!(
+            "@@ -{},{} +{},{} @@\n",
+            start(operations[0].1, old_count),
+            old_count,
+            start(operations[0].2, new_count),
+            new_count
+        );
+        for &(kind, i, j) in operations {
+            let line = if kind == '+' { new[j] } else { old[i] };
+            output += &format!("{}{}\n", kind, line);
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNVERSIONED_TOML: &str = "# Vivid 0.2
+desktop_vibrance = 50
+
+[[program_settings]]
+exe_name = \"game.exe\"
+vibrance = 80 # Boosted
+";
+
+    #[test]
+    fn versions() {
+        assert_eq!(version(UNVERSIONED_TOML, Format::Toml), Ok(UNVERSIONED));
+        assert_eq!(version("version = 2", Format::Toml), Ok(2));
+        assert_eq!(version("{ \"version\": 3 }", Format::Json), Ok(3));
+        assert_eq!(version("version: 2", Format::Yaml), Ok(2));
+        assert_eq!(
+            version("version = \"two\"", Format::Toml),
+            Err("`version` is the version of the configuration format, \"two\" isn't one".into())
+        );
+        assert!(version("version = 0", Format::Toml).is_err());
+        // Validation reports why it can't be parsed
+        assert_eq!(
+            version("desktop_vibrance = ", Format::Toml),
+            Ok(CURRENT_VERSION)
+        );
+
+        assert_eq!(
+            version_offset("# Header\nversion = 2\n", Format::Toml),
+            Some(19)
+        );
+        assert_eq!(version_offset(UNVERSIONED_TOML, Format::Toml), None);
+        assert_eq!(version_offset("{ \"version\": 2 }", Format::Json), None);
+    }
+
+    #[test]
+    fn upgrade_unversioned() {
+        let migrated = migrate(UNVERSIONED_TOML, Format::Toml).unwrap();
+        assert_eq!(migrated.from, UNVERSIONED);
+        assert!(migrated.is_upgrade());
+        assert_eq!(migrated.applied.len(), MIGRATIONS.len());
+        assert_eq!(
+            migrated.source,
+            format!("version = {}\n\n{}", CURRENT_VERSION, UNVERSIONED_TOML)
+        );
+        assert_eq!(version(&migrated.source, Format::Toml), Ok(CURRENT_VERSION));
+
+        // Lines don't move when only loading the file
+        let layout = upgrade_layout(UNVERSIONED_TOML, Format::Toml).unwrap();
+        assert!(layout.is_upgrade());
+        assert_eq!(layout.source, UNVERSIONED_TOML);
+    }
+
+    #[test]
+    fn upgrade_unversioned_json() {
+        let migrated = migrate("{ \"desktop_vibrance\": 50 }", Format::Json).unwrap();
+        assert!(migrated.is_upgrade());
+        let value: serde_json::Value = serde_json::from_str(&migrated.source).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "version": CURRENT_VERSION, "desktop_vibrance": 50 })
+        );
+    }
+
+    #[test]
+    fn upgrade_current() {
+        let source = format!("version = {}\n{}", CURRENT_VERSION, UNVERSIONED_TOML);
+        let migrated = migrate(&source, Format::Toml).unwrap();
+        assert_eq!(migrated.from, CURRENT_VERSION);
+        assert!(!migrated.is_upgrade());
+        assert_eq!(migrated.source, source);
+    }
+
+    #[test]
+    fn upgrade_future() {
+        let source = format!("version = {}\n", CURRENT_VERSION + 1);
+        assert_eq!(
+            migrate(&source, Format::Toml).unwrap_err(),
+            format!(
+                "this file is written for version {} of the configuration format, this Vivid only supports up to version {}",
+                CURRENT_VERSION + 1,
+                CURRENT_VERSION
+            )
+        );
+    }
+
+    #[test]
+    fn diff_insert() {
+        let migrated = migrate(UNVERSIONED_TOML, Format::Toml).unwrap();
+        assert_eq!(
+            diff(UNVERSIONED_TOML, &migrated.source),
+            format!(
+                "@@ -1,3 +1,5 @@\n+version = {}\n+\n # Vivid 0.2\n desktop_vibrance = 50\n \n",
+                CURRENT_VERSION
+            )
+        );
+        // Nothing to keep as context
+        assert_eq!(diff("", "a\nb\n"), "@@ -0,0 +1,2 @@\n+a\n+b\n");
+        assert_eq!(diff("a\nb\n", ""), "@@ -1,2 +0,0 @@\n-a\n-b\n");
+    }
+
+    #[test]
+    fn diff_hunks() {
+        assert_eq!(diff(UNVERSIONED_TOML, UNVERSIONED_TOML), "");
+
+        let before = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let after = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n";
+        assert_eq!(
+            diff(before, after),
+            "@@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n@@ -8,3 +8,4 @@\n 8\n 9\n 10\n+11\n"
+        );
+    }
+}
@@ -0,0 +1,379 @@
+use crate::error::{VividError, VividResult};
+
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSource {
+    /// Plugged in
+    Ac,
+    /// Running on battery
+    Battery,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PowerStatus {
+    pub source: PowerSource,
+    /// Remaining battery charge in percent, if there's a battery
+    pub battery_percent: Option<u8>,
+}
+
+impl Default for PowerStatus {
+    /// Desktop computers without any battery
+    fn default() -> Self {
+        Self {
+            source: PowerSource::Ac,
+            battery_percent: None,
+        }
+    }
+}
+
+/// Source of the power status of the system
+pub trait PowerProvider {
+    fn status(&self) -> VividResult<PowerStatus>;
+}
+
+/// Reads the power status through `GetSystemPowerStatus`
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WindowsPowerProvider;
+
+#[cfg(windows)]
+impl PowerProvider for WindowsPowerProvider {
+    fn status(&self) -> VividResult<PowerStatus> {
+        use winapi::um::winbase;
+        /// `BatteryFlag` value for systems without battery
+        const NO_SYSTEM_BATTERY: u8 = 128;
+        /// `ACLineStatus` and `BatteryLifePercent` value when the status is unknown
+        const UNKNOWN: u8 = 255;
+
+        let mut status = winbase::SYSTEM_POWER_STATUS::default();
+        if unsafe { winbase::GetSystemPowerStatus(&mut status) } == 0 {
+            return Err(VividError::windows_error());
+        }
+
+        Ok(PowerStatus {
+            source: if status.ACLineStatus == 0 {
+                PowerSource::Battery
+            } else {
+                PowerSource::Ac
+            },
+            battery_percent: if status.BatteryFlag & NO_SYSTEM_BATTERY != 0
+                || status.BatteryLifePercent == UNKNOWN
+            {
+                None
+            } else {
+                Some(status.BatteryLifePercent)
+            },
+        })
+    }
+}
+
+/// Reads the power status from a sysfs `power_supply` class directory
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone)]
+pub struct SysfsPowerProvider {
+    root: std::path::PathBuf,
+}
+
+#[cfg(target_os = "linux")]
+impl Default for SysfsPowerProvider {
+    fn default() -> Self {
+        Self::new("/sys/class/power_supply")
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl SysfsPowerProvider {
+    pub fn new<P: Into<std::path::PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn read_attribute(supply: &std::path::Path, attribute: &str) -> Option<String> {
+        std::fs::read_to_string(supply.join(attribute))
+            .ok()
+            .map(|value| value.trim().to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl PowerProvider for SysfsPowerProvider {
+    fn status(&self) -> VividResult<PowerStatus> {
+        let mut mains_online = None;
+        let mut battery_discharging = false;
+        let mut battery_percent = None;
+        for entry in std::fs::read_dir(&self.root)? {
+            let supply = entry?.path();
+            match Self::read_attribute(&supply, "type").as_deref() {
+                Some("Mains") | Some("USB") => {
+                    let online = Self::read_attribute(&supply, "online").as_deref() == Some("1");
+                    mains_online = Some(mains_online.unwrap_or(false) || online);
+                }
+                Some("Battery") => {
+                    // Peripherals (mice, gamepads...) report their batteries here too
+                    if Self::read_attribute(&supply, "scope").as_deref() == Some("Device") {
+                        continue;
+                    }
+                    battery_discharging |=
+                        Self::read_attribute(&supply, "status").as_deref() == Some("Discharging");
+                    if battery_percent.is_none() {
+                        battery_percent = Self::read_attribute(&supply, "capacity")
+                            .and_then(|capacity| capacity.parse().ok());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let on_battery = match mains_online {
+            Some(online) => !online,
+            None => battery_discharging,
+        };
+
+        Ok(PowerStatus {
+            source: if on_battery {
+                PowerSource::Battery
+            } else {
+                PowerSource::Ac
+            },
+            battery_percent,
+        })
+    }
+}
+
+/// Current power status using the platform provider. Errors are logged and reported as AC power
+pub fn current_status() -> PowerStatus {
+    #[cfg(windows)]
+    let provider = WindowsPowerProvider;
+    #[cfg(target_os = "linux")]
+    let provider = SysfsPowerProvider::default();
+
+    status_of(&provider)
+}
+
+/// Power status read from a provider. Errors are logged and reported as AC power
+pub fn status_of(provider: &dyn PowerProvider) -> PowerStatus {
+    provider.status().unwrap_or_else(|e| {
+        log::error!("Cannot read power status, assuming AC power: {}", e);
+        PowerStatus::default()
+    })
+}
+
+/// Last read power status, telling which changes can change settings
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PowerTracker {
+    last: PowerStatus,
+}
+
+impl PowerTracker {
+    pub fn new(status: PowerStatus) -> Self {
+        Self { last: status }
+    }
+
+    /// Reads the current status, telling if settings may change with it: the power source changed, or one of the
+    /// conditions isn't met the same way anymore (a battery threshold has been crossed). Battery charge changes
+    /// alone don't change settings
+    pub fn poll(&mut self, provider: &dyn PowerProvider, conditions: &[&PowerCondition]) -> bool {
+        let status = status_of(provider);
+        let previous = std::mem::replace(&mut self.last, status);
+        status.source != previous.source
+            || conditions
+                .iter()
+                .any(|condition| condition.is_met(&previous) != condition.is_met(&status))
+    }
+}
+
+/// Power requirements for settings to apply
+#[derive(
+    Debug, Clone, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+pub struct PowerCondition {
+    /// Required power source, "ac" or "battery"
+    pub source: Option<PowerSource>,
+    /// Minimum battery charge in percent, only checked when running on battery
+    #[schemars(range(max = 100))]
+    pub min_battery: Option<u8>,
+    /// Maximum battery charge in percent, only checked when running on battery
+    #[schemars(range(max = 100))]
+    pub max_battery: Option<u8>,
+}
+
+impl PowerCondition {
+    pub fn is_met(&self, status: &PowerStatus) -> bool {
+        if let Some(source) = self.source {
+            if source != status.source {
+                return false;
+            }
+        }
+
+        match (status.source, status.battery_percent) {
+            (PowerSource::Battery, Some(percent)) => {
+                self.min_battery.map_or(true, |min| percent >= min)
+                    && self.max_battery.map_or(true, |max| percent <= max)
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    fn sysfs_status(fixture: &str) -> PowerStatus {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("power_supply")
+            .join(fixture);
+        SysfsPowerProvider::new(root).status().unwrap()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sysfs_ac_online() {
+        assert_eq!(
+            sysfs_status("ac_online"),
+            PowerStatus {
+                source: PowerSource::Ac,
+                battery_percent: Some(64),
+            }
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sysfs_ac_offline() {
+        // The mouse battery isn't the system's, and the USB-C port is unplugged too
+        assert_eq!(
+            sysfs_status("ac_offline"),
+            PowerStatus {
+                source: PowerSource::Battery,
+                battery_percent: Some(42),
+            }
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sysfs_no_battery() {
+        assert_eq!(sysfs_status("no_battery"), PowerStatus::default());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sysfs_battery_only() {
+        // Without any mains supply, the battery status tells the source
+        assert_eq!(
+            sysfs_status("battery_only"),
+            PowerStatus {
+                source: PowerSource::Battery,
+                battery_percent: Some(15),
+            }
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sysfs_missing_directory() {
+        assert!(SysfsPowerProvider::new("/nonexistent/power_supply")
+            .status()
+            .is_err());
+    }
+
+    /// Provider reporting the statuses it's given, then failing
+    struct Statuses(std::cell::RefCell<Vec<PowerStatus>>);
+
+    impl PowerProvider for Statuses {
+        fn status(&self) -> VividResult<PowerStatus> {
+            let mut statuses = self.0.borrow_mut();
+            if statuses.is_empty() {
+                return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no battery").into());
+            }
+            Ok(statuses.remove(0))
+        }
+    }
+
+    fn battery(percent: u8) -> PowerStatus {
+        PowerStatus {
+            source: PowerSource::Battery,
+            battery_percent: Some(percent),
+        }
+    }
+
+    /// Polls every status in turn, telling which ones may change settings
+    fn poll(first: PowerStatus, statuses: &[PowerStatus], conditions: &[&str]) -> Vec<bool> {
+        let conditions: Vec<PowerCondition> = conditions
+            .iter()
+            .map(|condition| toml::from_str(condition).unwrap())
+            .collect();
+        let conditions: Vec<&PowerCondition> = conditions.iter().collect();
+        let provider = Statuses(std::cell::RefCell::new(statuses.to_vec()));
+        let mut tracker = PowerTracker::new(first);
+        (0..=statuses.len())
+            .map(|_| tracker.poll(&provider, &conditions))
+            .collect()
+    }
+
+    #[test]
+    fn source_changes() {
+        assert_eq!(
+            poll(
+                PowerStatus::default(),
+                &[battery(80), battery(79), PowerStatus::default()],
+                &[]
+            ),
+            // The provider failing last is reported as AC power, which hasn't changed
+            [true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn battery_thresholds() {
+        assert_eq!(
+            poll(
+                battery(32),
+                &[
+                    battery(31),
+                    battery(30),
+                    battery(29),
+                    battery(28),
+                    battery(81)
+                ],
+                &["min_battery = 30", "source = \"battery\"\nmax_battery = 80"]
+            ),
+            [false, false, true, false, true, true]
+        );
+        // Without conditions on it, the battery charge never matters
+        assert_eq!(
+            poll(
+                battery(32),
+                &[battery(29), battery(81)],
+                &["source = \"ac\""]
+            ),
+            [false, false, true]
+        );
+    }
+
+    #[test]
+    fn power_condition() {
+        let battery = |percent| PowerStatus {
+            source: PowerSource::Battery,
+            battery_percent: Some(percent),
+        };
+        let on_battery_above_30: PowerCondition =
+            toml::from_str("source = \"battery\"\nmin_battery = 30").unwrap();
+        assert!(on_battery_above_30.is_met(&battery(30)));
+        assert!(!on_battery_above_30.is_met(&battery(29)));
+        assert!(!on_battery_above_30.is_met(&PowerStatus::default()));
+
+        // Battery charge thresholds aren't checked on AC
+        let below_80: PowerCondition = toml::from_str("max_battery = 80").unwrap();
+        assert!(!below_80.is_met(&battery(81)));
+        assert!(below_80.is_met(&PowerStatus {
+            source: PowerSource::Ac,
+            battery_percent: Some(100),
+        }));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::error::{VividError, VividResult};
+use crate::foreground_watch::{ForegroundWatcher, ForegroundWatcherEventKind};
+use crate::power::PowerTracker;
+use winapi::shared::{
+    basetsd::UINT_PTR,
+    minwindef::{DWORD, UINT},
+    ntdef::NULL,
+    windef::HWND,
+};
+use winapi::um::winuser;
+
+/// Interval between two power status checks
+pub const POLL_INTERVAL_MS: UINT = 10_000;
+
+lazy_static::lazy_static! {
+    static ref TRACKER: parking_lot::Mutex<PowerTracker> = Default::default();
+}
+
+/// Re-evaluates settings when the power source changes or the battery charge crosses a threshold of the configuration
+#[derive(Debug, Default, Clone)]
+pub struct PowerWatcher {
+    timer: Option<UINT_PTR>,
+}
+
+impl PowerWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_registered(&self) -> bool {
+        self.timer.is_some()
+    }
+
+    pub fn register(&mut self) -> VividResult<()> {
+        *TRACKER.lock() = PowerTracker::new(crate::power::current_status());
+
+        let timer =
+            unsafe { winuser::SetTimer(NULL as _, 0, POLL_INTERVAL_MS, Some(Self::timer_proc)) };
+
+        if timer == 0 {
+            log::error!("PowerWatcher::register() -> failed");
+            return Err(VividError::windows_error());
+        }
+
+        self.timer = Some(timer);
+        log::trace!("PowerWatcher::register() -> successful");
+        Ok(())
+    }
+
+    pub fn unregister(&mut self) -> VividResult<()> {
+        if let Some(timer) = self.timer.take() {
+            if unsafe { winuser::KillTimer(NULL as _, timer) } == 0 {
+                log::error!("PowerWatcher::unregister() -> failed");
+                return Err(VividError::windows_error());
+            }
+            log::trace!("PowerWatcher::unregister() -> successful");
+        }
+
+        Ok(())
+    }
+
+    unsafe extern "system" fn timer_proc(_: HWND, _: UINT, _: UINT_PTR, _: DWORD) {
+        let provider = crate::power::WindowsPowerProvider;
+        let conditions = crate::CONFIG
+            .as_ref()
+            .map(|config| config.power_conditions())
+            .unwrap_or_default();
+        let changed = TRACKER.lock().poll(&provider, &conditions);
+
+        if changed {
+            log::trace!(
+                "PowerWatcher::timer_proc: power source changed or a battery threshold was crossed"
+            );
+            ForegroundWatcher::dispatch_current(ForegroundWatcherEventKind::PowerChanged);
+        }
+    }
+}
+
+impl Drop for PowerWatcher {
+    fn drop(&mut self) {
+        let _ = self.unregister();
+    }
+}
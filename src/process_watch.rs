@@ -0,0 +1,158 @@
+use crate::error::{VividError, VividResult};
+use winapi::shared::{
+    basetsd::UINT_PTR,
+    minwindef::{DWORD, UINT},
+    ntdef::NULL,
+    windef::HWND,
+};
+use winapi::um::winuser;
+
+/// Interval between two process list refreshes
+pub const POLL_INTERVAL_MS: UINT = 2000;
+
+lazy_static::lazy_static! {
+    static ref CALLBACKS: parking_lot::RwLock<Vec<fn(&ProcessWatcherEvent) -> VividResult<()>>> = parking_lot::RwLock::new(vec![]);
+    /// Currently running processes, PID -> process name
+    static ref RUNNING: parking_lot::RwLock<std::collections::HashMap<usize, String>> = parking_lot::RwLock::new(Default::default());
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ProcessWatcherEventKind {
+    Started,
+    Exited,
+}
+
+#[derive(Debug)]
+pub struct ProcessWatcherEvent {
+    pub kind: ProcessWatcherEventKind,
+    pub process_id: usize,
+    pub process_exe: String,
+}
+
+/// Tells if a process with this name (case insensitive) is currently running, as of the last refresh of the system
+/// snapshot
+pub fn is_running(process_exe: &str) -> bool {
+    use sysinfo::{ProcessExt as _, SystemExt as _};
+    crate::foreground_watch::SYSTEM
+        .read()
+        .get_processes()
+        .values()
+        .any(|process| process.name().eq_ignore_ascii_case(process_exe))
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ProcessWatcher {
+    timer: Option<UINT_PTR>,
+}
+
+impl ProcessWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_event_callback(&mut self, cb: fn(&ProcessWatcherEvent) -> VividResult<()>) {
+        CALLBACKS.write().push(cb);
+    }
+
+    pub fn is_registered(&self) -> bool {
+        self.timer.is_some()
+    }
+
+    /// Takes a first snapshot of running processes and starts polling on the thread's message loop
+    pub fn register(&mut self) -> VividResult<()> {
+        *RUNNING.write() = Self::snapshot();
+
+        let timer =
+            unsafe { winuser::SetTimer(NULL as _, 0, POLL_INTERVAL_MS, Some(Self::timer_proc)) };
+
+        if timer == 0 {
+            log::error!("ProcessWatcher::register() -> failed");
+            return Err(VividError::windows_error());
+        }
+
+        self.timer = Some(timer);
+        log::trace!("ProcessWatcher::register() -> successful");
+        Ok(())
+    }
+
+    pub fn unregister(&mut self) -> VividResult<()> {
+        if let Some(timer) = self.timer.take() {
+            if unsafe { winuser::KillTimer(NULL as _, timer) } == 0 {
+                log::error!("ProcessWatcher::unregister() -> failed");
+                return Err(VividError::windows_error());
+            }
+            log::trace!("ProcessWatcher::unregister() -> successful");
+        }
+
+        Ok(())
+    }
+
+    fn snapshot() -> std::collections::HashMap<usize, String> {
+        use sysinfo::{ProcessExt as _, SystemExt as _};
+        let mut system = crate::foreground_watch::SYSTEM.write();
+        system.refresh_processes();
+        system
+            .get_processes()
+            .iter()
+            .map(|(pid, process)| (*pid, process.name().to_string()))
+            .collect()
+    }
+
+    unsafe extern "system" fn timer_proc(_: HWND, _: UINT, _: UINT_PTR, _: DWORD) {
+        let current = Self::snapshot();
+        let mut events = vec![];
+        {
+            let mut running = RUNNING.write();
+            for (&process_id, process_exe) in running.iter() {
+                if !current.contains_key(&process_id) {
+                    events.push(ProcessWatcherEvent {
+                        kind: ProcessWatcherEventKind::Exited,
+                        process_id,
+                        process_exe: process_exe.clone(),
+                    });
+                }
+            }
+            for (&process_id, process_exe) in current.iter() {
+                if !running.contains_key(&process_id) {
+                    events.push(ProcessWatcherEvent {
+                        kind: ProcessWatcherEventKind::Started,
+                        process_id,
+                        process_exe: process_exe.clone(),
+                    });
+                }
+            }
+            *running = current;
+        }
+
+        for event in events {
+            log::trace!("ProcessWatcher::timer_proc: {:?}", event);
+            CALLBACKS.read().iter().for_each(|f| {
+                if let Err(e) = f(&event) {
+                    log::error!("ProcessWatcher::timer_proc: Error in callback: {}", e);
+                }
+            })
+        }
+    }
+}
+
+impl Drop for ProcessWatcher {
+    fn drop(&mut self) {
+        let _ = self.unregister();
+        CALLBACKS.write().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_names_are_case_insensitive() {
+        ProcessWatcher::snapshot();
+        let exe = std::env::current_exe().unwrap();
+        let name = exe.file_name().unwrap().to_string_lossy();
+        assert!(is_running(&name));
+        assert!(is_running(&name.to_uppercase()));
+        assert!(!is_running("not-a-running-program.exe"));
+    }
+}
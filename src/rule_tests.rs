@@ -0,0 +1,405 @@
+//! Test cases embedded in the configuration (`[[tests]]`): a synthetic foreground context and the
+//! outcome the rule engine is expected to produce for it.
+//! Like the rule engine, nothing in here touches the OS: programs listed in `running` are the only
+//! ones considered running, so results don't depend on the machine running the tests.
+
+use crate::config::{Config, VideoMode, WindowState};
+use crate::power::{PowerSource, PowerStatus};
+use crate::rules::{Decision, Evaluation, RuleContext};
+
+/// Synthetic foreground context and expected outcome
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct TestCase {
+    /// Name of the test, used in reports
+    pub name: Option<String>,
+    /// Name of the foreground program, no program (desktop) if unset
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub exe: String,
+    /// Path of the foreground program, defaults to `exe`
+    pub path: Option<String>,
+    /// Title of the foreground window
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    /// Class of the foreground window
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub class: String,
+    /// State of the foreground window, defaults to windowed
+    pub window_state: Option<WindowState>,
+    /// Monitor the foreground window is displayed on (i.e. `\\.\DISPLAY1`)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub display: String,
+    /// Either "HH:MM" (today) or an RFC 3339 date and time. A full date is required once a schedule depending on
+    /// weekdays or sunrise/sunset may apply, so results don't depend on the day tests run
+    pub time: Option<String>,
+    /// Power source, defaults to AC
+    pub power: Option<PowerSource>,
+    /// Battery charge in percent
+    #[schemars(range(max = 100))]
+    pub battery: Option<u8>,
+    /// Programs running
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub running: Vec<String>,
+    /// Expected outcome, unset fields aren't checked
+    pub expect: Expectation,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Expectation {
+    /// Vibrance applied
+    #[schemars(range(max = 100))]
+    pub vibrance: Option<u8>,
+    /// Name of the winning rule, or the `exe_name` of the winning `program_settings` entry
+    #[serde(alias = "profile")]
+    pub rule: Option<String>,
+    /// Whether no rule should match, leaving desktop settings
+    pub desktop: Option<bool>,
+    /// Video mode applied
+    pub resolution: Option<VideoMode>,
+    /// Whether settings stick to the window's monitor
+    pub sticky: Option<bool>,
+    /// Whether the window is in the ignore list
+    pub ignored: Option<bool>,
+}
+
+/// A value that differs from the expectation
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Result of a test case
+#[derive(Debug)]
+pub struct TestOutcome {
+    /// Name of the test, or its position in the configuration
+    pub name: String,
+    pub mismatches: Vec<Mismatch>,
+    /// The test case itself couldn't be run
+    pub error: Option<String>,
+    pub decision: Option<Decision>,
+}
+
+impl TestOutcome {
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.mismatches.is_empty()
+    }
+}
+
+impl std::fmt::Display for TestOutcome {
+    /// Test name and status, followed by a diff of the mismatches and the reason of the decision
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.passed() {
+            return write!(f, "PASS {}", self.name);
+        }
+
+        writeln!(f, "FAIL {}", self.name)?;
+        if let Some(error) = &self.error {
+            return write!(f, "    {}", error);
+        }
+
+        for mismatch in &self.mismatches {
+            writeln!(f, "  - {}: {}", mismatch.field, mismatch.expected)?;
+            writeln!(f, "  + {}: {}", mismatch.field, mismatch.actual)?;
+        }
+
+        match &self.decision {
+            Some(Decision::Evaluated(evaluation)) => match evaluation.winning_rule() {
+                Some(outcome) => write!(
+                    f,
+                    "    {} won: {}",
+                    rule_name(outcome.rule.name.as_deref()),
+                    outcome.result.as_ref().unwrap_or_else(|reason| reason)
+                ),
+                None => write!(f, "    no rule matched"),
+            },
+            Some(Decision::Ignored) => write!(f, "    the window is ignored"),
+            None => Ok(()),
+        }
+    }
+}
+
+fn rule_name(name: Option<&str>) -> String {
+    name.map_or_else(|| "<unnamed rule>".into(), |name| format!("\"{}\"", name))
+}
+
+impl TestCase {
+    /// Name of the test, or its position in the configuration
+    pub fn label(&self, index: usize) -> String {
+        self.name.as_ref().map_or_else(
+            || format!("tests[{}]", index),
+            |name| format!("tests[{}] \"{}\"", index, name),
+        )
+    }
+
+    /// Tells why this test case needs a full date and time, if it doesn't have one: a schedule depending on
+    /// weekdays or sunrise/sunset may apply to it. "HH:MM" times are taken on the day of `now`
+    pub fn missing_date(
+        &self,
+        config: &Config,
+        now: &chrono::DateTime<chrono::FixedOffset>,
+    ) -> Option<String> {
+        let has_date = self.time.as_deref().map_or(false, |time| {
+            chrono::DateTime::parse_from_rfc3339(time).is_ok()
+        });
+        if has_date || config.is_ignored(&self.exe, &self.class) {
+            return None;
+        }
+
+        let location = config.location();
+        let rules = crate::rules::effective_rules(config);
+        let depends_on_date = self.in_context(now, |context| {
+            let rule_may_apply = rules.iter().any(|rule| {
+                rule.when.depends_on_date() && rule.when.may_match_any_day(context, location)
+            });
+            // Desktop schedules apply when no rule matches, which rules that don't depend on the date settle
+            let desktop_may_apply = config
+                .desktop_schedules()
+                .iter()
+                .any(|schedule| schedule.when.depends_on_date())
+                && !rules.iter().any(|rule| {
+                    !rule.when.depends_on_date() && rule.when.check(context, location).is_ok()
+                });
+            rule_may_apply || desktop_may_apply
+        });
+        // Invalid times are reported on their own
+        if !depends_on_date.unwrap_or(false) {
+            return None;
+        }
+
+        Some(
+            "a schedule depending on weekdays or sunrise/sunset may apply, `time` needs a full date and time \
+             (i.e. \"2026-10-19T23:30:00+02:00\") for results to be stable"
+                .into(),
+        )
+    }
+
+    /// Evaluates the rules of `config` for this test case, "HH:MM" times being taken on the day of `now`
+    pub fn run(
+        &self,
+        config: &Config,
+        now: &chrono::DateTime<chrono::FixedOffset>,
+    ) -> Result<Decision, String> {
+        if let Some(error) = self.missing_date(config, now) {
+            return Err(error);
+        }
+        self.in_context(now, |context| crate::rules::decide(config, context))
+    }
+
+    /// Calls `f` with the foreground context and system state this test case describes
+    fn in_context<T>(
+        &self,
+        now: &chrono::DateTime<chrono::FixedOffset>,
+        f: impl FnOnce(&RuleContext) -> T,
+    ) -> Result<T, String> {
+        let now = match &self.time {
+            Some(time) => crate::schedule::parse_time(time, now)?,
+            None => *now,
+        };
+        let process_path = std::path::PathBuf::from(self.path.as_deref().unwrap_or(&self.exe));
+        let is_running = |exe: &str| {
+            self.running
+                .iter()
+                .any(|running| running.eq_ignore_ascii_case(exe))
+        };
+        let context = RuleContext {
+            process_exe: &self.exe,
+            process_path: &process_path,
+            window_title: &self.title,
+            window_class: &self.class,
+            window_state: self.window_state.unwrap_or(WindowState::Windowed),
+            monitor: &self.display,
+            now,
+            power: PowerStatus {
+                source: self.power.unwrap_or(PowerSource::Ac),
+                battery_percent: self.battery,
+            },
+            is_running: &is_running,
+        };
+
+        Ok(f(&context))
+    }
+
+    fn compare(&self, decision: &Decision) -> Vec<Mismatch> {
+        let mut mismatches = vec![];
+        let mut check = |field, expected: String, actual: String| {
+            if expected != actual {
+                mismatches.push(Mismatch {
+                    field,
+                    expected,
+                    actual,
+                });
+            }
+        };
+
+        let expect = &self.expect;
+        let evaluation: Option<&Evaluation> = match decision {
+            Decision::Ignored => None,
+            Decision::Evaluated(evaluation) => Some(evaluation),
+        };
+        if let Some(ignored) = expect.ignored {
+            check(
+                "ignored",
+                ignored.to_string(),
+                evaluation.is_none().to_string(),
+            );
+        }
+
+        // Other expectations describe an evaluation
+        let evaluation = match evaluation {
+            Some(evaluation) => evaluation,
+            None => {
+                if expect.ignored.is_none() {
+                    check("ignored", false.to_string(), true.to_string());
+                }
+                return mismatches;
+            }
+        };
+
+        let winner = evaluation
+            .winning_rule()
+            .map(|outcome| outcome.rule.name.as_deref());
+        if let Some(vibrance) = expect.vibrance {
+            check(
+                "vibrance",
+                vibrance.to_string(),
+                evaluation.vibrance.to_string(),
+            );
+        }
+        if let Some(rule) = &expect.rule {
+            check(
+                "rule",
+                rule_name(Some(rule.as_str())),
+                winner.map_or_else(|| "desktop settings".into(), rule_name),
+            );
+        }
+        if let Some(desktop) = expect.desktop {
+            check("desktop", desktop.to_string(), winner.is_none().to_string());
+        }
+        if let Some(resolution) = &expect.resolution {
+            check(
+                "resolution",
+                resolution.to_string(),
+                evaluation
+                    .resolution
+                    .as_ref()
+                    .map_or_else(|| "unchanged".into(), ToString::to_string),
+            );
+        }
+        if let Some(sticky) = expect.sticky {
+            check("sticky", sticky.to_string(), evaluation.sticky.to_string());
+        }
+
+        mismatches
+    }
+}
+
+/// Runs every test case of the configuration
+pub fn run(config: &Config, now: &chrono::DateTime<chrono::FixedOffset>) -> Vec<TestOutcome> {
+    config
+        .tests()
+        .iter()
+        .enumerate()
+        .map(|(index, test)| {
+            let name = test.label(index);
+            match test.run(config, now) {
+                Ok(decision) => TestOutcome {
+                    name,
+                    mismatches: test.compare(&decision),
+                    error: None,
+                    decision: Some(decision),
+                },
+                Err(error) => TestOutcome {
+                    name,
+                    mismatches: vec![],
+                    error: Some(error),
+                    decision: None,
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+desktop_vibrance = 50
+
+[[rules]]
+name = "weekend"
+vibrance = 70
+when = { exe = "game.exe", schedule = { weekdays = ["sat", "sun"] } }
+"#;
+
+    fn test_case(source: &str) -> TestCase {
+        toml::from_str(source).unwrap()
+    }
+
+    /// Monday
+    fn now() -> chrono::DateTime<chrono::FixedOffset> {
+        chrono::DateTime::parse_from_rfc3339("2021-06-21T12:00:00+02:00").unwrap()
+    }
+
+    #[test]
+    fn full_date() {
+        let config = Config::fixture(CONFIG);
+        let saturday = test_case(
+            "exe = \"game.exe\"\ntime = \"2021-06-26T23:30:00+02:00\"\nexpect = { rule = \"weekend\" }",
+        );
+        let decision = saturday.run(&config, &now()).unwrap();
+        assert!(saturday.compare(&decision).is_empty());
+    }
+
+    #[test]
+    fn weekdays_require_a_full_date() {
+        let config = Config::fixture(CONFIG);
+        for source in &["exe = \"game.exe\"\ntime = \"23:30\"", "exe = \"game.exe\""] {
+            let test = test_case(&format!("{}\nexpect = {{ rule = \"weekend\" }}", source));
+            assert!(test.missing_date(&config, &now()).is_some());
+            assert!(test.run(&config, &now()).unwrap_err().contains("full date"));
+        }
+    }
+
+    #[test]
+    fn date_only_required_where_schedules_may_apply() {
+        let config = Config::fixture(CONFIG);
+        // The weekend rule requires game.exe, its schedule never applies to other programs
+        let editor =
+            test_case("exe = \"editor.exe\"\ntime = \"23:30\"\nexpect = { desktop = true }");
+        assert!(editor.missing_date(&config, &now()).is_none());
+        let decision = editor.run(&config, &now()).unwrap();
+        assert!(editor.compare(&decision).is_empty());
+
+        // Desktop schedules apply whenever no rule matches
+        let config = Config::fixture(&format!(
+            "{}\n[[desktop_schedules]]\nvibrance = 40\nwhen = {{ weekdays = [\"mon\"] }}\n\n[[rules]]\nname = \"editor\"\nvibrance = 60\nwhen = {{ exe = \"editor.exe\" }}",
+            CONFIG
+        ));
+        let browser = test_case("exe = \"browser.exe\"\nexpect = { desktop = true }");
+        assert!(browser.missing_date(&config, &now()).is_some());
+        // Unless a rule that doesn't depend on the date matches
+        assert!(editor.missing_date(&config, &now()).is_none());
+
+        // Ignored windows keep current settings, whatever the date
+        let overlay = test_case("exe = \"Overlay.exe\"\nexpect = { ignored = true }");
+        let config = Config::fixture(&format!(
+            "{}\n[ignore]\nprograms = [\"overlay.exe\"]\n\n[[desktop_schedules]]\nvibrance = 40\nwhen = {{ weekdays = [\"mon\"] }}",
+            CONFIG
+        ));
+        assert!(overlay.missing_date(&config, &now()).is_none());
+    }
+
+    #[test]
+    fn time_of_day() {
+        // Without weekdays nor sunrise/sunset, the day doesn't matter
+        let config = Config::fixture(
+            "desktop_vibrance = 50\n\n[[desktop_schedules]]\nvibrance = 40\nwhen = { from = \"22:00\", to = \"06:00\" }",
+        );
+        let night = test_case("time = \"23:30\"\nexpect = { vibrance = 40 }");
+        assert!(night.missing_date(&config, &now()).is_none());
+        let decision = night.run(&config, &now()).unwrap();
+        assert!(night.compare(&decision).is_empty());
+    }
+}
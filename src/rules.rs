@@ -0,0 +1,839 @@
+//! Rule engine: decides which settings apply for a given foreground window and system state.
+//!
+//! Rules carry a boolean condition tree and a priority. The rule with the highest priority whose
+//! condition matches wins, ties being broken by declaration order. Legacy `program_settings` entries
+//! are translated into rules declared after the explicit ones.
+//! Nothing in here touches the OS: every fact is provided through a [`RuleContext`].
+
+use crate::config::{Config, Program, Scope, VideoMode, WindowState};
+use crate::power::{PowerCondition, PowerStatus};
+use crate::schedule::{Coordinates, Schedule};
+
+/// Facts about the foreground window and the system that rules are evaluated against
+pub struct RuleContext<'a> {
+    pub process_exe: &'a str,
+    pub process_path: &'a std::path::Path,
+    pub window_title: &'a str,
+    pub window_class: &'a str,
+    pub window_state: WindowState,
+    /// Device name of the monitor the window is displayed on
+    pub monitor: &'a str,
+    /// Current local time
+    pub now: chrono::DateTime<chrono::FixedOffset>,
+    /// Current power status
+    pub power: PowerStatus,
+    /// Tells if a program is running
+    pub is_running: &'a dyn Fn(&str) -> bool,
+}
+
+/// Boolean condition tree
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "ConditionTable", into = "ConditionTable")]
+pub enum Condition {
+    /// All of the conditions match
+    All(Vec<Condition>),
+    /// At least one of the conditions matches
+    Any(Vec<Condition>),
+    /// The condition doesn't match
+    Not(Box<Condition>),
+    /// The foreground program has this name (case insensitive)
+    Exe(String),
+    /// The foreground program's path starts with this path (case insensitive)
+    Path(String),
+    /// The foreground window title contains this text (case insensitive)
+    Title(String),
+    /// The foreground window is in one of these states
+    WindowState(Vec<WindowState>),
+    /// The schedule is active
+    Schedule(Schedule),
+    /// The power requirements are met
+    Power(PowerCondition),
+    /// A program with this name is running
+    Running(String),
+    /// The foreground window is displayed on this monitor (i.e. `\\.\DISPLAY1`)
+    Display(String),
+}
+
+/// Representation of conditions in configuration files: a table with one key per condition,
+/// several keys meaning all of them have to match (i.e. `{ exe = "game.exe", running = "obs64.exe" }`).
+/// Plain values come first as TOML requires them to be emitted before tables
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConditionTable {
+    /// The foreground program has this name (case insensitive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exe: Option<String>,
+    /// The foreground program's path starts with this path (case insensitive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    /// The foreground window title contains this text (case insensitive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// The foreground window is in one of these states
+    #[serde(skip_serializing_if = "Option::is_none")]
+    window_state: Option<Vec<WindowState>>,
+    /// A program with this name is running
+    #[serde(skip_serializing_if = "Option::is_none")]
+    running: Option<String>,
+    /// The foreground window is displayed on this monitor (i.e. `\\.\DISPLAY1`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display: Option<String>,
+    /// The schedule is active
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schedule: Option<Schedule>,
+    /// The power requirements are met
+    #[serde(skip_serializing_if = "Option::is_none")]
+    power: Option<PowerCondition>,
+    /// The condition doesn't match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    not: Option<Box<Condition>>,
+    /// All of the conditions match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    all: Option<Vec<Condition>>,
+    /// At least one of the conditions matches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    any: Option<Vec<Condition>>,
+}
+
+/// Conditions are written as tables in configuration files
+impl schemars::JsonSchema for Condition {
+    fn schema_name() -> String {
+        "Condition".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        ConditionTable::json_schema(gen)
+    }
+}
+
+impl std::convert::TryFrom<ConditionTable> for Condition {
+    type Error = String;
+
+    fn try_from(table: ConditionTable) -> Result<Self, Self::Error> {
+        let mut conditions: Vec<Condition> = vec![];
+        conditions.extend(table.all.map(Self::All));
+        conditions.extend(table.any.map(Self::Any));
+        conditions.extend(table.not.map(Self::Not));
+        conditions.extend(table.exe.map(Self::Exe));
+        conditions.extend(table.path.map(Self::Path));
+        conditions.extend(table.title.map(Self::Title));
+        conditions.extend(table.window_state.map(Self::WindowState));
+        conditions.extend(table.schedule.map(Self::Schedule));
+        conditions.extend(table.power.map(Self::Power));
+        conditions.extend(table.running.map(Self::Running));
+        conditions.extend(table.display.map(Self::Display));
+
+        match conditions.len() {
+            0 => Err("empty condition, expected at least one of: all, any, not, exe, path, title, window_state, schedule, power, running, display".into()),
+            1 => Ok(conditions.remove(0)),
+            _ => Ok(Self::All(conditions)),
+        }
+    }
+}
+
+impl From<Condition> for ConditionTable {
+    fn from(condition: Condition) -> Self {
+        let mut table = Self::default();
+        match condition {
+            Condition::All(conditions) => table.all = Some(conditions),
+            Condition::Any(conditions) => table.any = Some(conditions),
+            Condition::Not(condition) => table.not = Some(condition),
+            Condition::Exe(exe) => table.exe = Some(exe),
+            Condition::Path(path) => table.path = Some(path),
+            Condition::Title(title) => table.title = Some(title),
+            Condition::WindowState(states) => table.window_state = Some(states),
+            Condition::Schedule(schedule) => table.schedule = Some(schedule),
+            Condition::Power(power) => table.power = Some(power),
+            Condition::Running(exe) => table.running = Some(exe),
+            Condition::Display(display) => table.display = Some(display),
+        }
+        table
+    }
+}
+
+/// Outcome of a condition: why it matched, or why it didn't
+pub type ConditionResult = Result<String, String>;
+
+impl Condition {
+    /// Tells if this condition depends on the foreground or running state of this program
+    pub fn mentions_process(&self, program_exe: &str) -> bool {
+        match self {
+            Self::All(conditions) | Self::Any(conditions) => conditions
+                .iter()
+                .any(|condition| condition.mentions_process(program_exe)),
+            Self::Not(condition) => condition.mentions_process(program_exe),
+            Self::Exe(exe) | Self::Running(exe) => exe.eq_ignore_ascii_case(program_exe),
+            _ => false,
+        }
+    }
+
+    /// Every schedule this condition depends on
+    pub fn schedules(&self) -> Vec<&Schedule> {
+        match self {
+            Self::All(conditions) | Self::Any(conditions) => conditions
+                .iter()
+                .flat_map(|condition| condition.schedules())
+                .collect(),
+            Self::Not(condition) => condition.schedules(),
+            Self::Schedule(schedule) => vec![schedule],
+            _ => vec![],
+        }
+    }
+
+    /// Every power condition this condition depends on
+    pub fn power_conditions(&self) -> Vec<&PowerCondition> {
+        match self {
+            Self::All(conditions) | Self::Any(conditions) => conditions
+                .iter()
+                .flat_map(|condition| condition.power_conditions())
+                .collect(),
+            Self::Not(condition) => condition.power_conditions(),
+            Self::Power(power) => vec![power],
+            _ => vec![],
+        }
+    }
+
+    /// Tells if this condition depends on the date, through schedules with weekdays or sunrise/sunset
+    pub fn depends_on_date(&self) -> bool {
+        self.schedules()
+            .iter()
+            .any(|schedule| schedule.depends_on_date())
+    }
+
+    /// Tells if this condition may match in this context on some day: the parts depending on the date are
+    /// assumed to match either way, the others are checked
+    pub fn may_match_any_day(&self, context: &RuleContext, location: Option<&Coordinates>) -> bool {
+        match self {
+            Self::All(conditions) => conditions
+                .iter()
+                .all(|condition| condition.may_match_any_day(context, location)),
+            Self::Any(conditions) => conditions
+                .iter()
+                .any(|condition| condition.may_match_any_day(context, location)),
+            Self::Not(condition) if condition.depends_on_date() => true,
+            Self::Schedule(schedule) if schedule.depends_on_date() => true,
+            condition => condition.check(context, location).is_ok(),
+        }
+    }
+
+    pub fn check(&self, context: &RuleContext, location: Option<&Coordinates>) -> ConditionResult {
+        match self {
+            Self::All(conditions) => {
+                let mut reasons = vec![];
+                for condition in conditions {
+                    reasons.push(condition.check(context, location)?);
+                }
+                Ok(reasons.join(" and "))
+            }
+            Self::Any(conditions) => {
+                let mut reasons = vec![];
+                for condition in conditions {
+                    match condition.check(context, location) {
+                        Ok(reason) => return Ok(reason),
+                        Err(reason) => reasons.push(reason),
+                    }
+                }
+                Err(format!("none of: {}", reasons.join(", ")))
+            }
+            Self::Not(condition) => match condition.check(context, location) {
+                Ok(reason) => Err(format!("{}, which is excluded", reason)),
+                Err(reason) => Ok(reason),
+            },
+            Self::Exe(exe) => {
+                if exe.eq_ignore_ascii_case(context.process_exe) {
+                    Ok(format!("exe is {}", exe))
+                } else {
+                    Err(format!("exe is {}, not {}", context.process_exe, exe))
+                }
+            }
+            Self::Path(prefix) => {
+                let path = context.process_path.to_string_lossy().to_lowercase();
+                if path.starts_with(&prefix.to_lowercase()) {
+                    Ok(format!("path is in {}", prefix))
+                } else {
+                    Err(format!("path {} is not in {}", path, prefix))
+                }
+            }
+            Self::Title(text) => {
+                if context
+                    .window_title
+                    .to_lowercase()
+                    .contains(&text.to_lowercase())
+                {
+                    Ok(format!("title contains \"{}\"", text))
+                } else {
+                    Err(format!(
+                        "title \"{}\" doesn't contain \"{}\"",
+                        context.window_title, text
+                    ))
+                }
+            }
+            Self::WindowState(states) => {
+                if states.contains(&context.window_state) {
+                    Ok(format!("window is {:?}", context.window_state))
+                } else {
+                    Err(format!(
+                        "window is {:?}, not one of {:?}",
+                        context.window_state, states
+                    ))
+                }
+            }
+            Self::Schedule(schedule) => {
+                if schedule.is_active(&context.now, location) {
+                    Ok(format!("schedule is active at {}", context.now))
+                } else {
+                    Err(format!("schedule is inactive at {}", context.now))
+                }
+            }
+            Self::Power(power) => {
+                if power.is_met(&context.power) {
+                    Ok(format!("power {:?} meets requirements", context.power))
+                } else {
+                    Err(format!(
+                        "power {:?} doesn't meet {:?}",
+                        context.power, power
+                    ))
+                }
+            }
+            Self::Running(exe) => {
+                if (context.is_running)(exe) {
+                    Ok(format!("{} is running", exe))
+                } else {
+                    Err(format!("{} isn't running", exe))
+                }
+            }
+            Self::Display(display) => {
+                if display.eq_ignore_ascii_case(context.monitor) {
+                    Ok(format!("display is {}", display))
+                } else {
+                    Err(format!("display is {}, not {}", context.monitor, display))
+                }
+            }
+        }
+    }
+}
+
+/// Settings to apply when a condition matches
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Rule {
+    /// Name of the rule, used in diagnostics
+    pub name: Option<String>,
+    /// Rules with a higher priority win, declaration order breaks ties. Defaults to 0
+    #[serde(default)]
+    pub priority: i32,
+    /// Vibrance value in percentage to apply when this rule matches
+    #[schemars(range(max = 100))]
+    pub vibrance: u8,
+    /// Keep settings on the window's monitor as long as it stays visible there
+    pub sticky: Option<bool>,
+    /// Condition for this rule to apply
+    pub when: Condition,
+    /// Video mode to apply when this rule matches
+    pub resolution: Option<VideoMode>,
+}
+
+impl Rule {
+    /// Translates a legacy `program_settings` entry into rules, most specific first
+    pub fn from_program(program: &Program) -> Vec<Self> {
+        let running_scope = program.scope.unwrap_or_default() == Scope::Running;
+        let mut conditions = vec![if running_scope {
+            Condition::Running(program.exe_name.clone())
+        } else {
+            Condition::Exe(program.exe_name.clone())
+        }];
+        if let Some(schedule) = &program.schedule {
+            conditions.push(Condition::Schedule(schedule.clone()));
+        }
+        if let Some(power) = &program.power {
+            conditions.push(Condition::Power(power.clone()));
+        }
+        if let Some(processes) = &program.processes {
+            conditions.extend(
+                processes
+                    .running
+                    .iter()
+                    .map(|exe| Condition::Running(exe.clone())),
+            );
+            conditions.extend(
+                processes
+                    .not_running
+                    .iter()
+                    .map(|exe| Condition::Not(Box::new(Condition::Running(exe.clone())))),
+            );
+        }
+
+        let rule = |vibrance, state_conditions: Vec<Condition>| {
+            let mut conditions = conditions.clone();
+            conditions.extend(state_conditions);
+            Self {
+                name: Some(program.exe_name.clone()),
+                priority: program.priority.unwrap_or_default(),
+                vibrance,
+                sticky: program.sticky,
+                when: Condition::All(conditions),
+                resolution: program.resolution.clone(),
+            }
+        };
+
+        // Window states only make sense while the program is focused
+        let state_conditions = |states: Vec<WindowState>| {
+            let mut conditions = vec![];
+            if running_scope {
+                conditions.push(Condition::Exe(program.exe_name.clone()));
+            }
+            conditions.push(Condition::WindowState(states));
+            conditions
+        };
+
+        let mut rules = vec![];
+        if let Some(window_states) = &program.window_states {
+            for &state in &[
+                WindowState::Fullscreen,
+                WindowState::Borderless,
+                WindowState::Windowed,
+            ] {
+                if let Some(vibrance) = window_states.get(state) {
+                    rules.push(rule(vibrance, state_conditions(vec![state])));
+                }
+            }
+        }
+
+        // Running-scoped programs apply as long as they run, whatever the foreground window is
+        if program.fullscreen_only.unwrap_or_default() && !running_scope {
+            rules.push(rule(
+                program.vibrance,
+                state_conditions(vec![WindowState::Fullscreen, WindowState::Borderless]),
+            ));
+        } else {
+            rules.push(rule(program.vibrance, vec![]));
+        }
+
+        rules
+    }
+}
+
+/// Evaluation of a single rule
+#[derive(Debug, Clone)]
+pub struct RuleOutcome {
+    pub rule: Rule,
+    /// Why the rule matched or not
+    pub result: ConditionResult,
+}
+
+/// Result of the evaluation of every rule
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    /// Every rule considered, in declaration order
+    pub outcomes: Vec<RuleOutcome>,
+    /// Index of the winning rule in `outcomes`, if any
+    pub winner: Option<usize>,
+    /// Vibrance to apply
+    pub vibrance: u8,
+    /// Video mode to apply, if any
+    pub resolution: Option<VideoMode>,
+    /// Whether settings should stick to the window's monitor
+    pub sticky: bool,
+}
+
+impl Evaluation {
+    pub fn winning_rule(&self) -> Option<&RuleOutcome> {
+        self.winner.map(|index| &self.outcomes[index])
+    }
+}
+
+/// Every rule of the configuration in declaration order: explicit rules, then program settings
+/// (focused ones before running-scoped ones, as they used to be looked up in that order)
+pub fn effective_rules(config: &Config) -> Vec<Rule> {
+    let programs = config.program_settings();
+    let (running, focused): (Vec<&Program>, Vec<&Program>) = programs
+        .iter()
+        .partition(|program| program.scope.unwrap_or_default() == Scope::Running);
+
+    config
+        .rules()
+        .iter()
+        .cloned()
+        .chain(focused.into_iter().flat_map(Rule::from_program))
+        .chain(running.into_iter().flat_map(Rule::from_program))
+        .collect()
+}
+
+/// Tells if rules depend on this program running or being focused
+pub fn is_watched_process(config: &Config, program_exe: &str) -> bool {
+    effective_rules(config)
+        .iter()
+        .any(|rule| rule.when.mentions_process(program_exe))
+}
+
+/// What to do for a window
+#[derive(Debug)]
+pub enum Decision {
+    /// The window is in the ignore list, current settings are kept
+    Ignored,
+    /// Rules have been evaluated
+    Evaluated(Evaluation),
+}
+
+/// Checks the ignore list, then evaluates rules
+pub fn decide(config: &Config, context: &RuleContext) -> Decision {
+    if config.is_ignored(context.process_exe, context.window_class) {
+        return Decision::Ignored;
+    }
+
+    Decision::Evaluated(evaluate(config, context))
+}
+
+/// Evaluates every rule of the configuration and picks the winner.
+/// Without any matching rule, the desktop settings apply
+pub fn evaluate(config: &Config, context: &RuleContext) -> Evaluation {
+    let outcomes: Vec<RuleOutcome> = effective_rules(config)
+        .into_iter()
+        .map(|rule| {
+            let result = rule.when.check(context, config.location());
+            log::trace!("rule {:?} -> {:?}", rule.name, result);
+            RuleOutcome { rule, result }
+        })
+        .collect();
+
+    let mut winner: Option<usize> = None;
+    for (index, outcome) in outcomes.iter().enumerate() {
+        if outcome.result.is_ok()
+            && winner.map_or(true, |winner| {
+                outcome.rule.priority > outcomes[winner].rule.priority
+            })
+        {
+            winner = Some(index);
+        }
+    }
+
+    match winner.map(|index| &outcomes[index].rule) {
+        Some(rule) => Evaluation {
+            vibrance: rule.vibrance,
+            resolution: rule.resolution.clone(),
+            sticky: rule.sticky.unwrap_or_default(),
+            winner,
+            outcomes,
+        },
+        None => Evaluation {
+            vibrance: config.desktop_vibrance_at(&context.now),
+            resolution: config.desktop_resolution().cloned(),
+            sticky: false,
+            winner,
+            outcomes,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Foreground window and system state rules are evaluated against, as a test case changes them
+    struct Facts {
+        exe: &'static str,
+        path: &'static str,
+        title: &'static str,
+        window_state: WindowState,
+        monitor: &'static str,
+        time: &'static str,
+        power: PowerStatus,
+        running: Vec<&'static str>,
+    }
+
+    impl Default for Facts {
+        fn default() -> Self {
+            Self {
+                exe: "explorer.exe",
+                path: "C:\\Windows\\explorer.exe",
+                title: "",
+                window_state: WindowState::Windowed,
+                monitor: "\\\\.\\DISPLAY1",
+                time: "2021-06-21T12:00:00+02:00",
+                power: PowerStatus::default(),
+                running: vec![],
+            }
+        }
+    }
+
+    impl Facts {
+        fn evaluate(&self, config: &Config) -> Evaluation {
+            match self.decide(config) {
+                Decision::Evaluated(evaluation) => evaluation,
+                Decision::Ignored => panic!("{} is ignored", self.exe),
+            }
+        }
+
+        fn decide(&self, config: &Config) -> Decision {
+            let path = std::path::PathBuf::from(self.path);
+            let is_running = |exe: &str| {
+                self.running
+                    .iter()
+                    .any(|running| running.eq_ignore_ascii_case(exe))
+            };
+            let context = RuleContext {
+                process_exe: self.exe,
+                process_path: &path,
+                window_title: self.title,
+                window_class: "",
+                window_state: self.window_state,
+                monitor: self.monitor,
+                now: chrono::DateTime::parse_from_rfc3339(self.time).unwrap(),
+                power: self.power,
+                is_running: &is_running,
+            };
+            decide(config, &context)
+        }
+    }
+
+    /// Name of the winning rule
+    fn winner(evaluation: &Evaluation) -> Option<&str> {
+        evaluation
+            .winning_rule()
+            .map(|outcome| outcome.rule.name.as_deref().unwrap_or_default())
+    }
+
+    #[test]
+    fn nested_conditions() {
+        let config = Config::fixture(include_str!("../tests/fixtures/rules/conditions.toml"));
+        let game = |window_state, title, running| Facts {
+            exe: "game.exe",
+            path: "D:\\Games\\Game\\game.exe",
+            title,
+            window_state,
+            running,
+            ..Default::default()
+        };
+
+        let evaluation = game(WindowState::Fullscreen, "Game", vec![]).evaluate(&config);
+        assert_eq!(winner(&evaluation), Some("game"));
+        assert_eq!(evaluation.vibrance, 80);
+        // `any` matches with its second condition
+        let evaluation = game(WindowState::Windowed, "Game - Replay", vec![]).evaluate(&config);
+        assert_eq!(winner(&evaluation), Some("game"));
+        // Neither condition of `any` matches
+        let evaluation = game(WindowState::Windowed, "Game", vec![]).evaluate(&config);
+        assert_eq!(winner(&evaluation), None);
+        assert!(evaluation.outcomes[0]
+            .result
+            .as_ref()
+            .unwrap_err()
+            .starts_with("none of: "));
+        // `not` excludes recording sessions
+        let evaluation = game(WindowState::Borderless, "Game", vec!["OBS64.exe"]).evaluate(&config);
+        assert_eq!(winner(&evaluation), None);
+        assert!(evaluation.outcomes[0]
+            .result
+            .as_ref()
+            .unwrap_err()
+            .ends_with("which is excluded"));
+    }
+
+    #[test]
+    fn multiple_keys_are_all_required() {
+        let config = Config::fixture(include_str!("../tests/fixtures/rules/conditions.toml"));
+        assert_eq!(
+            config.rules()[1].when,
+            Condition::All(vec![
+                Condition::Path("C:\\Program Files\\Editor\\".into()),
+                Condition::Display("\\\\.\\DISPLAY2".into()),
+            ])
+        );
+
+        let editor = |monitor| Facts {
+            exe: "editor.exe",
+            path: "c:\\program files\\editor\\editor.exe",
+            monitor,
+            ..Default::default()
+        };
+        let evaluation = editor("\\\\.\\DISPLAY2").evaluate(&config);
+        assert_eq!(winner(&evaluation), Some("editor on the second display"));
+        let evaluation = editor("\\\\.\\DISPLAY1").evaluate(&config);
+        assert_eq!(winner(&evaluation), None);
+    }
+
+    #[test]
+    fn ignored_windows() {
+        let config = Config::fixture(include_str!("../tests/fixtures/rules/conditions.toml"));
+        let overlay = Facts {
+            exe: "overlay.exe",
+            ..Default::default()
+        };
+        assert!(matches!(overlay.decide(&config), Decision::Ignored));
+        // The built-in list still applies
+        let game_bar = Facts {
+            exe: "GameBar.exe",
+            ..Default::default()
+        };
+        assert!(matches!(game_bar.decide(&config), Decision::Ignored));
+    }
+
+    #[test]
+    fn priorities() {
+        let config = Config::fixture(include_str!("../tests/fixtures/rules/priorities.toml"));
+        let game = |time| Facts {
+            exe: "game.exe",
+            window_state: WindowState::Fullscreen,
+            time,
+            ..Default::default()
+        };
+
+        // Higher priority wins over declaration order, ties go to the first declared rule
+        let evaluation = game("2021-06-21T12:00:00+02:00").evaluate(&config);
+        assert_eq!(winner(&evaluation), Some("game"));
+        assert_eq!(evaluation.vibrance, 80);
+        assert!(!evaluation.sticky);
+        assert!(evaluation
+            .outcomes
+            .iter()
+            .take(3)
+            .all(|outcome| outcome.result.is_ok()));
+
+        let evaluation = game("2021-06-21T23:00:00+02:00").evaluate(&config);
+        assert_eq!(winner(&evaluation), Some("game at night"));
+        assert_eq!(evaluation.vibrance, 70);
+        assert!(evaluation.sticky);
+
+        let other = Facts {
+            exe: "video.exe",
+            window_state: WindowState::Fullscreen,
+            ..Default::default()
+        };
+        assert_eq!(
+            winner(&other.evaluate(&config)),
+            Some("any fullscreen window")
+        );
+    }
+
+    #[test]
+    fn desktop_fallback() {
+        let config = Config::fixture(include_str!(
+            "../tests/fixtures/rules/program_settings.toml"
+        ));
+        let desktop = |time| Facts {
+            time,
+            ..Default::default()
+        };
+
+        let evaluation = desktop("2021-06-21T12:00:00+02:00").evaluate(&config);
+        assert_eq!(evaluation.winner, None);
+        assert!(evaluation
+            .outcomes
+            .iter()
+            .all(|outcome| outcome.result.is_err()));
+        assert_eq!(evaluation.vibrance, 50);
+        assert_eq!(
+            evaluation
+                .resolution
+                .map(|mode| mode.to_string())
+                .as_deref(),
+            Some("1920x1080@60Hz")
+        );
+        assert!(!evaluation.sticky);
+        // Desktop schedules apply too
+        assert_eq!(
+            desktop("2021-06-21T23:00:00+02:00")
+                .evaluate(&config)
+                .vibrance,
+            40
+        );
+    }
+
+    #[test]
+    fn program_settings_translation() {
+        let config = Config::fixture(include_str!(
+            "../tests/fixtures/rules/program_settings.toml"
+        ));
+        let rules = effective_rules(&config);
+        let names: Vec<&str> = rules
+            .iter()
+            .map(|rule| rule.name.as_deref().unwrap_or_default())
+            .collect();
+        // Explicit rules first, then focused programs, window states first, and running-scoped ones last
+        assert_eq!(
+            names,
+            [
+                "explicit",
+                "game.exe",
+                "game.exe",
+                "game.exe",
+                "laptop_game.exe",
+                "obs64.exe"
+            ]
+        );
+
+        let state = |states: &[WindowState]| {
+            Condition::All(vec![
+                Condition::Exe("game.exe".into()),
+                Condition::WindowState(states.to_vec()),
+            ])
+        };
+        assert_eq!(rules[1].when, state(&[WindowState::Borderless]));
+        assert_eq!(rules[1].vibrance, 65);
+        assert_eq!(rules[2].when, state(&[WindowState::Windowed]));
+        assert_eq!(rules[2].vibrance, 52);
+        // `fullscreen_only` becomes a window state condition
+        assert_eq!(
+            rules[3].when,
+            state(&[WindowState::Fullscreen, WindowState::Borderless])
+        );
+        assert_eq!(rules[3].vibrance, 80);
+        assert_eq!(
+            rules[3]
+                .resolution
+                .as_ref()
+                .map(ToString::to_string)
+                .as_deref(),
+            Some("1280x720@144Hz")
+        );
+
+        assert_eq!(
+            rules[4].when,
+            Condition::All(vec![
+                Condition::Exe("laptop_game.exe".into()),
+                Condition::Power(PowerCondition {
+                    source: Some(crate::power::PowerSource::Ac),
+                    ..Default::default()
+                }),
+                Condition::Running("discord.exe".into()),
+                Condition::Not(Box::new(Condition::Running("steam.exe".into()))),
+            ])
+        );
+        // Running-scoped programs apply whatever the foreground window is
+        assert_eq!(
+            rules[5].when,
+            Condition::All(vec![Condition::Running("obs64.exe".into())])
+        );
+        assert!(is_watched_process(&config, "OBS64.exe"));
+        assert!(is_watched_process(&config, "steam.exe"));
+        assert!(!is_watched_process(&config, "explorer.exe"));
+    }
+
+    #[test]
+    fn program_settings_evaluation() {
+        let config = Config::fixture(include_str!(
+            "../tests/fixtures/rules/program_settings.toml"
+        ));
+        let game = |window_state| Facts {
+            exe: "game.exe",
+            window_state,
+            ..Default::default()
+        };
+        assert_eq!(game(WindowState::Fullscreen).evaluate(&config).vibrance, 80);
+        assert_eq!(game(WindowState::Borderless).evaluate(&config).vibrance, 65);
+        assert_eq!(game(WindowState::Windowed).evaluate(&config).vibrance, 52);
+
+        let running_obs = Facts {
+            running: vec!["obs64.exe"],
+            ..Default::default()
+        };
+        assert_eq!(winner(&running_obs.evaluate(&config)), Some("obs64.exe"));
+        // Focused programs win over running-scoped ones of the same priority
+        let game_with_obs = Facts {
+            exe: "game.exe",
+            window_state: WindowState::Fullscreen,
+            running: vec!["obs64.exe"],
+            ..Default::default()
+        };
+        assert_eq!(game_with_obs.evaluate(&config).vibrance, 80);
+    }
+}
@@ -0,0 +1,627 @@
+use chrono::{
+    DateTime, Datelike as _, Duration, FixedOffset, NaiveDate, NaiveTime, TimeZone as _,
+    Timelike as _, Weekday,
+};
+
+/// Source of the current local time, injectable so schedules can be evaluated at any point in time
+pub trait Clock {
+    fn now(&self) -> DateTime<FixedOffset>;
+}
+
+/// Clock reading the local system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        chrono::Local::now().into()
+    }
+}
+
+/// Geographic coordinates, used to compute sunrise and sunset times
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Coordinates {
+    /// Latitude in degrees, positive north
+    #[schemars(range(min = -90, max = 90))]
+    pub latitude: f64,
+    /// Longitude in degrees, positive east
+    #[schemars(range(min = -180, max = 180))]
+    pub longitude: f64,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SunEvent {
+    Sunrise,
+    Sunset,
+}
+
+/// Time of day: either a fixed "HH:MM" time or sunrise/sunset with an optional offset ("sunset-00:30")
+#[derive(Debug, Clone, Copy, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TimeSpec {
+    At(NaiveTime),
+    Sun {
+        event: SunEvent,
+        /// Offset in minutes
+        offset: i64,
+    },
+}
+
+impl std::str::FromStr for TimeSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let parse_time = |s: &str| {
+            NaiveTime::parse_from_str(s, "%H:%M")
+                .map_err(|e| format!("invalid time \"{}\", expected HH:MM: {}", s, e))
+        };
+
+        let (event, rest) = if let Some(rest) = s.strip_prefix("sunrise") {
+            (SunEvent::Sunrise, rest)
+        } else if let Some(rest) = s.strip_prefix("sunset") {
+            (SunEvent::Sunset, rest)
+        } else {
+            return parse_time(s).map(Self::At);
+        };
+
+        let offset = if rest.is_empty() {
+            0
+        } else {
+            let (sign, offset) = rest.split_at(1);
+            let offset = parse_time(offset)?;
+            let minutes = (offset.hour() * 60 + offset.minute()) as i64;
+            match sign {
+                "+" => minutes,
+                "-" => -minutes,
+                _ => {
+                    return Err(format!(
+                        "invalid sun offset \"{}\", expected +HH:MM or -HH:MM",
+                        rest
+                    ))
+                }
+            }
+        };
+
+        Ok(Self::Sun { event, offset })
+    }
+}
+
+impl std::convert::TryFrom<String> for TimeSpec {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<TimeSpec> for String {
+    fn from(spec: TimeSpec) -> Self {
+        match spec {
+            TimeSpec::At(time) => time.format("%H:%M").to_string(),
+            TimeSpec::Sun { event, offset } => {
+                let event = match event {
+                    SunEvent::Sunrise => "sunrise",
+                    SunEvent::Sunset => "sunset",
+                };
+                if offset == 0 {
+                    event.into()
+                } else {
+                    format!(
+                        "{}{}{:02}:{:02}",
+                        event,
+                        if offset < 0 { '-' } else { '+' },
+                        offset.abs() / 60,
+                        offset.abs() % 60
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// Schema of the string representation
+impl schemars::JsonSchema for TimeSpec {
+    fn schema_name() -> String {
+        "TimeSpec".into()
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use schemars::schema::{InstanceType, Metadata, SchemaObject, StringValidation};
+        SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "Time of day: either a fixed \"HH:MM\" time or sunrise/sunset with an optional offset (\"sunset-00:30\")".into(),
+                ),
+                examples: vec!["22:00".into(), "sunset-00:30".into()],
+                ..Default::default()
+            })),
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(r"^\s*(\d{1,2}:\d{2}|(sunrise|sunset)([+-]\d{1,2}:\d{2})?)\s*$".into()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+impl TimeSpec {
+    /// Resolves this spec to a time of day on the provided date.
+    /// Returns `None` when sun events are requested without coordinates or when the sun doesn't rise/set on that day
+    pub fn resolve(
+        &self,
+        date: NaiveDate,
+        utc_offset: &FixedOffset,
+        location: Option<&Coordinates>,
+    ) -> Option<NaiveTime> {
+        match *self {
+            Self::At(time) => Some(time),
+            Self::Sun { event, offset } => {
+                let time = sun_event_time(date, location?, utc_offset, event)?;
+                Some(time.overflowing_add_signed(Duration::minutes(offset)).0)
+            }
+        }
+    }
+}
+
+/// Parses either an RFC 3339 date and time, or "HH:MM" on the same day as `now`
+pub fn parse_time(
+    value: &str,
+    now: &DateTime<FixedOffset>,
+) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time);
+    }
+
+    let time = NaiveTime::parse_from_str(value, "%H:%M")
+        .map_err(|e| format!("expected HH:MM or an RFC 3339 date and time: {}", e))?;
+    now.offset()
+        .from_local_datetime(&now.naive_local().date().and_time(time))
+        .single()
+        .ok_or_else(|| format!("{} doesn't exist on {}", value, now.date().naive_local()))
+}
+
+/// Computes the local time of sunrise or sunset, using the NOAA general solar position equations
+pub fn sun_event_time(
+    date: NaiveDate,
+    location: &Coordinates,
+    utc_offset: &FixedOffset,
+    event: SunEvent,
+) -> Option<NaiveTime> {
+    use std::f64::consts::PI;
+    let gamma = 2.0 * PI / 365.0 * (date.ordinal0() as f64);
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * gamma.cos()
+            - 0.032077 * gamma.sin()
+            - 0.014615 * (2.0 * gamma).cos()
+            - 0.040849 * (2.0 * gamma).sin());
+    let declination = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin()
+        - 0.006758 * (2.0 * gamma).cos()
+        + 0.000907 * (2.0 * gamma).sin()
+        - 0.002697 * (3.0 * gamma).cos()
+        + 0.00148 * (3.0 * gamma).sin();
+
+    let latitude = location.latitude.to_radians();
+    let cos_hour_angle = 90.833f64.to_radians().cos() / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        // Polar day or night
+        return None;
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    let hour_angle = match event {
+        SunEvent::Sunrise => hour_angle,
+        SunEvent::Sunset => -hour_angle,
+    };
+    let utc_minutes = 720.0 - 4.0 * (location.longitude + hour_angle) - equation_of_time;
+    let local_minutes = utc_minutes + (utc_offset.local_minus_utc() as f64) / 60.0;
+    let local_seconds = (local_minutes * 60.0).round() as i64;
+    let local_seconds = local_seconds.rem_euclid(24 * 60 * 60) as u32;
+    NaiveTime::from_num_seconds_from_midnight_opt(local_seconds, 0)
+}
+
+/// Schema of weekday lists: chrono parses short and full names in any case, only lowercase ones are suggested
+fn weekdays_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+    use schemars::schema::{
+        ArrayValidation, InstanceType, SchemaObject, StringValidation, SubschemaValidation,
+    };
+    const NAMES: [&str; 7] = [
+        "monday",
+        "tuesday",
+        "wednesday",
+        "thursday",
+        "friday",
+        "saturday",
+        "sunday",
+    ];
+
+    let suggested = SchemaObject {
+        enum_values: Some(
+            NAMES
+                .iter()
+                .map(|name| name[..3].into())
+                .chain(NAMES.iter().map(|name| (*name).into()))
+                .collect(),
+        ),
+        ..Default::default()
+    };
+    // Case insensitive match, JSON schema patterns have no flags
+    let case_insensitive = |name: &str| -> String {
+        name.chars()
+            .map(|c| format!("[{}{}]", c.to_ascii_uppercase(), c))
+            .collect()
+    };
+    let pattern = NAMES
+        .iter()
+        .map(|name| {
+            format!(
+                "{}({})?",
+                case_insensitive(&name[..3]),
+                case_insensitive(&name[3..])
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("|");
+    let any_case = SchemaObject {
+        string: Some(Box::new(StringValidation {
+            pattern: Some(format!("^({})$", pattern)),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+
+    SchemaObject {
+        instance_type: Some(InstanceType::Array.into()),
+        array: Some(Box::new(ArrayValidation {
+            items: Some(
+                schemars::schema::Schema::Object(SchemaObject {
+                    instance_type: Some(InstanceType::String.into()),
+                    subschemas: Some(Box::new(SubschemaValidation {
+                        any_of: Some(vec![suggested.into(), any_case.into()]),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                })
+                .into(),
+            ),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Time ranges and weekdays during which settings are active
+#[derive(
+    Debug, Clone, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+pub struct Schedule {
+    /// Days of the week the schedule is active on ("mon", "tue"...), every day if unset
+    #[serde(default)]
+    #[schemars(schema_with = "weekdays_schema")]
+    pub weekdays: Option<Vec<Weekday>>,
+    /// Start of the active range, either "HH:MM", "sunrise" or "sunset" with an optional offset ("sunset-00:30")
+    pub from: Option<TimeSpec>,
+    /// End of the active range, same format as `from`. Ranges can wrap around midnight
+    pub to: Option<TimeSpec>,
+}
+
+impl Schedule {
+    /// Tells if this schedule depends on sunrise or sunset, which requires a location
+    pub fn uses_sun(&self) -> bool {
+        [&self.from, &self.to]
+            .iter()
+            .any(|spec| matches!(spec, Some(TimeSpec::Sun { .. })))
+    }
+
+    /// Tells if this schedule depends on the date, through weekdays or sunrise/sunset
+    pub fn depends_on_date(&self) -> bool {
+        self.weekdays.is_some() || self.uses_sun()
+    }
+
+    pub fn is_active(&self, now: &DateTime<FixedOffset>, location: Option<&Coordinates>) -> bool {
+        if let Some(weekdays) = &self.weekdays {
+            if !weekdays.contains(&now.weekday()) {
+                return false;
+            }
+        }
+
+        let date = now.naive_local().date();
+        let time = now.time();
+        let resolve = |spec: &Option<TimeSpec>| match spec {
+            Some(spec) => spec
+                .resolve(date, now.offset(), location)
+                .map(Some)
+                .ok_or(()),
+            None => Ok(None),
+        };
+
+        match (resolve(&self.from), resolve(&self.to)) {
+            (Ok(Some(from)), Ok(Some(to))) if from <= to => from <= time && time < to,
+            (Ok(Some(from)), Ok(Some(to))) => time >= from || time < to,
+            (Ok(Some(from)), Ok(None)) => time >= from,
+            (Ok(None), Ok(Some(to))) => time < to,
+            (Ok(None), Ok(None)) => true,
+            _ => {
+                log::warn!(
+                    "Schedule {:?} cannot be resolved for {} (missing location or no sunrise/sunset), ignoring it",
+                    self,
+                    date
+                );
+                false
+            }
+        }
+    }
+
+    /// Next point in time strictly after `now` where this schedule might change state
+    pub fn next_boundary(
+        &self,
+        now: &DateTime<FixedOffset>,
+        location: Option<&Coordinates>,
+    ) -> Option<DateTime<FixedOffset>> {
+        let today = now.naive_local().date();
+        let tomorrow = today.succ();
+        let mut candidates = vec![];
+        for date in &[today, tomorrow] {
+            for spec in self.from.iter().chain(self.to.iter()) {
+                if let Some(time) = spec.resolve(*date, now.offset(), location) {
+                    candidates.push(date.and_time(time));
+                }
+            }
+            if self.weekdays.is_some() {
+                candidates.push(date.and_hms(0, 0, 0));
+            }
+        }
+
+        candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                chrono::TimeZone::from_local_datetime(now.offset(), &candidate).single()
+            })
+            .filter(|candidate| candidate > now)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clock stopped at a given local time
+    struct FixedClock(DateTime<FixedOffset>);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<FixedOffset> {
+            self.0
+        }
+    }
+
+    /// Paris, in summer time
+    const PARIS: Coordinates = Coordinates {
+        latitude: 48.8566,
+        longitude: 2.3522,
+    };
+
+    fn at(date: &str, time: &str) -> FixedClock {
+        let local = format!("{}T{}:00+02:00", date, time);
+        FixedClock(DateTime::parse_from_rfc3339(&local).unwrap())
+    }
+
+    fn schedule(source: &str) -> Schedule {
+        toml::from_str(source).unwrap()
+    }
+
+    fn minutes_between(a: NaiveTime, b: NaiveTime) -> i64 {
+        (a - b).num_minutes().abs()
+    }
+
+    #[test]
+    fn time_range_crossing_midnight() {
+        let night = schedule(
+            r#"from = "22:00"
+to = "06:00""#,
+        );
+        // 2021-06-21 is a Monday
+        assert!(night.is_active(&at("2021-06-21", "23:30").now(), None));
+        assert!(night.is_active(&at("2021-06-21", "00:00").now(), None));
+        assert!(night.is_active(&at("2021-06-21", "05:59").now(), None));
+        assert!(!night.is_active(&at("2021-06-21", "06:00").now(), None));
+        assert!(!night.is_active(&at("2021-06-21", "12:00").now(), None));
+        assert!(night.is_active(&at("2021-06-21", "22:00").now(), None));
+    }
+
+    #[test]
+    fn open_ended_ranges() {
+        let evening = schedule(r#"from = "18:00""#);
+        assert!(!evening.is_active(&at("2021-06-21", "17:59").now(), None));
+        assert!(evening.is_active(&at("2021-06-21", "23:59").now(), None));
+
+        let morning = schedule(r#"to = "09:00""#);
+        assert!(morning.is_active(&at("2021-06-21", "08:59").now(), None));
+        assert!(!morning.is_active(&at("2021-06-21", "09:00").now(), None));
+    }
+
+    #[test]
+    fn weekday_filter() {
+        let weekend = schedule(r#"weekdays = ["sat", "Sunday"]"#);
+        assert!(!weekend.is_active(&at("2021-06-25", "12:00").now(), None));
+        assert!(weekend.is_active(&at("2021-06-26", "12:00").now(), None));
+        assert!(weekend.is_active(&at("2021-06-27", "23:59").now(), None));
+        assert!(!weekend.is_active(&at("2021-06-28", "00:00").now(), None));
+
+        // Weekdays are the current day's: past midnight, a range started on Friday is on Saturday
+        let friday_night = schedule(
+            r#"weekdays = ["fri"]
+from = "22:00"
+to = "02:00""#,
+        );
+        assert!(friday_night.is_active(&at("2021-06-25", "23:00").now(), None));
+        assert!(friday_night.is_active(&at("2021-06-25", "01:00").now(), None));
+        assert!(!friday_night.is_active(&at("2021-06-26", "01:00").now(), None));
+    }
+
+    #[test]
+    fn sunrise_and_sunset() {
+        // Almanac times for Paris on the 2021 summer solstice: sunrise 05:47, sunset 21:58
+        let date = NaiveDate::from_ymd(2021, 6, 21);
+        let offset = FixedOffset::east(2 * 3600);
+        let sunrise = sun_event_time(date, &PARIS, &offset, SunEvent::Sunrise).unwrap();
+        let sunset = sun_event_time(date, &PARIS, &offset, SunEvent::Sunset).unwrap();
+        assert!(minutes_between(sunrise, NaiveTime::from_hms(5, 47, 0)) <= 2);
+        assert!(minutes_between(sunset, NaiveTime::from_hms(21, 58, 0)) <= 2);
+
+        // Winter solstice, in winter time: sunrise 08:41, sunset 16:56
+        let date = NaiveDate::from_ymd(2021, 12, 21);
+        let offset = FixedOffset::east(3600);
+        let sunrise = sun_event_time(date, &PARIS, &offset, SunEvent::Sunrise).unwrap();
+        let sunset = sun_event_time(date, &PARIS, &offset, SunEvent::Sunset).unwrap();
+        assert!(minutes_between(sunrise, NaiveTime::from_hms(8, 41, 0)) <= 2);
+        assert!(minutes_between(sunset, NaiveTime::from_hms(16, 56, 0)) <= 2);
+
+        // Midnight sun in Tromsø
+        let tromso = Coordinates {
+            latitude: 69.6492,
+            longitude: 18.9553,
+        };
+        let date = NaiveDate::from_ymd(2021, 6, 21);
+        assert_eq!(
+            sun_event_time(date, &tromso, &offset, SunEvent::Sunset),
+            None
+        );
+    }
+
+    #[test]
+    fn sun_ranges() {
+        let night = schedule(
+            r#"from = "sunset-00:30"
+to = "sunrise""#,
+        );
+        assert!(night.uses_sun());
+        assert!(!night.is_active(&at("2021-06-21", "21:00").now(), Some(&PARIS)));
+        assert!(night.is_active(&at("2021-06-21", "21:40").now(), Some(&PARIS)));
+        assert!(night.is_active(&at("2021-06-21", "05:30").now(), Some(&PARIS)));
+        assert!(!night.is_active(&at("2021-06-21", "06:00").now(), Some(&PARIS)));
+        // Without a location, sun based schedules never apply
+        assert!(!night.is_active(&at("2021-06-21", "23:00").now(), None));
+    }
+
+    #[test]
+    fn time_spec_round_trip() {
+        for spec in &["22:00", "sunrise", "sunset-00:30", "sunrise+01:15"] {
+            let parsed: TimeSpec = spec.parse().unwrap();
+            assert_eq!(String::from(parsed), *spec);
+        }
+        assert!("sunset*00:30".parse::<TimeSpec>().is_err());
+        assert!("25:00".parse::<TimeSpec>().is_err());
+    }
+
+    #[test]
+    fn next_boundary() {
+        let night = schedule(
+            r#"from = "22:00"
+to = "06:00""#,
+        );
+        let boundary = |clock: FixedClock| {
+            night
+                .next_boundary(&clock.now(), None)
+                .map(|boundary| boundary.to_rfc3339())
+        };
+        assert_eq!(
+            boundary(at("2021-06-21", "12:00")).as_deref(),
+            Some("2021-06-21T22:00:00+02:00")
+        );
+        assert_eq!(
+            boundary(at("2021-06-21", "22:00")).as_deref(),
+            Some("2021-06-22T06:00:00+02:00")
+        );
+        assert_eq!(
+            boundary(at("2021-06-21", "03:00")).as_deref(),
+            Some("2021-06-21T06:00:00+02:00")
+        );
+
+        // Weekday filters change at midnight
+        let weekend = schedule(r#"weekdays = ["sat", "sun"]"#);
+        assert_eq!(
+            weekend
+                .next_boundary(&at("2021-06-25", "12:00").now(), None)
+                .map(|boundary| boundary.to_rfc3339())
+                .as_deref(),
+            Some("2021-06-26T00:00:00+02:00")
+        );
+
+        // Sunset tomorrow once today's has passed
+        let evening = schedule(r#"from = "sunset""#);
+        let next = evening
+            .next_boundary(&at("2021-06-21", "23:00").now(), Some(&PARIS))
+            .unwrap();
+        assert_eq!(next.date().naive_local(), NaiveDate::from_ymd(2021, 6, 22));
+        assert!(minutes_between(next.time(), NaiveTime::from_hms(21, 58, 0)) <= 2);
+
+        // Always active schedules never change
+        assert_eq!(
+            schedule("").next_boundary(&at("2021-06-21", "12:00").now(), None),
+            None
+        );
+    }
+
+    #[test]
+    fn next_config_boundary() {
+        let config = crate::config::Config::fixture(
+            r#"desktop_vibrance = 50
+
+[[desktop_schedules]]
+vibrance = 40
+when = { from = "22:00", to = "07:00" }
+
+[[program_settings]]
+exe_name = "game.exe"
+vibrance = 80
+schedule = { from = "18:30" }
+"#,
+        );
+        let boundary = |clock: FixedClock| {
+            config
+                .next_schedule_boundary(&clock.now())
+                .map(|boundary| boundary.to_rfc3339())
+        };
+        assert_eq!(
+            boundary(at("2021-06-21", "12:00")).as_deref(),
+            Some("2021-06-21T18:30:00+02:00")
+        );
+        assert_eq!(
+            boundary(at("2021-06-21", "20:00")).as_deref(),
+            Some("2021-06-21T22:00:00+02:00")
+        );
+        assert_eq!(
+            boundary(at("2021-06-21", "23:00")).as_deref(),
+            Some("2021-06-22T07:00:00+02:00")
+        );
+        assert_eq!(
+            config.desktop_vibrance_at(&at("2021-06-21", "23:00").now()),
+            40
+        );
+        assert_eq!(
+            config.desktop_vibrance_at(&at("2021-06-21", "12:00").now()),
+            50
+        );
+    }
+
+    #[test]
+    fn parse_test_time() {
+        let now = at("2021-06-21", "12:00").now();
+        assert_eq!(
+            parse_time("23:15", &now).unwrap().to_rfc3339(),
+            "2021-06-21T23:15:00+02:00"
+        );
+        assert_eq!(
+            parse_time("2021-06-26T10:00:00+02:00", &now)
+                .unwrap()
+                .to_rfc3339(),
+            "2021-06-26T10:00:00+02:00"
+        );
+        assert!(parse_time("noon", &now).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::error::{VividError, VividResult};
+use crate::foreground_watch::{ForegroundWatcher, ForegroundWatcherEventKind};
+use crate::schedule::{Clock as _, SystemClock};
+use winapi::shared::{
+    basetsd::UINT_PTR,
+    minwindef::{DWORD, UINT},
+    ntdef::NULL,
+    windef::HWND,
+};
+use winapi::um::winuser;
+
+/// Maximum delay between two wake-ups, so sleep/hibernation and clock changes are caught up with
+pub const MAX_WAIT_MS: UINT = 15 * 60 * 1000;
+
+lazy_static::lazy_static! {
+    /// Current timer and the schedule boundary it's waiting for
+    static ref STATE: parking_lot::Mutex<(UINT_PTR, Option<chrono::DateTime<chrono::FixedOffset>>)> = parking_lot::Mutex::new((0, None));
+}
+
+/// Re-evaluates settings on schedule boundaries, using a timer on the thread's message loop
+#[derive(Debug, Default, Clone)]
+pub struct ScheduleWatcher {
+    registered: bool,
+}
+
+impl ScheduleWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_registered(&self) -> bool {
+        self.registered
+    }
+
+    pub fn register(&mut self) -> VividResult<()> {
+        Self::arm()?;
+        self.registered = true;
+        log::trace!("ScheduleWatcher::register() -> successful");
+        Ok(())
+    }
+
+    pub fn unregister(&mut self) -> VividResult<()> {
+        let mut state = STATE.lock();
+        if state.0 != 0 && unsafe { winuser::KillTimer(NULL as _, state.0) } == 0 {
+            log::error!("ScheduleWatcher::unregister() -> failed");
+            return Err(VividError::windows_error());
+        }
+
+        *state = (0, None);
+        self.registered = false;
+        log::trace!("ScheduleWatcher::unregister() -> successful");
+        Ok(())
+    }
+
+    /// (Re)starts the timer, targeting the next schedule boundary
+    pub(crate) fn arm() -> VividResult<()> {
+        let now = SystemClock.now();
+        let boundary = unsafe { crate::CONFIG.as_ref() }
+            .ok()
+            .and_then(|config| config.next_schedule_boundary(&now));
+        let wait_ms = boundary.map_or(MAX_WAIT_MS, |boundary| {
+            (boundary - now)
+                .num_milliseconds()
+                .max(winuser::USER_TIMER_MINIMUM as i64)
+                .min(MAX_WAIT_MS as i64) as UINT
+        });
+
+        let mut state = STATE.lock();
+        if state.0 != 0 {
+            unsafe { winuser::KillTimer(NULL as _, state.0) };
+        }
+
+        let timer = unsafe { winuser::SetTimer(NULL as _, 0, wait_ms, Some(Self::timer_proc)) };
+        if timer == 0 {
+            *state = (0, None);
+            return Err(VividError::windows_error());
+        }
+
+        log::trace!(
+            "ScheduleWatcher::arm() -> next boundary: {:?}, waking up in {}ms",
+            boundary,
+            wait_ms
+        );
+        *state = (timer, boundary);
+        Ok(())
+    }
+
+    unsafe extern "system" fn timer_proc(_: HWND, _: UINT, _: UINT_PTR, _: DWORD) {
+        let boundary = STATE.lock().1;
+        if boundary.map_or(false, |boundary| SystemClock.now() >= boundary) {
+            log::trace!(
+                "ScheduleWatcher::timer_proc: reached boundary {:?}",
+                boundary
+            );
+            ForegroundWatcher::dispatch_current(ForegroundWatcherEventKind::ScheduleChanged);
+        }
+
+        if let Err(e) = Self::arm() {
+            log::error!("ScheduleWatcher::timer_proc: cannot restart timer: {}", e);
+        }
+    }
+}
+
+impl Drop for ScheduleWatcher {
+    fn drop(&mut self) {
+        if self.registered {
+            let _ = self.unregister();
+        }
+    }
+}
@@ -0,0 +1,1118 @@
+//! Configuration validation: syntax errors with their position, and the semantic checks types can't
+//! express (ranges, displays, video modes, rules that can never apply).
+//! Configurations merged from several files are checked once merged, diagnostics pointing to the file
+//! each value comes from. Values of JSON and YAML files have no known position, only syntax errors do.
+//! Nothing in here touches the OS: connected displays and supported video modes are provided through
+//! an [`Environment`].
+
+use crate::config::{Config, ConfigFragment, Origins, Program, Scope, VideoMode};
+use crate::format::{Format, ParseError};
+use crate::power::PowerCondition;
+use crate::rule_tests::TestCase;
+use crate::rules::{Condition, Rule};
+use crate::schedule::Schedule;
+use chrono::TimeZone as _;
+use std::collections::BTreeMap;
+use toml::Spanned;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Severity {
+    /// The configuration works, but probably not as intended
+    Warning,
+    /// The configuration is refused
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Whether `[[tests]]` are checked. Problems with them only matter to `vivid check` and `vivid test`, they don't
+/// prevent the configuration from loading
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Tests {
+    Checked,
+    Skipped,
+}
+
+/// A configuration file and its contents
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// Path of the file, empty for configurations that don't come from a file
+    pub path: std::path::PathBuf,
+    pub format: Format,
+    pub source: String,
+}
+
+impl SourceFile {
+    fn name(&self) -> Option<std::path::PathBuf> {
+        Some(self.path.clone()).filter(|path| !path.as_os_str().is_empty())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// File the diagnostic points to, if known
+    pub file: Option<std::path::PathBuf>,
+    /// Line and column (starting at 1) the diagnostic points to, if known
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn from_parse(error: ParseError, file: Option<std::path::PathBuf>) -> Self {
+        Self {
+            severity: Severity::Error,
+            file,
+            position: error.position,
+            message: error.message,
+        }
+    }
+}
+
+/// Writes a file and a position the way compilers do, i.e. `vivid.toml:12:5`
+fn write_location(
+    f: &mut std::fmt::Formatter<'_>,
+    file: Option<&std::path::Path>,
+    position: Option<(usize, usize)>,
+) -> std::fmt::Result {
+    match (file, position) {
+        (Some(file), Some((line, column))) => write!(f, "{}:{}:{}", file.display(), line, column),
+        (Some(file), None) => write!(f, "{}", file.display()),
+        (None, Some((line, column))) => write!(f, "line {}, column {}", line, column),
+        (None, None) => write!(f, "<unknown>"),
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.file.is_some() || self.position.is_some() {
+            write_location(f, self.file.as_deref(), self.position)?;
+            write!(f, ": ")?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Where an entry of the merged configuration has been declared
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    /// The entry, i.e. `rules[0] (Streaming)`
+    pub entry: String,
+    pub file: Option<std::path::PathBuf>,
+    pub position: Option<(usize, usize)>,
+}
+
+impl std::fmt::Display for Provenance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} from ", self.entry)?;
+        write_location(f, self.file.as_deref(), self.position)
+    }
+}
+
+/// What the system offers. Unknown parts aren't checked
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    /// Device names of the connected monitors (i.e. `\\.\DISPLAY1`)
+    pub displays: Option<Vec<String>>,
+    /// Video modes supported by the primary monitor
+    pub video_modes: Option<Vec<VideoMode>>,
+}
+
+/// Outcome of a validation
+#[derive(Debug)]
+pub struct Report {
+    /// The parsed configuration, unless it has syntax or type errors
+    pub config: Option<Config>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Where each rule and program entry comes from, in evaluation order
+    pub provenance: Vec<Provenance>,
+    /// Files the keys of the merged configuration come from
+    pub origins: Origins,
+}
+
+impl Report {
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+}
+
+type SpannedTable = Spanned<BTreeMap<String, Spanned<toml::Value>>>;
+
+/// Positions of the values of the configuration file.
+/// Tables declared with a `[header]` have no position, the closest known one is used instead
+#[derive(Debug, Default, serde::Deserialize)]
+struct SourceMap {
+    desktop_vibrance: Option<Spanned<toml::Value>>,
+    resolution: Option<Spanned<toml::Value>>,
+    location: Option<Spanned<toml::Value>>,
+    #[serde(default)]
+    program_settings: Vec<SpannedTable>,
+    #[serde(default)]
+    rules: Vec<SpannedTable>,
+    #[serde(default)]
+    desktop_schedules: Vec<SpannedTable>,
+    #[serde(default)]
+    tests: Vec<SpannedTable>,
+}
+
+/// Location of a value in the configuration, i.e. `rules[2].vibrance`
+#[derive(Debug, Clone, Copy)]
+struct Key<'a> {
+    section: &'a str,
+    index: Option<usize>,
+    field: Option<&'a str>,
+}
+
+impl<'a> Key<'a> {
+    fn top(section: &'a str) -> Self {
+        Self {
+            section,
+            index: None,
+            field: None,
+        }
+    }
+
+    fn entry(section: &'a str, index: usize) -> Self {
+        Self {
+            section,
+            index: Some(index),
+            field: None,
+        }
+    }
+
+    fn field(self, field: &'a str) -> Self {
+        Self {
+            field: Some(field),
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for Key<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}", self.section)?;
+        if let Some(index) = self.index {
+            write!(f, "[{}]", index)?;
+        }
+        if let Some(field) = self.field {
+            write!(f, ".{}", field)?;
+        }
+        write!(f, "`")
+    }
+}
+
+/// A rule set as declared by the user: an explicit rule, or a `program_settings` entry
+struct Entry<'a> {
+    key: Key<'a>,
+    /// Key within its file and label, i.e. `rules[0]` (Streaming)
+    name: String,
+    /// Name of its file, when there are several
+    file_name: Option<String>,
+    rules: Vec<Rule>,
+}
+
+impl std::fmt::Display for Entry<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.file_name {
+            Some(file_name) => write!(f, "{} in {}", self.name, file_name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+struct Checker<'a> {
+    files: &'a [SourceFile],
+    maps: Vec<SourceMap>,
+    origins: Origins,
+    /// Keys of each file that aren't part of the configuration
+    unknown_keys: Vec<Vec<String>>,
+    environment: &'a Environment,
+    tests: Tests,
+    config: &'a Config,
+    diagnostics: Vec<Diagnostic>,
+    provenance: Vec<Provenance>,
+}
+
+/// Parses, merges and validates configuration files, provided in merge order.
+/// Files written for older versions of the format are upgraded first
+pub fn check_files(files: &[SourceFile], environment: &Environment, tests: Tests) -> Report {
+    let mut fragments = vec![];
+    let mut diagnostics = vec![];
+    let mut upgraded = vec![];
+    for file in files {
+        let source = match crate::migrate::upgrade_layout(&file.source, file.format) {
+            Ok(migrated) => migrated.source,
+            Err(message) => {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    file: file.name(),
+                    position: crate::migrate::version_offset(&file.source, file.format)
+                        .map(|offset| line_col(&file.source, offset)),
+                    message,
+                });
+                file.source.clone()
+            }
+        };
+        upgraded.push(SourceFile {
+            path: file.path.clone(),
+            format: file.format,
+            source,
+        });
+    }
+
+    let files = &upgraded[..];
+    let mut unknown_keys = vec![];
+    for file in files {
+        match file
+            .format
+            .parse_with_unknown_keys::<ConfigFragment>(&file.source)
+        {
+            Ok((fragment, unknown)) => {
+                fragments.push(fragment);
+                // The version is only read by migrations
+                unknown_keys.push(unknown.into_iter().filter(|key| key != "version").collect());
+            }
+            Err(e) => diagnostics.push(Diagnostic::from_parse(e, file.name())),
+        }
+    }
+
+    let merged = if diagnostics.is_empty() {
+        Config::merge(fragments).map_err(|message| Diagnostic {
+            severity: Severity::Error,
+            file: None,
+            position: None,
+            message,
+        })
+    } else {
+        Err(diagnostics.remove(0))
+    };
+
+    let (config, origins) = match merged {
+        Ok(merged) => merged,
+        Err(diagnostic) => {
+            diagnostics.insert(0, diagnostic);
+            return Report {
+                config: None,
+                diagnostics,
+                provenance: vec![],
+                origins: Origins::default(),
+            };
+        }
+    };
+
+    let (diagnostics, provenance, origins) = {
+        let mut checker = Checker {
+            files,
+            maps: files
+                .iter()
+                .map(|file| match file.format {
+                    Format::Toml => toml::from_str(&file.source).unwrap_or_default(),
+                    Format::Json | Format::Yaml => SourceMap::default(),
+                })
+                .collect(),
+            origins,
+            unknown_keys,
+            environment,
+            tests,
+            config: &config,
+            diagnostics: vec![],
+            provenance: vec![],
+        };
+        checker.check();
+        (checker.diagnostics, checker.provenance, checker.origins)
+    };
+
+    Report {
+        config: Some(config),
+        diagnostics,
+        provenance,
+        origins,
+    }
+}
+
+/// Flattens nested `all` conditions
+fn conjuncts(condition: &Condition) -> Vec<&Condition> {
+    match condition {
+        Condition::All(conditions) => conditions.iter().flat_map(conjuncts).collect(),
+        condition => vec![condition],
+    }
+}
+
+/// Tells why a condition can never match, if it can't
+fn never_matches(condition: &Condition) -> Option<String> {
+    match condition {
+        Condition::Any(conditions) if conditions.is_empty() => {
+            Some("`any` has no condition to match".into())
+        }
+        Condition::Any(conditions) => {
+            let reasons: Option<Vec<String>> = conditions.iter().map(never_matches).collect();
+            reasons.map(|reasons| reasons.join(", "))
+        }
+        Condition::WindowState(states) if states.is_empty() => {
+            Some("`window_state` lists no state".into())
+        }
+        Condition::Power(PowerCondition {
+            min_battery: Some(min),
+            max_battery: Some(max),
+            ..
+        }) if min > max => Some(format!(
+            "`min_battery` ({}) is above `max_battery` ({})",
+            min, max
+        )),
+        Condition::Schedule(Schedule {
+            weekdays: Some(weekdays),
+            ..
+        }) if weekdays.is_empty() => Some("the schedule has no weekday".into()),
+        Condition::All(_) => {
+            let conditions = conjuncts(condition);
+            if let Some(reason) = conditions.iter().find_map(|c| never_matches(c)) {
+                return Some(reason);
+            }
+
+            for (index, a) in conditions.iter().enumerate() {
+                for b in &conditions[index + 1..] {
+                    match (a, b) {
+                        (Condition::Exe(a), Condition::Exe(b)) if !a.eq_ignore_ascii_case(b) => {
+                            return Some(format!("the exe can't be both {} and {}", a, b))
+                        }
+                        (Condition::Display(a), Condition::Display(b))
+                            if !a.eq_ignore_ascii_case(b) =>
+                        {
+                            return Some(format!("the display can't be both {} and {}", a, b))
+                        }
+                        (Condition::Not(a), b) | (b, Condition::Not(a)) if **a == **b => {
+                            return Some(format!("it both requires and excludes {:?}", b))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Tells if `a` matches whenever `b` does, as far as a structural comparison can tell
+fn covers(a: &Rule, b: &Rule) -> bool {
+    let b_conditions = conjuncts(&b.when);
+    conjuncts(&a.when)
+        .iter()
+        .all(|condition| b_conditions.contains(condition))
+}
+
+/// Converts a byte offset into a line and a column, both starting at 1
+fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+    (line, column)
+}
+
+impl<'a> Checker<'a> {
+    /// Finds the file a key of the merged configuration comes from, and the key within that file
+    fn locate<'k>(&self, key: Key<'k>) -> (Option<usize>, Key<'k>) {
+        match key.index {
+            None => (self.origins.keys.get(key.section).copied(), key),
+            Some(index) => match self
+                .origins
+                .entries
+                .get(key.section)
+                .and_then(|origins| origins.get(index))
+            {
+                Some(origin) => (
+                    Some(origin.file),
+                    Key {
+                        index: Some(origin.index),
+                        ..key
+                    },
+                ),
+                None => (None, key),
+            },
+        }
+    }
+
+    /// Position of a key in the file it comes from
+    fn position(&self, file: usize, key: Key) -> Option<(usize, usize)> {
+        fn known<T>(spanned: &Spanned<T>) -> Option<usize> {
+            Some(spanned.start()).filter(|_| spanned.end() > 0)
+        }
+
+        let map = self.maps.get(file)?;
+        let offset = match key.index {
+            None => match key.section {
+                "desktop_vibrance" => map.desktop_vibrance.as_ref().and_then(known),
+                "resolution" => map.resolution.as_ref().and_then(known),
+                "location" => map.location.as_ref().and_then(known),
+                _ => None,
+            },
+            Some(index) => {
+                let entries = match key.section {
+                    "program_settings" => &map.program_settings,
+                    "rules" => &map.rules,
+                    "desktop_schedules" => &map.desktop_schedules,
+                    "tests" => &map.tests,
+                    _ => return None,
+                };
+                entries.get(index).and_then(|entry| {
+                    key.field
+                        .and_then(|field| entry.get_ref().get(field))
+                        .and_then(known)
+                        .or_else(|| known(entry))
+                        .or_else(|| entry.get_ref().values().filter_map(known).min())
+                })
+            }
+        };
+
+        offset.map(|offset| line_col(&self.files[file].source, offset))
+    }
+
+    /// File, position, and key within its file of a key of the merged configuration
+    fn resolve(&self, key: Key) -> (Option<std::path::PathBuf>, Option<(usize, usize)>, String) {
+        match self.locate(key) {
+            (Some(file), local_key) => (
+                self.files[file].name(),
+                self.position(file, local_key),
+                local_key.to_string(),
+            ),
+            (None, key) => (None, None, key.to_string()),
+        }
+    }
+
+    /// Describes an entry of the merged configuration, naming its file when there are several
+    fn entry<'k>(&self, key: Key<'k>, label: Option<&str>, rules: Vec<Rule>) -> Entry<'k> {
+        let (file, local_key) = self.locate(key);
+        let mut name = local_key.to_string();
+        if let Some(label) = label {
+            name = format!("{} ({})", name, label);
+        }
+        let file_name = file
+            .filter(|_| self.files.len() > 1)
+            .and_then(|file| self.files[file].path.file_name())
+            .map(|file_name| file_name.to_string_lossy().into_owned());
+        Entry {
+            key,
+            name,
+            file_name,
+            rules,
+        }
+    }
+
+    fn report(&mut self, severity: Severity, key: Key, message: String) {
+        let (file, position, key) = self.resolve(key);
+        self.diagnostics.push(Diagnostic {
+            severity,
+            file,
+            position,
+            message: format!("{}: {}", key, message),
+        });
+    }
+
+    fn check(&mut self) {
+        let config = self.config;
+        self.check_unknown_keys();
+        self.check_vibrance(Key::top("desktop_vibrance"), config.desktop_vibrance());
+        if let Some(mode) = config.desktop_resolution() {
+            self.check_video_mode(Key::top("resolution"), mode);
+        }
+        if let Some(location) = config.location() {
+            if !(-90.0..=90.0).contains(&location.latitude)
+                || !(-180.0..=180.0).contains(&location.longitude)
+            {
+                self.report(
+                    Severity::Error,
+                    Key::top("location"),
+                    format!(
+                        "coordinates {}, {} are out of range, latitude goes from -90 to 90 and longitude from -180 to 180",
+                        location.latitude, location.longitude
+                    ),
+                );
+            }
+        }
+
+        for (index, program) in config.program_settings().iter().enumerate() {
+            self.check_program(Key::entry("program_settings", index), program);
+        }
+
+        for (index, rule) in config.rules().iter().enumerate() {
+            let key = Key::entry("rules", index);
+            self.check_vibrance(key.field("vibrance"), rule.vibrance);
+            if let Some(mode) = &rule.resolution {
+                self.check_video_mode(key.field("resolution"), mode);
+            }
+            self.check_condition(key.field("when"), &rule.when);
+            if let Some(reason) = never_matches(&rule.when) {
+                self.report(
+                    Severity::Warning,
+                    key,
+                    format!("this rule can never match: {}", reason),
+                );
+            }
+        }
+
+        let mut always_active: Option<usize> = None;
+        for (index, schedule) in config.desktop_schedules().iter().enumerate() {
+            let key = Key::entry("desktop_schedules", index);
+            self.check_vibrance(key.field("vibrance"), schedule.vibrance);
+            self.check_schedule(key.field("when"), &schedule.when);
+            if let Some(previous) = always_active {
+                self.report(
+                    Severity::Warning,
+                    key,
+                    format!(
+                        "this schedule is never used, `desktop_schedules[{}]` is always active",
+                        previous
+                    ),
+                );
+            } else if schedule.when == Schedule::default() {
+                always_active = Some(index);
+            }
+        }
+
+        self.check_shadowing();
+
+        if self.tests == Tests::Checked {
+            for (index, test) in config.tests().iter().enumerate() {
+                self.check_test(Key::entry("tests", index), test);
+            }
+        }
+    }
+
+    /// Reports keys the configuration doesn't know about, i.e. misspelled ones, which are ignored
+    fn check_unknown_keys(&mut self) {
+        for (file, keys) in self.unknown_keys.iter().enumerate() {
+            for key in keys {
+                self.diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    file: self.files[file].name(),
+                    position: self.key_position(file, key),
+                    message: format!("`{}`: unknown key, it is ignored", key),
+                });
+            }
+        }
+    }
+
+    /// Position of a key written as `program_settings[0].fulscreen_only`, as far as it's known
+    fn key_position(&self, file: usize, key: &str) -> Option<(usize, usize)> {
+        let (section, rest) = match key.find(|c| c == '[' || c == '.') {
+            Some(index) => key.split_at(index),
+            None => (key, ""),
+        };
+        if !rest.starts_with('[') {
+            // Positions of other top-level keys aren't in the source map
+            let map: BTreeMap<String, Spanned<toml::Value>> = match self.files[file].format {
+                Format::Toml => toml::from_str(&self.files[file].source).ok()?,
+                Format::Json | Format::Yaml => return None,
+            };
+            let value = map.get(section).filter(|value| value.end() > 0)?;
+            return Some(line_col(&self.files[file].source, value.start()));
+        }
+
+        let end = rest.find(']')?;
+        let index = rest[1..end].parse().ok()?;
+        let field = rest[end + 1..]
+            .strip_prefix('.')
+            .and_then(|fields| fields.split(|c| c == '.' || c == '[').next());
+        let key = Key {
+            section,
+            index: Some(index),
+            field,
+        };
+        self.position(file, key)
+    }
+
+    fn check_test(&mut self, key: Key, test: &TestCase) {
+        // Only the format matters here, any day will do
+        let day = chrono::FixedOffset::east(0)
+            .ymd(2000, 1, 1)
+            .and_hms(12, 0, 0);
+        let time_error = test
+            .time
+            .as_ref()
+            .and_then(|time| crate::schedule::parse_time(time, &day).err());
+        if let Some(error) = time_error.or_else(|| test.missing_date(self.config, &day)) {
+            self.report(Severity::Error, key.field("time"), error);
+        }
+        if let Some(battery) = test.battery {
+            if battery > 100 {
+                self.report(
+                    Severity::Error,
+                    key.field("battery"),
+                    format!("battery charge is a percentage, {} is above 100", battery),
+                );
+            }
+        }
+
+        let expect = &test.expect;
+        if let Some(rule) = &expect.rule {
+            let config = self.config;
+            let exists = config
+                .rules()
+                .iter()
+                .filter_map(|rule| rule.name.as_deref())
+                .chain(
+                    config
+                        .program_settings()
+                        .iter()
+                        .map(|program| program.exe_name.as_str()),
+                )
+                .any(|name| name == rule);
+            if !exists {
+                self.report(
+                    Severity::Error,
+                    key.field("expect"),
+                    format!("there's no rule or program named \"{}\"", rule),
+                );
+            }
+        }
+        if let Some(vibrance) = expect.vibrance {
+            self.check_vibrance(key.field("expect"), vibrance);
+        }
+        if expect.vibrance.is_none()
+            && expect.rule.is_none()
+            && expect.desktop.is_none()
+            && expect.resolution.is_none()
+            && expect.sticky.is_none()
+            && expect.ignored.is_none()
+        {
+            self.report(
+                Severity::Warning,
+                key.field("expect"),
+                "this test expects nothing, it always passes".into(),
+            );
+        }
+    }
+
+    fn check_vibrance(&mut self, key: Key, vibrance: u8) {
+        if vibrance > 100 {
+            self.report(
+                Severity::Error,
+                key,
+                format!("vibrance is a percentage, {} is above 100", vibrance),
+            );
+        }
+    }
+
+    fn check_video_mode(&mut self, key: Key, mode: &VideoMode) {
+        if mode.width == 0 || mode.height == 0 || mode.freq == 0 {
+            self.report(
+                Severity::Error,
+                key,
+                format!("{} is not a valid video mode", mode),
+            );
+        } else if let Some(modes) = &self.environment.video_modes {
+            if !modes.contains(mode) {
+                self.report(
+                    Severity::Warning,
+                    key,
+                    format!("{} is not supported by the primary display", mode),
+                );
+            }
+        }
+    }
+
+    fn check_schedule(&mut self, key: Key, schedule: &Schedule) {
+        if schedule.uses_sun() && self.config.location().is_none() {
+            self.report(
+                Severity::Error,
+                key,
+                "sunrise and sunset need a `location` to be set, this schedule is never active"
+                    .into(),
+            );
+        }
+        if schedule.weekdays.as_ref().map_or(false, Vec::is_empty) {
+            self.report(
+                Severity::Warning,
+                key,
+                "no weekday is listed, this schedule is never active".into(),
+            );
+        }
+    }
+
+    fn check_power(&mut self, key: Key, power: &PowerCondition) {
+        for percent in power.min_battery.iter().chain(power.max_battery.iter()) {
+            if *percent > 100 {
+                self.report(
+                    Severity::Error,
+                    key,
+                    format!("battery charge is a percentage, {} is above 100", percent),
+                );
+            }
+        }
+    }
+
+    fn check_condition(&mut self, key: Key, condition: &Condition) {
+        match condition {
+            Condition::All(conditions) | Condition::Any(conditions) => conditions
+                .iter()
+                .for_each(|condition| self.check_condition(key, condition)),
+            Condition::Not(condition) => self.check_condition(key, condition),
+            Condition::Schedule(schedule) => self.check_schedule(key, schedule),
+            Condition::Power(power) => self.check_power(key, power),
+            Condition::Display(display) => {
+                if let Some(displays) = &self.environment.displays {
+                    if !displays
+                        .iter()
+                        .any(|connected| connected.eq_ignore_ascii_case(display))
+                    {
+                        let message = format!(
+                            "display {} is not connected, connected displays are: {}",
+                            display,
+                            displays.join(", ")
+                        );
+                        self.report(Severity::Warning, key, message);
+                    }
+                }
+            }
+            Condition::Exe(exe) | Condition::Running(exe) if exe.is_empty() => {
+                self.report(Severity::Error, key, "program names can't be empty".into())
+            }
+            _ => {}
+        }
+    }
+
+    fn check_program(&mut self, key: Key, program: &Program) {
+        if program.exe_name.is_empty() {
+            self.report(
+                Severity::Error,
+                key.field("exe_name"),
+                "program names can't be empty".into(),
+            );
+        }
+        self.check_vibrance(key.field("vibrance"), program.vibrance);
+        if let Some(window_states) = &program.window_states {
+            for vibrance in [
+                window_states.fullscreen,
+                window_states.borderless,
+                window_states.windowed,
+            ]
+            .iter()
+            .flatten()
+            {
+                self.check_vibrance(key.field("window_states"), *vibrance);
+            }
+        }
+        if let Some(mode) = &program.resolution {
+            self.check_video_mode(key.field("resolution"), mode);
+        }
+        if let Some(schedule) = &program.schedule {
+            self.check_schedule(key.field("schedule"), schedule);
+        }
+        if let Some(power) = &program.power {
+            self.check_power(key.field("power"), power);
+        }
+        if program.scope == Some(Scope::Running) && program.fullscreen_only.is_some() {
+            self.report(
+                Severity::Warning,
+                key.field("fullscreen_only"),
+                "`fullscreen_only` has no effect on programs with the `running` scope".into(),
+            );
+        }
+
+        if let Some(reason) = Rule::from_program(program)
+            .iter()
+            .find_map(|rule| never_matches(&rule.when))
+        {
+            self.report(
+                Severity::Warning,
+                key,
+                format!("these settings can never apply: {}", reason),
+            );
+        }
+    }
+
+    /// Reports entries that never win, because an earlier entry with a higher or equal priority, or a later one
+    /// with a higher priority, matches whenever they do
+    fn check_shadowing(&mut self) {
+        let config = self.config;
+        let mut entries: Vec<Entry> = config
+            .rules()
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                self.entry(
+                    Key::entry("rules", index),
+                    rule.name.as_deref(),
+                    vec![rule.clone()],
+                )
+            })
+            .collect();
+        // Same order as the rule engine: focused programs, then running-scoped ones
+        for &running in &[false, true] {
+            entries.extend(
+                config
+                    .program_settings()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, program)| {
+                        (program.scope.unwrap_or_default() == Scope::Running) == running
+                    })
+                    .map(|(index, program)| {
+                        self.entry(
+                            Key::entry("program_settings", index),
+                            Some(&program.exe_name),
+                            Rule::from_program(program),
+                        )
+                    }),
+            );
+        }
+
+        for entry in &entries {
+            let (file, position, _) = self.resolve(entry.key);
+            self.provenance.push(Provenance {
+                entry: entry.name.clone(),
+                file,
+                position,
+            });
+        }
+
+        for (index, entry) in entries.iter().enumerate() {
+            // Priorities win over declaration order, ties going to the earlier entry
+            let shadowing = entries.iter().enumerate().find(|(other_index, other)| {
+                *other_index != index
+                    && entry.rules.iter().all(|rule| {
+                        other.rules.iter().any(|other_rule| {
+                            let wins = if *other_index < index {
+                                other_rule.priority >= rule.priority
+                            } else {
+                                other_rule.priority > rule.priority
+                            };
+                            wins && covers(other_rule, rule)
+                        })
+                    })
+            });
+
+            if let Some((other_index, other)) = shadowing {
+                let duplicate = other_index < index
+                    && other.rules.len() == entry.rules.len()
+                    && other
+                        .rules
+                        .iter()
+                        .zip(&entry.rules)
+                        .all(|(a, b)| a.priority == b.priority && covers(a, b) && covers(b, a));
+                let message = format!(
+                    "{} never applies, {} {}{}",
+                    entry,
+                    if duplicate {
+                        "it duplicates"
+                    } else {
+                        "it is shadowed by"
+                    },
+                    other,
+                    if duplicate {
+                        ""
+                    } else if other_index < index {
+                        " which matches whenever it does, with a higher or equal priority"
+                    } else {
+                        " which matches whenever it does, with a higher priority"
+                    }
+                );
+                let (file, position, _) = self.resolve(entry.key);
+                self.diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    file,
+                    position,
+                    message,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> SourceFile {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("validate")
+            .join(name);
+        SourceFile {
+            source: std::fs::read_to_string(&path).unwrap(),
+            format: Format::of(&path),
+            path: name.into(),
+        }
+    }
+
+    fn environment() -> Environment {
+        Environment {
+            displays: Some(vec!["\\\\.\\DISPLAY1".into(), "\\\\.\\DISPLAY2".into()]),
+            video_modes: Some(vec![VideoMode {
+                width: 1920,
+                height: 1080,
+                freq: 60,
+            }]),
+        }
+    }
+
+    fn diagnostics(files: &[&str]) -> Vec<String> {
+        let files: Vec<SourceFile> = files.iter().map(|name| fixture(name)).collect();
+        check_files(&files, &environment(), Tests::Checked)
+            .diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            diagnostics(&["ranges.toml"]),
+            [
+                "ranges.toml:1:20: error: `desktop_vibrance`: vibrance is a percentage, 150 is above 100",
+                "ranges.toml:2:14: error: `resolution`: 0x1080@60Hz is not a valid video mode",
+                "ranges.toml:3:12: error: `location`: coordinates 95, 2.35 are out of range, latitude goes from -90 to 90 and longitude from -180 to 180",
+                "ranges.toml:6:12: error: `program_settings[0].exe_name`: program names can't be empty",
+                "ranges.toml:7:12: error: `program_settings[0].vibrance`: vibrance is a percentage, 120 is above 100",
+                // Tables declared with a header have no position, the entry's is used
+                "ranges.toml:10:12: error: `program_settings[1].window_states`: vibrance is a percentage, 101 is above 100",
+                "ranges.toml:12:14: warning: `program_settings[1].resolution`: 1280x720@60Hz is not supported by the primary display",
+                "ranges.toml:13:9: error: `program_settings[1].power`: battery charge is a percentage, 120 is above 100",
+                "ranges.toml:26:8: warning: `rules[1].when`: display \\\\.\\DISPLAY3 is not connected, connected displays are: \\\\.\\DISPLAY1, \\\\.\\DISPLAY2",
+                "ranges.toml:31:11: error: `tests[0].battery`: battery charge is a percentage, 150 is above 100",
+                "ranges.toml:32:10: error: `tests[0].expect`: vibrance is a percentage, 200 is above 100",
+            ]
+        );
+    }
+
+    #[test]
+    fn unreachable_rules() {
+        assert_eq!(
+            diagnostics(&["rules.toml"]),
+            [
+                "rules.toml:37:19: warning: `program_settings[1].fullscreen_only`: `fullscreen_only` has no effect on programs with the `running` scope",
+                "rules.toml:14:8: warning: `rules[2]`: this rule can never match: the exe can't be both a.exe and b.exe",
+                "rules.toml:19:8: warning: `rules[3]`: this rule can never match: `window_state` lists no state, `min_battery` (80) is above `max_battery` (20)",
+                "rules.toml:45:8: warning: `desktop_schedules[1].when`: no weekday is listed, this schedule is never active",
+                "rules.toml:44:12: warning: `desktop_schedules[1]`: this schedule is never used, `desktop_schedules[0]` is always active",
+                // Shadowed by a later rule, as priorities win over declaration order
+                "rules.toml:4:8: warning: `rules[0]` (Streaming) never applies, it is shadowed by `rules[4]` (Game) which matches whenever it does, with a higher priority",
+                // Conditions are compared whatever their order
+                "rules.toml:9:8: warning: `rules[1]` (Streaming again) never applies, it duplicates `rules[0]` (Streaming)",
+                "rules.toml:30:12: warning: `program_settings[0]` (game.exe) never applies, it is shadowed by `rules[4]` (Game) which matches whenever it does, with a higher or equal priority",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_keys() {
+        assert_eq!(
+            diagnostics(&["unknown_keys.toml", "unknown_keys.json"]),
+            [
+                "unknown_keys.toml:3:20: warning: `desktop_vibrence`: unknown key, it is ignored",
+                "unknown_keys.toml:8:18: warning: `program_settings[0].fulscreen_only`: unknown key, it is ignored",
+                "unknown_keys.toml:6:12: warning: `program_settings[0].window_states.fulscreen`: unknown key, it is ignored",
+                "unknown_keys.toml: warning: `ignore.program`: unknown key, it is ignored",
+                // Values of JSON files have no known position
+                "unknown_keys.json: warning: `program_settings[0].fulscreen_only`: unknown key, it is ignored",
+                // Entries of later files come first
+                "unknown_keys.toml:6:12: warning: `program_settings[0]` (game.exe) in unknown_keys.toml never applies, it duplicates `program_settings[0]` (game.exe) in unknown_keys.json",
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_and_warnings() {
+        let files = [fixture("ranges.toml")];
+        let report = check_files(&files, &environment(), Tests::Checked);
+        assert_eq!(report.errors().count(), 9);
+        assert_eq!(report.warnings().count(), 2);
+        assert!(report.has_errors());
+        // Semantic errors still give the parsed configuration
+        assert!(report.config.is_some());
+
+        let files = [fixture("rules.toml")];
+        let report = check_files(&files, &environment(), Tests::Checked);
+        assert!(!report.has_errors());
+        assert_eq!(
+            report
+                .provenance
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "`rules[0]` (Streaming) from rules.toml:4:8",
+                "`rules[1]` (Streaming again) from rules.toml:9:8",
+                "`rules[2]` (Impossible) from rules.toml:14:8",
+                "`rules[3]` (Nowhere) from rules.toml:19:8",
+                "`rules[4]` (Game) from rules.toml:24:8",
+                "`program_settings[0]` (game.exe) from rules.toml:30:12",
+                "`program_settings[1]` (obs64.exe) from rules.toml:34:12",
+            ]
+        );
+    }
+
+    #[test]
+    fn tests_skipped_when_loading() {
+        let files = [fixture("ranges.toml")];
+        let report = check_files(&files, &environment(), Tests::Skipped);
+        assert!(!report
+            .diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.message.starts_with("`tests")));
+        assert_eq!(report.errors().count(), 7);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let file = SourceFile {
+            path: "vivid.toml".into(),
+            format: Format::Toml,
+            source: "desktop_vibrance = 50\n[[rules]\n".into(),
+        };
+        let report = check_files(&[file], &Environment::default(), Tests::Checked);
+        assert!(report.config.is_none());
+        assert_eq!(
+            report.diagnostics[0].to_string(),
+            "vivid.toml:2:9: error: expected a right bracket, found a newline"
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        let rule = |when: &str| -> Rule {
+            toml::from_str(&format!("vibrance = 50\nwhen = {}", when)).unwrap()
+        };
+        let game = rule("{ exe = \"game.exe\" }");
+        let streaming = rule("{ exe = \"game.exe\", running = \"obs64.exe\" }");
+        assert!(covers(&game, &streaming));
+        assert!(!covers(&streaming, &game));
+        assert!(covers(&game, &game));
+
+        assert_eq!(never_matches(&game.when), None);
+        assert_eq!(
+            never_matches(&rule("{ exe = \"game.exe\", not = { exe = \"game.exe\" } }").when),
+            Some("it both requires and excludes Exe(\"game.exe\")".into())
+        );
+        assert_eq!(
+            never_matches(&rule("{ any = [] }").when),
+            Some("`any` has no condition to match".into())
+        );
+        // One alternative that can match is enough
+        assert_eq!(
+            never_matches(&rule("{ any = [{ window_state = [] }, { exe = \"game.exe\" }] }").when),
+            None
+        );
+    }
+}
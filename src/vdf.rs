@@ -0,0 +1,257 @@
+//! Parser for Valve's KeyValues text format, used by Steam's `libraryfolders.vdf` and `appmanifest_*.acf` files:
+//!
+//! ```text
+//! "AppState"
+//! {
+//!     "appid"      "730"
+//!     "installdir" "Counter-Strike Global Offensive"
+//! }
+//! ```
+//!
+//! Keys and values are strings, quoted or not, and values can be nested objects. `//` comments and platform
+//! conditions (`[$WIN32]`) are skipped, `#include` and `#base` directives aren't supported.
+
+/// A value: either a string or an object, whose keys can repeat and keep their order
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Value {
+    String(String),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// First value of a key of an object, keys are case insensitive like Steam does
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries()
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    /// String value of a key of an object
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(Value::as_str)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            Self::Object(_) => None,
+        }
+    }
+
+    /// Entries of an object, none for strings
+    pub fn entries(&self) -> &[(String, Value)] {
+        match self {
+            Self::String(_) => &[],
+            Self::Object(entries) => entries,
+        }
+    }
+}
+
+/// Syntax error, with the line it's on (starting at 1)
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    String(String),
+    Open,
+    Close,
+    Condition,
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, Error> {
+        loop {
+            match self.chars.peek().copied() {
+                None => return Ok(None),
+                Some(c) if c.is_whitespace() => {
+                    self.next_char();
+                }
+                Some('/') => {
+                    self.next_char();
+                    if self.chars.peek() != Some(&'/') {
+                        return Err(self.error("expected a `//` comment"));
+                    }
+                    while !matches!(self.chars.peek(), None | Some('\n')) {
+                        self.next_char();
+                    }
+                }
+                Some('{') => {
+                    self.next_char();
+                    return Ok(Some(Token::Open));
+                }
+                Some('}') => {
+                    self.next_char();
+                    return Ok(Some(Token::Close));
+                }
+                Some('[') => {
+                    while self.next_char().map_or(false, |c| c != ']') {}
+                    return Ok(Some(Token::Condition));
+                }
+                Some('"') => {
+                    self.next_char();
+                    let mut value = String::new();
+                    loop {
+                        match self.next_char() {
+                            None => return Err(self.error("unterminated string")),
+                            Some('"') => break,
+                            Some('\\') => match self.next_char() {
+                                Some('n') => value.push('\n'),
+                                Some('t') => value.push('\t'),
+                                Some(c) => value.push(c),
+                                None => return Err(self.error("unterminated string")),
+                            },
+                            Some(c) => value.push(c),
+                        }
+                    }
+                    return Ok(Some(Token::String(value)));
+                }
+                Some(_) => {
+                    let mut value = String::new();
+                    while let Some(&c) = self.chars.peek() {
+                        if c.is_whitespace() || matches!(c, '{' | '}' | '"' | '[') {
+                            break;
+                        }
+                        value.push(c);
+                        self.next_char();
+                    }
+                    return Ok(Some(Token::String(value)));
+                }
+            }
+        }
+    }
+
+    /// Next token, platform conditions following keys and values being skipped
+    fn next_significant(&mut self) -> Result<Option<Token>, Error> {
+        loop {
+            match self.next_token()? {
+                Some(Token::Condition) => continue,
+                token => return Ok(token),
+            }
+        }
+    }
+
+    /// Entries until the closing brace, or the end of the file at the top level
+    fn entries(&mut self, top_level: bool) -> Result<Vec<(String, Value)>, Error> {
+        let mut entries = vec![];
+        loop {
+            let key = match self.next_significant()? {
+                Some(Token::String(key)) => key,
+                Some(Token::Close) if !top_level => return Ok(entries),
+                None if top_level => return Ok(entries),
+                None => return Err(self.error("missing `}`")),
+                Some(_) => return Err(self.error("expected a key")),
+            };
+            let value = match self.next_significant()? {
+                Some(Token::String(value)) => Value::String(value),
+                Some(Token::Open) => Value::Object(self.entries(false)?),
+                _ => return Err(self.error(format!("expected a value for \"{}\"", key))),
+            };
+            entries.push((key, value));
+        }
+    }
+}
+
+/// Parses a file, its top level keys being the entries of the returned object
+pub fn parse(source: &str) -> Result<Value, Error> {
+    let mut lexer = Lexer {
+        chars: source.trim_start_matches('\u{feff}').chars().peekable(),
+        line: 1,
+    };
+    lexer.entries(true).map(Value::Object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Value {
+        Value::String(value.into())
+    }
+
+    #[test]
+    fn syntax() {
+        let root = parse(include_str!("../tests/fixtures/vdf/syntax.vdf")).unwrap();
+        let object = root.get("root").unwrap();
+        assert_eq!(
+            object.get_str("quoted"),
+            Some("a \"quoted\" value\twith a tab\\nand no newline")
+        );
+        assert_eq!(object.get_str("unquoted"), Some("value"));
+        assert_eq!(object.get_str("path"), Some("C:\\Games\\Steam"));
+        // Conditions are skipped, whatever the platform
+        assert_eq!(object.get_str("windows"), Some("d3d11"));
+        assert_eq!(object.get_str("linux"), Some("vulkan"));
+        // Repeated keys are kept, in order
+        assert_eq!(
+            object.get("nested"),
+            Some(&Value::Object(vec![
+                ("key".into(), string("value")),
+                ("KEY".into(), string("again")),
+            ]))
+        );
+        assert_eq!(object.get("nested").unwrap().get_str("Key"), Some("value"));
+        assert_eq!(object.get("empty"), Some(&Value::Object(vec![])));
+        assert_eq!(object.get("missing"), None);
+        assert_eq!(object.get_str("nested"), None);
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        assert_eq!(
+            parse("\u{feff}\"key\" \"value\"").unwrap(),
+            Value::Object(vec![("key".into(), string("value"))])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source: &str| parse(source).unwrap_err().to_string();
+        assert_eq!(
+            error("\"AppState\"\n{\n\t\"name\"\t\"Portal 2\n}\n"),
+            "line 5: unterminated string"
+        );
+        assert_eq!(
+            error("\"AppState\"\n{\n\t\"name\"\t\"Portal 2\"\n"),
+            "line 4: missing `}`"
+        );
+        assert_eq!(
+            error("\"AppState\"\n{\n\t\"name\"\n}\n"),
+            "line 4: expected a value for \"name\""
+        );
+        assert_eq!(error("\"key\" \"value\"\n}"), "line 2: expected a key");
+        assert_eq!(error("/ comment"), "line 1: expected a `//` comment");
+    }
+}
@@ -0,0 +1,362 @@
+//! Import of VibranceGUI settings. VibranceGUI saves them in `%APPDATA%\vibranceGUI\vibranceGUI.ini`,
+//! a `[Settings]` section holding the vibrance to use outside of listed programs and the program list as JSON:
+//!
+//! ```ini
+//! [Settings]
+//! inactiveValue=0
+//! refreshRate=5000
+//! affectPrimaryMonitorOnly=False
+//! neverSwitchResolution=False
+//! applicationSettings=[{"Name":"csgo","FileName":"C:\\Games\\csgo.exe","IngameLevel":40,
+//!   "IsResolutionChangeNeeded":true,"ResolutionSettings":{"dmPelsWidth":1280,"dmPelsHeight":960,"dmDisplayFrequency":144}}]
+//! ```
+//!
+//! NVIDIA vibrance levels go from 0 (the driver default) to 63, they're converted to Vivid's percentages where
+//! 50 is the driver default. Nothing in here touches the OS.
+
+use crate::config::{Program, VideoMode};
+
+/// Highest NVIDIA digital vibrance level
+const MAX_LEVEL: i64 = 63;
+
+/// Default location of the VibranceGUI settings
+pub fn default_path() -> Option<std::path::PathBuf> {
+    std::env::var_os("APPDATA").map(|appdata| {
+        std::path::PathBuf::from(appdata)
+            .join("vibranceGUI")
+            .join("vibranceGUI.ini")
+    })
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApplicationSetting {
+    #[serde(default)]
+    name: String,
+    file_name: String,
+    ingame_level: i64,
+    #[serde(default)]
+    is_resolution_change_needed: bool,
+    resolution_settings: Option<ResolutionSettings>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[allow(non_snake_case)]
+struct ResolutionSettings {
+    dmPelsWidth: u32,
+    dmPelsHeight: u32,
+    dmDisplayFrequency: u32,
+}
+
+/// Settings converted from VibranceGUI, and what couldn't be converted
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Imported {
+    /// Version of the configuration format the settings are written for
+    pub version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub desktop_vibrance: Option<u8>,
+    pub program_settings: Vec<Program>,
+    /// Settings that were left out or changed, and why
+    #[serde(skip)]
+    pub notes: Vec<String>,
+}
+
+/// Converts an NVIDIA vibrance level to a percentage
+fn percent(level: i64) -> Option<u8> {
+    Some(level)
+        .filter(|level| (0..=MAX_LEVEL).contains(level))
+        .map(|level| (50 + (level * 50 + MAX_LEVEL / 2) / MAX_LEVEL) as u8)
+}
+
+/// Decodes the settings file: `WritePrivateProfileString` writes either ANSI or UTF-16 files
+pub fn decode(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => String::from_utf16_lossy(
+            &rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect::<Vec<_>>(),
+        ),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        bytes => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Keys of the `[Settings]` section, in order. Section and key names are case insensitive like Windows does
+fn settings(source: &str) -> Vec<(String, String)> {
+    let mut in_settings = false;
+    let mut settings = vec![];
+    for line in source.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            in_settings = section.trim().eq_ignore_ascii_case("settings");
+            continue;
+        }
+        if let (true, Some(index)) = (in_settings, line.find('=')) {
+            let (key, value) = (&line[..index], &line[index + 1..]);
+            settings.push((key.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    settings
+}
+
+fn is_true(value: &str) -> bool {
+    value.eq_ignore_ascii_case("true") || value == "1"
+}
+
+/// Converts VibranceGUI settings. Errors are only returned when the file isn't VibranceGUI settings at all
+pub fn import(source: &str) -> Result<Imported, String> {
+    let settings = settings(source);
+    if settings.is_empty() {
+        return Err("there's no [Settings] section, this isn't a vibranceGUI.ini file".into());
+    }
+    let never_switch_resolution = settings
+        .iter()
+        .any(|(key, value)| key == "neverswitchresolution" && is_true(value));
+
+    let mut imported = Imported {
+        version: crate::migrate::CURRENT_VERSION,
+        ..Default::default()
+    };
+    for (key, value) in &settings {
+        match key.as_str() {
+            "inactivevalue" => match value.parse().ok().and_then(percent) {
+                Some(vibrance) => imported.desktop_vibrance = Some(vibrance),
+                None => imported.notes.push(format!(
+                    "inactiveValue {} isn't an NVIDIA vibrance level (0 to {}), desktop_vibrance isn't set",
+                    value, MAX_LEVEL
+                )),
+            },
+            "applicationsettings" => {
+                match serde_json::from_str::<Vec<ApplicationSetting>>(value) {
+                    Ok(applications) => {
+                        for application in applications {
+                            import_application(application, never_switch_resolution, &mut imported);
+                        }
+                    }
+                    Err(e) => imported
+                        .notes
+                        .push(format!("the program list can't be read, no program is imported: {}", e)),
+                }
+            }
+            "neverswitchresolution" => {}
+            "refreshrate" => imported.notes.push(
+                "refreshRate isn't needed, Vivid reacts to foreground changes instead of polling".into(),
+            ),
+            "keepactive" if is_true(value) => imported.notes.push(
+                "keepActive has no equivalent, Vivid only changes vibrance when the foreground window changes".into(),
+            ),
+            "affectprimarymonitoronly" if is_true(value) => imported.notes.push(
+                "affectPrimaryMonitorOnly has no equivalent, use a `display` condition in rules instead".into(),
+            ),
+            "keepactive" | "affectprimarymonitoronly" => {}
+            _ => imported.notes.push(format!("{} isn't known, it's left out", key)),
+        }
+    }
+
+    Ok(imported)
+}
+
+fn import_application(
+    application: ApplicationSetting,
+    never_switch_resolution: bool,
+    imported: &mut Imported,
+) {
+    let label = if application.name.is_empty() {
+        application.file_name.clone()
+    } else {
+        application.name.clone()
+    };
+    // Only the file name is kept: Vivid matches programs by name
+    let exe_name = match application
+        .file_name
+        .rsplit(|c| c == '\\' || c == '/')
+        .next()
+    {
+        Some(exe_name) if !exe_name.is_empty() => exe_name.to_string(),
+        _ => {
+            imported
+                .notes
+                .push(format!("{} has no program path, it's left out", label));
+            return;
+        }
+    };
+    if imported
+        .program_settings
+        .iter()
+        .any(|program| program.exe_name.eq_ignore_ascii_case(&exe_name))
+    {
+        imported.notes.push(format!(
+            "{} is listed several times, only the first entry is kept",
+            exe_name
+        ));
+        return;
+    }
+    let vibrance = match percent(application.ingame_level) {
+        Some(vibrance) => vibrance,
+        None => {
+            imported.notes.push(format!(
+                "{} has a vibrance level of {}, which isn't an NVIDIA one (0 to {}), it's left out",
+                exe_name, application.ingame_level, MAX_LEVEL
+            ));
+            return;
+        }
+    };
+
+    let resolution = match (
+        application.is_resolution_change_needed,
+        application.resolution_settings,
+    ) {
+        (true, Some(_)) if never_switch_resolution => {
+            imported.notes.push(format!(
+                "{} switches resolution but neverSwitchResolution is set, the resolution is left out",
+                exe_name
+            ));
+            None
+        }
+        (true, Some(settings)) => Some(VideoMode {
+            width: settings.dmPelsWidth,
+            height: settings.dmPelsHeight,
+            freq: settings.dmDisplayFrequency,
+        }),
+        (true, None) => {
+            imported.notes.push(format!(
+                "{} switches resolution but has no resolution saved, the resolution is left out",
+                exe_name
+            ));
+            None
+        }
+        (false, _) => None,
+    };
+
+    imported.program_settings.push(Program {
+        exe_name,
+        vibrance,
+        fullscreen_only: None,
+        scope: None,
+        sticky: None,
+        priority: None,
+        resolution,
+        window_states: None,
+        schedule: None,
+        power: None,
+        processes: None,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Imported {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("vibrancegui")
+            .join(name);
+        import(&decode(&std::fs::read(path).unwrap())).unwrap()
+    }
+
+    fn programs(imported: &Imported) -> Vec<(&str, u8, Option<String>)> {
+        imported
+            .program_settings
+            .iter()
+            .map(|program| {
+                (
+                    program.exe_name.as_str(),
+                    program.vibrance,
+                    program.resolution.as_ref().map(ToString::to_string),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn levels_to_percentages() {
+        assert_eq!(percent(0), Some(50));
+        assert_eq!(percent(20), Some(66));
+        assert_eq!(percent(40), Some(82));
+        assert_eq!(percent(MAX_LEVEL), Some(100));
+        assert_eq!(percent(-1), None);
+        assert_eq!(percent(MAX_LEVEL + 1), None);
+    }
+
+    #[test]
+    fn decoding() {
+        assert_eq!(decode(b"[Settings]"), "[Settings]");
+        assert_eq!(decode(b"\xEF\xBB\xBF[Settings]"), "[Settings]");
+        assert_eq!(
+            decode(b"\xFF\xFE[\0S\0e\0t\0t\0i\0n\0g\0s\0]\0"),
+            "[Settings]"
+        );
+    }
+
+    #[test]
+    fn ansi_settings() {
+        let imported = fixture("ansi.ini");
+        // Only the [Settings] section is read
+        assert_eq!(imported.desktop_vibrance, Some(50));
+        assert_eq!(
+            programs(&imported),
+            [
+                ("csgo.exe", 82, Some("1280x960@144Hz".into())),
+                ("dota2.exe", 100, None),
+            ]
+        );
+        assert_eq!(
+            imported.notes,
+            [
+                "refreshRate isn't needed, Vivid reacts to foreground changes instead of polling",
+                "affectPrimaryMonitorOnly has no equivalent, use a `display` condition in rules instead",
+            ]
+        );
+    }
+
+    #[test]
+    fn utf16_settings() {
+        let imported = fixture("utf16.ini");
+        assert_eq!(imported.desktop_vibrance, Some(66));
+        assert_eq!(programs(&imported), [("café.exe", 75, None)]);
+        assert_eq!(
+            imported.notes,
+            ["café.exe switches resolution but has no resolution saved, the resolution is left out"]
+        );
+    }
+
+    #[test]
+    fn duplicate_and_invalid_entries() {
+        let imported = fixture("invalid.ini");
+        assert_eq!(imported.desktop_vibrance, None);
+        assert_eq!(programs(&imported), [("csgo.exe", 66, None)]);
+        assert_eq!(
+            imported.notes,
+            [
+                "inactiveValue 64 isn't an NVIDIA vibrance level (0 to 63), desktop_vibrance isn't set",
+                "keepActive has no equivalent, Vivid only changes vibrance when the foreground window changes",
+                "hotkey isn't known, it's left out",
+                "csgo.exe switches resolution but neverSwitchResolution is set, the resolution is left out",
+                "CSGO.EXE is listed several times, only the first entry is kept",
+                "broken has no program path, it's left out",
+                "vivid.exe has a vibrance level of 64, which isn't an NVIDIA one (0 to 63), it's left out",
+                "witcher3.exe has a vibrance level of -1, which isn't an NVIDIA one (0 to 63), it's left out",
+            ]
+        );
+    }
+
+    #[test]
+    fn not_vibrancegui_settings() {
+        assert!(import("[Window]\nwidth=800\n").is_err());
+        assert!(import("applicationSettings=[]\n").is_err());
+
+        let imported = import("[Settings]\napplicationSettings=[{\"Name\":\"csgo\"}]\n").unwrap();
+        assert!(imported.program_settings.is_empty());
+        assert_eq!(imported.notes.len(), 1);
+        assert!(imported.notes[0].starts_with("the program list can't be read"));
+    }
+}
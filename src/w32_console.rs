@@ -0,0 +1,12 @@
+use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+/// Release builds use the windows subsystem and don't get a console.
+/// Attaches to the console of the parent process (i.e. cmd/powershell) so subcommands can print their output
+pub fn attach_parent_console() {
+    if unsafe { AttachConsole(ATTACH_PARENT_PROCESS) } == 0 {
+        log::trace!(
+            "no parent console to attach to: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
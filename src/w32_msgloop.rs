@@ -6,7 +6,8 @@ pub fn read_message(msg: &mut winapi::um::winuser::MSG) -> VividResult<()> {
     let message_result =
         unsafe { winapi::um::winuser::GetMessageA(msg, winapi::shared::ntdef::NULL as _, 0, 0) };
 
-    if message_result != 0 {
+    // 0 is returned for WM_QUIT and any other message is positive, including timer ticks
+    if message_result == -1 {
         return Err(VividError::message_loop_error());
     }
 